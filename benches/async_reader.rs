@@ -0,0 +1,93 @@
+//! Measures throughput and latency of `AsyncReader`, with a plain `mpsc` channel of bytes (what
+//! the reader thread used to send through) as the baseline.
+//!
+//! Run with `cargo bench --bench async_reader`.
+
+// Like the reader thread, the baseline reads byte by byte.
+#![allow(clippy::unbuffered_bytes)]
+
+use std::io::{self, Read, Write};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use pres::AsyncReader;
+
+const THROUGHPUT_BYTES: usize = 16 * 1024 * 1024;
+const LATENCY_SAMPLES: usize = 10_000;
+
+fn report(name: &str, bytes: usize, elapsed: Duration) {
+    let mib_per_sec = bytes as f64 / (1024.0 * 1024.0) / elapsed.as_secs_f64();
+    println!("{:<24} {:>10.1} MiB/s", name, mib_per_sec);
+}
+
+fn throughput_async_reader() {
+    let mut reader = AsyncReader::from_read(io::repeat(b'x').take(THROUGHPUT_BYTES as u64));
+    let mut buf = [0; 4096];
+    let mut total = 0;
+
+    let start = Instant::now();
+    while total < THROUGHPUT_BYTES {
+        total += reader.read(&mut buf).unwrap();
+    }
+    report("throughput AsyncReader", total, start.elapsed());
+}
+
+fn throughput_mpsc() {
+    let (send, recv) = mpsc::channel();
+    thread::spawn(move || for byte in io::repeat(b'x').take(THROUGHPUT_BYTES as u64).bytes() {
+        if send.send(byte).is_err() {
+            return;
+        }
+    });
+    let mut total = 0;
+
+    let start = Instant::now();
+    while total < THROUGHPUT_BYTES {
+        if let Ok(Ok(_)) = recv.try_recv() {
+            total += 1;
+        }
+    }
+    report("throughput mpsc", total, start.elapsed());
+}
+
+/// Time from writing a byte into a pipe until a polling consumer sees it.
+fn latency(name: &str, mut poll: impl FnMut() -> bool, mut input: io::PipeWriter) {
+    let mut samples = Vec::with_capacity(LATENCY_SAMPLES);
+    for _ in 0..LATENCY_SAMPLES {
+        let start = Instant::now();
+        input.write_all(b"x").unwrap();
+        while !poll() {}
+        samples.push(start.elapsed());
+    }
+    samples.sort();
+    println!("{:<24} p50 {:>10?} p99 {:>10?}",
+             name,
+             samples[LATENCY_SAMPLES / 2],
+             samples[LATENCY_SAMPLES * 99 / 100]);
+}
+
+fn latency_async_reader() {
+    let (output, input) = io::pipe().unwrap();
+    let mut reader = AsyncReader::from_read(output);
+    let mut buf = [0; 1];
+    latency("latency AsyncReader", || reader.read(&mut buf).unwrap() == 1, input);
+}
+
+fn latency_mpsc() {
+    let (output, input) = io::pipe().unwrap();
+    let (send, recv) = mpsc::channel();
+    thread::spawn(move || for byte in output.bytes() {
+        if send.send(byte).is_err() {
+            return;
+        }
+    });
+    latency("latency mpsc", || recv.try_recv().is_ok(), input);
+}
+
+fn main() {
+    throughput_async_reader();
+    throughput_mpsc();
+    latency_async_reader();
+    latency_mpsc();
+}
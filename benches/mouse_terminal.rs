@@ -0,0 +1,53 @@
+//! Measures the cost of writing a frame through `MouseTerminal`.
+//!
+//! Run with `cargo bench --bench mouse_terminal`. The output target counts the writes it
+//! receives, each of which would be a system call on a real unbuffered stream.
+
+use std::io::{self, Write};
+use std::time::Instant;
+
+use pres::cursor::Goto;
+use pres::input::MouseTerminal;
+
+const FRAMES: usize = 1000;
+
+/// A sink standing in for an unbuffered terminal.
+#[derive(Default)]
+struct CountingSink {
+    writes: usize,
+}
+
+impl Write for CountingSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Draw a 80x24 frame cell by cell, the worst case for an unbuffered writer.
+fn draw<W: Write>(term: &mut MouseTerminal<W>) {
+    for frame in 0..FRAMES {
+        for y in 1..=24 {
+            for x in 1..=80 {
+                write!(term, "{}{}", Goto(x, y), frame % 10).unwrap();
+            }
+        }
+        term.flush().unwrap();
+    }
+}
+
+fn run(name: &str, mut term: MouseTerminal<CountingSink>) {
+    let start = Instant::now();
+    draw(&mut term);
+    let elapsed = start.elapsed();
+    println!("{:<12} {:>10} writes {:>12?} per frame", name, term.writes, elapsed / FRAMES as u32);
+}
+
+fn main() {
+    run("unbuffered", MouseTerminal::from(CountingSink::default()));
+    run("buffered", MouseTerminal::buffered(CountingSink::default()));
+}
@@ -2,7 +2,7 @@ extern crate pres;
 
 use std::io::{stdin, stdout, Write};
 
-use pres::event::{Event, Key, MouseEvent};
+use pres::event::{Event, KeyCode, KeyEvent, MouseEvent, MouseEventEx};
 use pres::input::{MouseTerminal, TermRead};
 use pres::raw::IntoRawMode;
 
@@ -20,14 +20,9 @@ fn main() {
     for c in stdin.events() {
         let event = c.unwrap();
         match event {
-            Event::Key(Key::Char('q')) => break,
-            Event::Mouse(mouse_event) => {
-                match mouse_event {
-                    MouseEvent::Press(_, x, y) => {
-                        write!(stdout, "{}x", pres::cursor::Goto(x, y)).unwrap();
-                    }
-                    _ => (),
-                }
+            Event::Key(KeyEvent { code: KeyCode::Char('q'), .. }) => break,
+            Event::Mouse(MouseEventEx { event: MouseEvent::Press(_, x, y), .. }) => {
+                write!(stdout, "{}x", pres::cursor::Goto(x, y)).unwrap();
             }
             _ => {}
         }
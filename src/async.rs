@@ -1,51 +1,138 @@
+use std::fs::File;
 use std::io::{self, Read};
-use std::sync::mpsc;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
+use crate::event::{Events, EventsAndRaw};
+use crate::event::events::RawBytes;
+use crate::idle;
+use crate::input::TermReadEventsAndRaw;
+use crate::metrics;
+use crate::ring::{self, Producer};
+use crate::sys::signal;
+use crate::sys::size::terminal_size_of;
 use crate::sys::tty::get_tty;
 
+/// The number of bytes buffered between the reader thread and the `AsyncReader`.
+const QUEUE_CAPACITY: usize = 64 * 1024;
+
 /// Construct an asynchronous handle to the TTY standard input, with a delimiter byte.
 ///
 /// This has the same advantages as async_stdin(), but also allows specifying a delimiter byte. The
 /// reader will stop reading after consuming the delimiter byte.
 pub fn async_stdin_until(delimiter: u8) -> AsyncReader {
-    let (send, recv) = mpsc::channel();
-
-    thread::spawn(move || for i in get_tty().unwrap().bytes() {
-        match i {
-            Ok(byte) => {
-                let end_of_stream = &byte == &delimiter;
-                let send_error = send.send(Ok(byte)).is_err();
-
-                if end_of_stream || send_error { return; }
-            }
-            Err(_) => { return; }
-        }
-    });
-
-    AsyncReader { recv: recv }
+    spawn_tty_reader(Some(delimiter))
 }
 
 /// Construct an asynchronous handle to the TTY standard input.
 ///
 /// This allows you to read from standard input _without blocking_ the current thread.
 /// Specifically, it works by firing up another thread to handle the event stream, which will then
-/// be buffered in a mpsc queue, which will eventually be read by the current thread.
+/// be buffered in a lock-free queue, which will eventually be read by the current thread.
 ///
 /// This will not read the piped standard input, but rather read from the TTY device, since reading
 /// asyncronized from piped input would rarely make sense. In other words, if you pipe standard
 /// output from another process, it won't be reflected in the stream returned by this function, as
 /// this represents the TTY device, and not the piped standard input.
 pub fn async_stdin() -> AsyncReader {
-    let (send, recv) = mpsc::channel();
+    spawn_tty_reader(None)
+}
 
-    thread::spawn(move || for i in get_tty().unwrap().bytes() {
-        if send.send(i).is_err() {
-            return;
-        }
+/// The window size of the last resize not reported yet, shared by the reader thread of
+/// `async_stdin_with_resize` and the events read from it.
+pub(crate) type PendingResize = Arc<Mutex<Option<(u16, u16)>>>;
+
+/// Construct an asynchronous handle to the TTY standard input, which also reports window size
+/// changes.
+///
+/// This is `async_stdin()`, with a `SIGWINCH` handler added: each time the window is resized, the
+/// `Events` from `AsyncReader::events` yield an `Event::Resize`, apart from the input bytes, so a
+/// resize never lands in the middle of a sequence. The handler is shared by the whole process, so
+/// only one such reader should exist at a time.
+pub fn async_stdin_with_resize() -> AsyncReader {
+    let (producer, consumer) = ring::channel(QUEUE_CAPACITY);
+    let pending = PendingResize::default();
+
+    let shared = pending.clone();
+    thread::spawn(move || match get_tty().and_then(|tty| Ok((tty, signal::resize_pipe()?))) {
+        Ok((tty, resize)) => pump_with_resize(tty, resize, producer, shared),
+        Err(e) => producer.fail(e),
     });
 
-    AsyncReader { recv: recv }
+    AsyncReader { recv: consumer, resize: Some(pending) }
+}
+
+fn spawn_tty_reader(delimiter: Option<u8>) -> AsyncReader {
+    let (producer, consumer) = ring::channel(QUEUE_CAPACITY);
+
+    thread::spawn(move || match get_tty() {
+        Ok(tty) => pump(tty, producer, delimiter, true),
+        Err(e) => producer.fail(e),
+    });
+
+    AsyncReader { recv: consumer, resize: None }
+}
+
+/// Move bytes from `source` to `producer` until the end of the stream, an error, the delimiter,
+/// or the `AsyncReader` going away.
+///
+/// Input from the TTY (`tty`) counts as user activity, see `idle`.
+fn pump<R: Read>(mut source: R, producer: Producer, delimiter: Option<u8>, tty: bool) {
+    // With a delimiter, read byte by byte so nothing after it is taken from the source.
+    let mut buf = [0; 1024];
+    let chunk = if delimiter.is_some() { 1 } else { buf.len() };
+
+    loop {
+        match source.read(&mut buf[..chunk]) {
+            Ok(0) => return,
+            Ok(n) => {
+                if tty {
+                    idle::record_tty_input();
+                }
+                let end_of_stream = n == 1 && Some(buf[0]) == delimiter;
+                let send_error = producer.push(&buf[..n]).is_err();
+
+                if end_of_stream || send_error { return; }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => {
+                producer.fail(e);
+                return;
+            }
+        }
+    }
+}
+
+/// Like `pump`, but also record the window size in `pending` whenever the resize pipe `resize` is
+/// notified.
+fn pump_with_resize(mut source: File, resize: RawFd, producer: Producer, pending: PendingResize) {
+    let mut buf = [0; 1024];
+
+    loop {
+        let resized = match signal::wait_input_or_resize(source.as_raw_fd(), resize) {
+            Ok(resized) => resized,
+            Err(e) => return producer.fail(e),
+        };
+        if resized {
+            signal::drain_resize_pipe(resize);
+            if let Ok(size) = terminal_size_of(source.as_raw_fd()) {
+                // Only the last size matters, so an unreported one is replaced.
+                *pending.lock().unwrap() = Some(size);
+            }
+            continue;
+        }
+
+        match source.read(&mut buf) {
+            Ok(0) => return,
+            Ok(n) => {
+                idle::record_tty_input();
+                if producer.push(&buf[..n]).is_err() { return; }
+            }
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return producer.fail(e),
+        }
+    }
 }
 
 /// An asynchronous reader.
@@ -53,11 +140,48 @@ pub fn async_stdin() -> AsyncReader {
 /// This acts as any other stream, with the exception that reading from it won't block. Instead,
 /// the buffer will only be partially updated based on how much the internal buffer holds.
 pub struct AsyncReader {
-    /// The underlying mpsc receiver.
-    recv: mpsc::Receiver<io::Result<u8>>,
+    /// The receiving end of the queue filled by the reader thread.
+    recv: ring::Consumer,
+    /// Where the reader thread of `async_stdin_with_resize` leaves the window size.
+    resize: Option<PendingResize>,
 }
 
-// FIXME: Allow constructing an async reader from an arbitrary stream.
+impl AsyncReader {
+    /// Read `source` asynchronously, from a background thread.
+    ///
+    /// This is what `async_stdin` does with the TTY, for any other stream.
+    pub fn from_read<R: Read + Send + 'static>(source: R) -> AsyncReader {
+        let (producer, consumer) = ring::channel(QUEUE_CAPACITY);
+        thread::spawn(move || pump(source, producer, None, false));
+        AsyncReader { recv: consumer, resize: None }
+    }
+
+    /// An iterator over input events, with `Event::Resize` when the window is resized for a
+    /// reader from `async_stdin_with_resize`.
+    ///
+    /// This takes the place of `TermRead::events`; going through the trait, e.g. from generic
+    /// code, gives the same events without the resizes.
+    pub fn events(self) -> Events<AsyncReader> {
+        Events { inner: self.events_and_raw(), last_raw: RawBytes::new() }
+    }
+
+    /// An iterator over input events and the bytes that define them, with `Event::Resize`, whose
+    /// bytes are empty, as for `events`.
+    pub fn events_and_raw(mut self) -> EventsAndRaw<AsyncReader> {
+        let resize = self.resize.take();
+        let mut events = TermReadEventsAndRaw::events_and_raw(self);
+        events.resize = resize;
+        events
+    }
+
+    /// Whether the stream ended and everything in it was read.
+    ///
+    /// Reads return 0 both when no input arrived yet and at the end of the stream, e.g. when
+    /// standard input is `/dev/null` or a closed pipe. Polling loops can check this to stop.
+    pub fn is_finished(&self) -> bool {
+        self.recv.is_finished()
+    }
+}
 
 impl Read for AsyncReader {
     /// Read from the byte stream.
@@ -66,19 +190,11 @@ impl Read for AsyncReader {
     /// bytes written is lower than the buffer's length, the event queue is empty or that the event
     /// stream halted.
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let mut total = 0;
-        loop {
-            if total >= buf.len() { break; }
-            match self.recv.try_recv() {
-                Ok(Ok(b)) => {
-                    buf[total] = b;
-                    total += 1;
-                }
-                Ok(Err(e)) => return Err(e),
-                Err(_) => break,
-            }
+        let read = self.recv.pop(buf);
+        if let Some(metrics) = metrics::get() {
+            metrics.queue_depth(self.recv.queued());
         }
-        Ok(total)
+        read
     }
 }
 
@@ -93,4 +209,64 @@ mod test {
         let stdin = async_stdin();
         stdin.bytes().next();
     }
+
+    #[test]
+    fn test_from_read() {
+        let input: Vec<u8> = (0..200).collect();
+        let mut reader = AsyncReader::from_read(io::Cursor::new(input.clone()));
+
+        let mut output = Vec::new();
+        let mut buf = [0; 16];
+        while output.len() < input.len() {
+            let n = reader.read(&mut buf).unwrap();
+            output.extend_from_slice(&buf[..n]);
+        }
+        assert_eq!(output, input);
+        while !reader.is_finished() {
+            assert_eq!(reader.read(&mut buf).unwrap(), 0);
+            thread::yield_now();
+        }
+    }
+
+    #[test]
+    fn test_resize_out_of_band() {
+        use crate::event::{Event, Key};
+
+        let mut reader = AsyncReader::from_read(io::Cursor::new(b"\x1B[A".to_vec()));
+        let pending = PendingResize::default();
+        reader.resize = Some(pending.clone());
+        *pending.lock().unwrap() = Some((80, 24));
+
+        let mut events = reader.events();
+        assert_eq!(events.next().unwrap().unwrap(), Event::Resize(80, 24));
+        assert_eq!(events.last_raw(), b"");
+        loop {
+            match events.next() {
+                Some(Ok(event)) => return assert_eq!(event, Event::Key(Key::Up.into())),
+                _ => thread::yield_now(),
+            }
+        }
+    }
+
+    #[test]
+    fn test_resize_events() {
+        use crate::event::Event;
+
+        // Without a controlling terminal, e.g. in CI, there is no window to be resized.
+        let Ok(tty) = get_tty() else { return };
+        let (cols, rows) = terminal_size_of(tty.as_raw_fd()).unwrap();
+
+        // Install the handler before the reader thread does, so the signal is not lost.
+        signal::resize_pipe().unwrap();
+        let mut events = async_stdin_with_resize().events();
+        unsafe { libc::raise(libc::SIGWINCH) };
+
+        for _ in 0..1000 {
+            match events.next() {
+                Some(Ok(Event::Resize(c, r))) => return assert_eq!((c, r), (cols, rows)),
+                _ => thread::sleep(std::time::Duration::from_millis(1)),
+            }
+        }
+        panic!("no resize event");
+    }
 }
@@ -0,0 +1,70 @@
+//! Running without a terminal.
+//!
+//! When the output of a program goes to a pipe or a file, terminal modes make no sense: raw mode
+//! fails and escape sequences end up as garbage in the output. In batch mode, the mode guards
+//! (`raw::RawTerminal`, `input::MouseTerminal`, `input::AlternateScroll` and `cursor::HideCursor`)
+//! still wrap their writer but do nothing else.
+//!
+//! By default (`BatchPolicy::Auto`), raw mode is skipped when the terminal it would switch is not
+//! a TTY, and the other guards when standard output is not one.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::batch::{self, BatchPolicy};
+//!
+//! // Never touch the terminal, e.g. for a `--no-tty` flag.
+//! batch::set_policy(BatchPolicy::Batch);
+//! assert!(batch::is_batch());
+//! # batch::set_policy(BatchPolicy::Auto);
+//! ```
+
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::sys::tty::is_tty_fd;
+
+/// When the mode guards are no-ops.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum BatchPolicy {
+    /// Batch mode when standard output is not a terminal.
+    #[default]
+    Auto,
+    /// Always enable terminal modes.
+    Interactive,
+    /// Never enable terminal modes.
+    Batch,
+}
+
+/// The process-wide policy, as the `BatchPolicy` discriminant.
+static POLICY: AtomicU8 = AtomicU8::new(BatchPolicy::Auto as u8);
+
+/// Replace the process-wide batch policy.
+///
+/// This affects guards created afterwards; existing guards keep behaving as they were created.
+pub fn set_policy(policy: BatchPolicy) {
+    POLICY.store(policy as u8, Ordering::Relaxed);
+}
+
+/// Get the process-wide batch policy.
+pub fn policy() -> BatchPolicy {
+    match POLICY.load(Ordering::Relaxed) {
+        0 => BatchPolicy::Auto,
+        1 => BatchPolicy::Interactive,
+        _ => BatchPolicy::Batch,
+    }
+}
+
+/// Whether mode guards created now would be no-ops.
+pub fn is_batch() -> bool {
+    is_batch_for(libc::STDOUT_FILENO)
+}
+
+/// Whether terminal modes of `fd` should be left alone.
+pub(crate) fn is_batch_for(fd: RawFd) -> bool {
+    match policy() {
+        BatchPolicy::Auto => !is_tty_fd(fd),
+        BatchPolicy::Interactive => false,
+        BatchPolicy::Batch => true,
+    }
+}
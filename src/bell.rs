@@ -0,0 +1,127 @@
+//! Bell volume.
+//!
+//! VT520-compatible terminals let programs set the volume of the warning bell (rung by `\x07`)
+//! and of the margin bell (rung when typing nears the right margin). Others ignore these
+//! sequences, so `supports_volume` can tell whether setting them has any effect.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::bell::{BellVolume, WarningBellVolume};
+//!
+//! // A quieter alert.
+//! print!("{}\x07", WarningBellVolume(BellVolume::Low));
+//! ```
+
+use std::fmt;
+use std::io::{self, Read, Write};
+
+/// A bell volume.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BellVolume {
+    /// The bell is silent.
+    Off,
+    /// A low volume.
+    Low,
+    /// A high volume, which is the usual default.
+    High,
+}
+
+impl BellVolume {
+    /// The parameter of this volume in DECSWBV and DECSMBV.
+    fn param(self) -> u8 {
+        match self {
+            BellVolume::Off => 1,
+            BellVolume::Low => 4,
+            BellVolume::High => 8,
+        }
+    }
+}
+
+/// Set the volume of the warning bell.
+///
+/// This is DECSWBV.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct WarningBellVolume(pub BellVolume);
+
+impl fmt::Display for WarningBellVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, csi!("{} t"), self.0.param())
+    }
+}
+
+/// Set the volume of the margin bell.
+///
+/// This is DECSMBV.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MarginBellVolume(pub BellVolume);
+
+impl fmt::Display for MarginBellVolume {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, csi!("{} u"), self.0.param())
+    }
+}
+
+/// Ask the terminal `tty` whether it supports setting the warning bell volume.
+///
+/// This requests the current setting with DECRQSS and checks whether the terminal recognized the
+/// request. A terminal that does not answer at all is taken as not supporting it, so `tty` should
+/// be in raw mode with a read timeout (see `raw::RawModeBuilder::vtime`).
+pub fn supports_volume<T: Read + Write>(tty: &mut T) -> io::Result<bool> {
+    write!(tty, "\x1BP$q t\x1B\\")?;
+    tty.flush()?;
+
+    // The answer is `DCS 1 $ r ... ST` if the request is valid, `DCS 0 $ r ST` otherwise.
+    let mut answer = Vec::new();
+    let mut buf = [0u8];
+    while !answer.ends_with(b"\x1B\\") {
+        if tty.read(&mut buf)? == 0 {
+            return Ok(false);
+        }
+        answer.push(buf[0]);
+    }
+    Ok(answer.windows(3).any(|w| w == b"1$r"))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A terminal answering DECRQSS requests.
+    struct FakeTty {
+        answer: io::Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for FakeTty {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.answer.read(buf)
+        }
+    }
+
+    impl Write for FakeTty {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_bell_volume() {
+        assert_eq!(WarningBellVolume(BellVolume::Off).to_string(), "\x1B[1 t");
+        assert_eq!(MarginBellVolume(BellVolume::High).to_string(), "\x1B[8 u");
+
+        let mut vt520 = FakeTty { answer: io::Cursor::new(b"\x1BP1$r8 t\x1B\\".to_vec()), written: Vec::new() };
+        assert!(supports_volume(&mut vt520).unwrap());
+        assert_eq!(vt520.written, b"\x1BP$q t\x1B\\");
+
+        let mut xterm = FakeTty { answer: io::Cursor::new(b"\x1BP0$r\x1B\\".to_vec()), written: Vec::new() };
+        assert!(!supports_volume(&mut xterm).unwrap());
+
+        let mut silent = FakeTty { answer: io::Cursor::new(Vec::new()), written: Vec::new() };
+        assert!(!supports_volume(&mut silent).unwrap());
+    }
+}
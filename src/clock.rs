@@ -0,0 +1,23 @@
+//! Where timing-dependent input handling gets the time from.
+//!
+//! `event::adapters::ClickCounter` and `keymap::capture_chord` measure time between events. They
+//! read it from a `Clock`, `SystemClock` unless told otherwise, so that tests can step it with
+//! `test::ManualClock` instead of sleeping.
+
+use std::time::Instant;
+
+/// A source of the current time.
+pub trait Clock {
+    /// The current time.
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic system clock, `Instant::now`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
@@ -0,0 +1,384 @@
+//! Color values and palette matching.
+//!
+//! `Fg` and `Bg` write a color as the foreground or background of the text after them.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::color::{self, closest_ansi, AnsiValue, Bg, Fg, Rgb};
+//!
+//! assert_eq!(closest_ansi(Rgb(128, 128, 128)), AnsiValue(244));
+//!
+//! println!("{}{}warning{}{}", Fg(color::Yellow), Bg(Rgb(40, 0, 0)), Fg(color::Reset), Bg(color::Reset));
+//! ```
+
+use std::fmt;
+use std::sync::OnceLock;
+
+/// A color the terminal can show as foreground or background.
+pub trait Color {
+    /// Write the sequence setting this color as the foreground.
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result;
+    /// Write the sequence setting this color as the background.
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result;
+}
+
+impl<C: Color + ?Sized> Color for &C {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).write_fg(f)
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).write_bg(f)
+    }
+}
+
+/// Derive a named color, one of the 16 the user's theme defines.
+macro_rules! derive_color {
+    ($doc:expr, $name:ident, $fg:expr, $bg:expr) => {
+        #[doc = $doc]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct $name;
+
+        impl Color for $name {
+            #[inline]
+            fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(csi!($fg, "m"))
+            }
+
+            #[inline]
+            fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                f.write_str(csi!($bg, "m"))
+            }
+        }
+    };
+}
+
+derive_color!("Black.", Black, "30", "40");
+derive_color!("Red.", Red, "31", "41");
+derive_color!("Green.", Green, "32", "42");
+derive_color!("Yellow.", Yellow, "33", "43");
+derive_color!("Blue.", Blue, "34", "44");
+derive_color!("Magenta.", Magenta, "35", "45");
+derive_color!("Cyan.", Cyan, "36", "46");
+derive_color!("White.", White, "37", "47");
+derive_color!("High-intensity black, often shown as dark gray.", LightBlack, "90", "100");
+derive_color!("High-intensity red.", LightRed, "91", "101");
+derive_color!("High-intensity green.", LightGreen, "92", "102");
+derive_color!("High-intensity yellow.", LightYellow, "93", "103");
+derive_color!("High-intensity blue.", LightBlue, "94", "104");
+derive_color!("High-intensity magenta.", LightMagenta, "95", "105");
+derive_color!("High-intensity cyan.", LightCyan, "96", "106");
+derive_color!("High-intensity white.", LightWhite, "97", "107");
+derive_color!("The terminal's default color.", Reset, "39", "49");
+
+impl Color for AnsiValue {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, csi!("38;5;{}m"), self.0)
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, csi!("48;5;{}m"), self.0)
+    }
+}
+
+impl Color for Rgb {
+    fn write_fg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, csi!("38;2;{};{};{}m"), self.0, self.1, self.2)
+    }
+
+    fn write_bg(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, csi!("48;2;{};{};{}m"), self.0, self.1, self.2)
+    }
+}
+
+/// Set the foreground color.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Fg<C: Color>(pub C);
+
+impl<C: Color> fmt::Display for Fg<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write_fg(f)
+    }
+}
+
+/// Set the background color.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Bg<C: Color>(pub C);
+
+impl<C: Color> fmt::Display for Bg<C> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.write_bg(f)
+    }
+}
+
+/// A value from the 256-color palette.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct AnsiValue(pub u8);
+
+impl AnsiValue {
+    /// 216-color (r, g, b ≤ 5) RGB.
+    pub fn rgb(r: u8, g: u8, b: u8) -> AnsiValue {
+        debug_assert!(r <= 5, "Red color fragment (r = {}) is out of bound. Make sure r ≤ 5.", r);
+        debug_assert!(g <= 5, "Green color fragment (g = {}) is out of bound. Make sure g ≤ 5.", g);
+        debug_assert!(b <= 5, "Blue color fragment (b = {}) is out of bound. Make sure b ≤ 5.", b);
+
+        AnsiValue(16 + 36 * r + 6 * g + b)
+    }
+
+    /// Grayscale color.
+    ///
+    /// There are 24 shades of gray.
+    pub fn grayscale(shade: u8) -> AnsiValue {
+        // Unfortunately, there are a little less than fifty shades.
+        debug_assert!(shade < 24, "Grayscale out of bound (shade = {}). There are only 24 shades of gray.", shade);
+
+        AnsiValue(0xE8 + shade)
+    }
+
+    /// The RGB value xterm uses for this palette entry by default.
+    ///
+    /// The first 16 entries are commonly themed by the user, so this is only an approximation of
+    /// what the terminal actually displays for them.
+    pub fn to_rgb(self) -> Rgb {
+        const SYSTEM: [(u8, u8, u8); 16] = [
+            (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+            (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+            (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+            (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+        ];
+        const CUBE: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+        match self.0 {
+            n @ 0..=15 => {
+                let (r, g, b) = SYSTEM[n as usize];
+                Rgb(r, g, b)
+            }
+            n @ 16..=231 => {
+                let n = n - 16;
+                Rgb(CUBE[(n / 36) as usize], CUBE[(n / 6 % 6) as usize], CUBE[(n % 6) as usize])
+            }
+            n => {
+                let level = 8 + 10 * (n - 232);
+                Rgb(level, level, level)
+            }
+        }
+    }
+}
+
+/// A truecolor RGB value.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Linearly interpolate between this color and `other`.
+    ///
+    /// `t` is clamped to `0.0..=1.0`, `0.0` giving `self` and `1.0` giving `other`.
+    pub fn lerp(self, other: Rgb, t: f32) -> Rgb {
+        let t = t.clamp(0.0, 1.0);
+        let mix = |a: u8, b: u8| (a as f32 + (b as f32 - a as f32) * t).round() as u8;
+        Rgb(mix(self.0, other.0), mix(self.1, other.1), mix(self.2, other.2))
+    }
+
+    /// Convert to hue (degrees, `0.0..360.0`), saturation and lightness (both `0.0..=1.0`).
+    pub fn to_hsl(self) -> (f32, f32, f32) {
+        let (r, g, b) = (self.0 as f32 / 255.0, self.1 as f32 / 255.0, self.2 as f32 / 255.0);
+        let max = r.max(g).max(b);
+        let min = r.min(g).min(b);
+        let l = (max + min) / 2.0;
+        let d = max - min;
+
+        if d == 0.0 {
+            return (0.0, 0.0, l);
+        }
+
+        let s = d / (1.0 - (2.0 * l - 1.0).abs());
+        let h = if max == r {
+            60.0 * ((g - b) / d).rem_euclid(6.0)
+        } else if max == g {
+            60.0 * ((b - r) / d + 2.0)
+        } else {
+            60.0 * ((r - g) / d + 4.0)
+        };
+
+        (h, s, l)
+    }
+
+    /// Convert from hue (degrees), saturation and lightness (both `0.0..=1.0`).
+    ///
+    /// The hue wraps around; saturation and lightness are clamped.
+    pub fn from_hsl(h: f32, s: f32, l: f32) -> Rgb {
+        let h = h.rem_euclid(360.0);
+        let s = s.clamp(0.0, 1.0);
+        let l = l.clamp(0.0, 1.0);
+
+        let c = (1.0 - (2.0 * l - 1.0).abs()) * s;
+        let x = c * (1.0 - ((h / 60.0).rem_euclid(2.0) - 1.0).abs());
+        let m = l - c / 2.0;
+        let (r, g, b) = match (h / 60.0) as u8 {
+            0 => (c, x, 0.0),
+            1 => (x, c, 0.0),
+            2 => (0.0, c, x),
+            3 => (0.0, x, c),
+            4 => (x, 0.0, c),
+            _ => (c, 0.0, x),
+        };
+        let channel = |v: f32| ((v + m) * 255.0).round() as u8;
+
+        Rgb(channel(r), channel(g), channel(b))
+    }
+
+    /// Convert to CIE L*a*b* (D65 white point).
+    fn to_lab(self) -> (f32, f32, f32) {
+        fn linear(c: u8) -> f32 {
+            let c = c as f32 / 255.0;
+            if c <= 0.04045 { c / 12.92 } else { ((c + 0.055) / 1.055).powf(2.4) }
+        }
+        fn f(t: f32) -> f32 {
+            if t > 0.008856 { t.cbrt() } else { 7.787 * t + 16.0 / 116.0 }
+        }
+
+        let (r, g, b) = (linear(self.0), linear(self.1), linear(self.2));
+        let x = (0.4124 * r + 0.3576 * g + 0.1805 * b) / 0.95047;
+        let y = 0.2126 * r + 0.7152 * g + 0.0722 * b;
+        let z = (0.0193 * r + 0.1192 * g + 0.9505 * b) / 1.08883;
+        let (fx, fy, fz) = (f(x), f(y), f(z));
+
+        (116.0 * fy - 16.0, 500.0 * (fx - fy), 200.0 * (fy - fz))
+    }
+}
+
+/// The perceptual distance (CIE76 ΔE, squared) between two colors in CIE L*a*b*.
+fn lab_distance((l1, a1, b1): (f32, f32, f32), (l2, a2, b2): (f32, f32, f32)) -> f32 {
+    (l1 - l2).powi(2) + (a1 - a2).powi(2) + (b1 - b2).powi(2)
+}
+
+/// The 256-color palette in CIE L*a*b*, computed once on first use.
+fn palette_lab() -> &'static [(f32, f32, f32); 256] {
+    static PALETTE: OnceLock<[(f32, f32, f32); 256]> = OnceLock::new();
+    PALETTE.get_or_init(|| std::array::from_fn(|n| AnsiValue(n as u8).to_rgb().to_lab()))
+}
+
+/// An iterator over evenly spaced colors between two endpoints.
+///
+/// This is constructed by `gradient`.
+pub struct Gradient {
+    from: Rgb,
+    to: Rgb,
+    steps: usize,
+    next: usize,
+}
+
+impl Iterator for Gradient {
+    type Item = Rgb;
+
+    fn next(&mut self) -> Option<Rgb> {
+        if self.next >= self.steps {
+            return None;
+        }
+        let t = if self.steps == 1 { 0.0 } else { self.next as f32 / (self.steps - 1) as f32 };
+        self.next += 1;
+        Some(self.from.lerp(self.to, t))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let len = self.steps - self.next;
+        (len, Some(len))
+    }
+}
+
+impl ExactSizeIterator for Gradient {}
+
+/// A color ramp of `steps` colors going from `from` to `to`, both included.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::color::{gradient, Rgb};
+///
+/// let ramp: Vec<Rgb> = gradient(Rgb(0, 0, 0), Rgb(255, 255, 255), 3).collect();
+/// assert_eq!(ramp, [Rgb(0, 0, 0), Rgb(128, 128, 128), Rgb(255, 255, 255)]);
+/// ```
+pub fn gradient(from: Rgb, to: Rgb, steps: usize) -> Gradient {
+    Gradient { from, to, steps, next: 0 }
+}
+
+/// Find the palette entry in `candidates` perceptually closest to `rgb`.
+fn closest_in<I: Iterator<Item=u8>>(rgb: Rgb, candidates: I) -> AnsiValue {
+    let target = rgb.to_lab();
+    let palette = palette_lab();
+    let distance = |n: u8| lab_distance(target, palette[n as usize]);
+    AnsiValue(candidates.min_by(|&a, &b| distance(a).total_cmp(&distance(b))).unwrap())
+}
+
+/// Find the 256-color palette entry that looks closest to `rgb`.
+///
+/// Matching happens in CIE L*a*b* space rather than on raw RGB components, which keeps grays gray
+/// instead of pulling them towards a tinted cube entry. Only the color cube and the grayscale ramp
+/// are considered, since the first 16 entries depend on the user's theme.
+pub fn closest_ansi(rgb: Rgb) -> AnsiValue {
+    closest_in(rgb, 16..=255)
+}
+
+/// Find the 16-color palette entry that looks closest to `rgb`, assuming xterm's default colors.
+pub fn closest_ansi_16(rgb: Rgb) -> AnsiValue {
+    closest_in(rgb, 0..16)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_palette_round_trip() {
+        for n in 16..=255 {
+            assert_eq!(closest_ansi(AnsiValue(n).to_rgb()), AnsiValue(n));
+        }
+    }
+
+    #[test]
+    fn test_grays_stay_gray() {
+        for level in (0..=255).step_by(5) {
+            let AnsiValue(n) = closest_ansi(Rgb(level, level, level));
+            let Rgb(r, g, b) = AnsiValue(n).to_rgb();
+            assert!(r == g && g == b, "{} matched tinted entry {}", level, n);
+        }
+    }
+
+    #[test]
+    fn test_hsl_round_trip() {
+        for &rgb in [Rgb(0, 0, 0), Rgb(255, 255, 255), Rgb(255, 0, 0), Rgb(12, 200, 99), Rgb(90, 30, 210)].iter() {
+            let (h, s, l) = rgb.to_hsl();
+            assert_eq!(Rgb::from_hsl(h, s, l), rgb);
+        }
+        assert_eq!(Rgb::from_hsl(120.0, 1.0, 0.5), Rgb(0, 255, 0));
+        assert_eq!(Rgb::from_hsl(-120.0, 1.0, 0.5), Rgb(0, 0, 255));
+    }
+
+    #[test]
+    fn test_gradient() {
+        assert_eq!(gradient(Rgb(0, 0, 0), Rgb(10, 20, 30), 0).count(), 0);
+        assert_eq!(gradient(Rgb(1, 2, 3), Rgb(10, 20, 30), 1).collect::<Vec<_>>(), [Rgb(1, 2, 3)]);
+
+        let ramp = gradient(Rgb(0, 100, 200), Rgb(200, 100, 0), 5);
+        assert_eq!(ramp.len(), 5);
+        assert_eq!(ramp.last(), Some(Rgb(200, 100, 0)));
+        assert_eq!(Rgb(0, 0, 0).lerp(Rgb(100, 100, 100), 2.0), Rgb(100, 100, 100));
+    }
+
+    #[test]
+    fn test_fg_bg() {
+        assert_eq!(Fg(Red).to_string(), "\x1B[31m");
+        assert_eq!(Bg(LightCyan).to_string(), "\x1B[106m");
+        assert_eq!(Fg(&Reset).to_string(), "\x1B[39m");
+        assert_eq!(Bg(AnsiValue::grayscale(3)).to_string(), "\x1B[48;5;235m");
+        assert_eq!(Fg(Rgb(1, 2, 3)).to_string(), "\x1B[38;2;1;2;3m");
+    }
+
+    #[test]
+    fn test_closest_ansi_16() {
+        assert_eq!(closest_ansi_16(Rgb(250, 10, 10)), AnsiValue(9));
+        assert_eq!(closest_ansi_16(Rgb(10, 10, 10)), AnsiValue(0));
+        assert_eq!(closest_ansi_16(Rgb(128, 128, 128)), AnsiValue(8));
+    }
+}
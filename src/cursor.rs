@@ -3,16 +3,21 @@
 use std::fmt;
 use std::io::{self, Error, ErrorKind, Read, Write};
 use std::ops;
+use std::str;
 use std::time::{Duration, SystemTime};
 
-use numtoa::NumToA;
-
+use crate::guard::ModeGuard;
 use crate::r#async::async_stdin_until;
 use crate::raw::CONTROL_SEQUENCE_TIMEOUT;
+use crate::scroll::Region;
+use crate::sequence::Sequence;
 
 derive_csi_sequence!("Hide the cursor.", Hide, "?25l");
 derive_csi_sequence!("Show the cursor.", Show, "?25h");
 
+derive_csi_sequence!("Make `Goto` relative to the scrolling region (DECOM).", EnterOriginMode, "?6h");
+derive_csi_sequence!("Make `Goto` relative to the whole screen again.", ExitOriginMode, "?6l");
+
 derive_csi_sequence!("Restore the cursor.", Restore, "u");
 derive_csi_sequence!("Save the cursor.", Save, "s");
 
@@ -43,10 +48,32 @@ derive_csi_sequence!("Change the cursor style to steady bar", SteadyBar, "\x36 q
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Goto(pub u16, pub u16);
 
+impl Goto {
+    /// Goto `(x, y)` counted from the top-left corner of `region`.
+    ///
+    /// With origin mode (`EnterOriginMode`) the terminal does this translation itself and a plain
+    /// `Goto(x, y)` is enough; without it, use this to avoid off-by-`top` bugs when drawing
+    /// inside a scrolling region.
+    ///
+    /// Returns `None` if line `y` (one-based) is outside the region.
+    pub fn relative_to_region(x: u16, y: u16, region: Region) -> Option<Goto> {
+        if y == 0 || y > region.height() {
+            return None;
+        }
+        Some(Goto(x, region.0.checked_add(y - 1)?))
+    }
+
+    /// The sequence as bytes, formatted on the stack.
+    #[inline]
+    pub fn bytes(self) -> Sequence {
+        debug_assert!(self != Goto(0, 0), "Goto is one-based.");
+        Sequence::csi2(self.1, self.0, b'H')
+    }
+}
+
 impl From<Goto> for String {
     fn from(this: Goto) -> String {
-        let (mut x, mut y) = ([0u8; 20], [0u8; 20]);
-        ["\x1B[", this.1.numtoa_str(10, &mut x), ";", this.0.numtoa_str(10, &mut y), "H"].concat()
+        this.bytes().as_str().to_owned()
     }
 }
 
@@ -57,9 +84,9 @@ impl Default for Goto {
 }
 
 impl fmt::Display for Goto {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        debug_assert!(self != &Goto(0, 0), "Goto is one-based.");
-        write!(f, "\x1B[{};{}H", self.1, self.0)
+        self.bytes().fmt(f)
     }
 }
 
@@ -67,16 +94,24 @@ impl fmt::Display for Goto {
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Left(pub u16);
 
+impl Left {
+    /// The sequence as bytes, formatted on the stack.
+    #[inline]
+    pub fn bytes(self) -> Sequence {
+        Sequence::csi1(self.0, b'D')
+    }
+}
+
 impl From<Left> for String {
     fn from(this: Left) -> String {
-        let mut buf = [0u8; 20];
-        ["\x1B[", this.0.numtoa_str(10, &mut buf), "D"].concat()
+        this.bytes().as_str().to_owned()
     }
 }
 
 impl fmt::Display for Left {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\x1B[{}D", self.0)
+        self.bytes().fmt(f)
     }
 }
 
@@ -84,16 +119,24 @@ impl fmt::Display for Left {
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Right(pub u16);
 
+impl Right {
+    /// The sequence as bytes, formatted on the stack.
+    #[inline]
+    pub fn bytes(self) -> Sequence {
+        Sequence::csi1(self.0, b'C')
+    }
+}
+
 impl From<Right> for String {
     fn from(this: Right) -> String {
-        let mut buf = [0u8; 20];
-        ["\x1B[", this.0.numtoa_str(10, &mut buf), "C"].concat()
+        this.bytes().as_str().to_owned()
     }
 }
 
 impl fmt::Display for Right {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\x1B[{}C", self.0)
+        self.bytes().fmt(f)
     }
 }
 
@@ -101,16 +144,24 @@ impl fmt::Display for Right {
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Up(pub u16);
 
+impl Up {
+    /// The sequence as bytes, formatted on the stack.
+    #[inline]
+    pub fn bytes(self) -> Sequence {
+        Sequence::csi1(self.0, b'A')
+    }
+}
+
 impl From<Up> for String {
     fn from(this: Up) -> String {
-        let mut buf = [0u8; 20];
-        ["\x1B[", this.0.numtoa_str(10, &mut buf), "A"].concat()
+        this.bytes().as_str().to_owned()
     }
 }
 
 impl fmt::Display for Up {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\x1B[{}A", self.0)
+        self.bytes().fmt(f)
     }
 }
 
@@ -118,16 +169,24 @@ impl fmt::Display for Up {
 #[derive(Copy, Clone, PartialEq, Eq)]
 pub struct Down(pub u16);
 
+impl Down {
+    /// The sequence as bytes, formatted on the stack.
+    #[inline]
+    pub fn bytes(self) -> Sequence {
+        Sequence::csi1(self.0, b'B')
+    }
+}
+
 impl From<Down> for String {
     fn from(this: Down) -> String {
-        let mut buf = [0u8; 20];
-        ["\x1B[", this.0.numtoa_str(10, &mut buf), "B"].concat()
+        this.bytes().as_str().to_owned()
     }
 }
 
 impl fmt::Display for Down {
+    #[inline]
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "\x1B[{}B", self.0)
+        self.bytes().fmt(f)
     }
 }
 
@@ -161,48 +220,79 @@ impl<W: Write> DetectCursorPos for W {
         }
 
         if read_chars.is_empty() {
-            return Err(Error::new(ErrorKind::Other, "Cursor position detection timed out."));
+            return Err(Error::other("Cursor position detection timed out."));
         }
 
-        // The answer will look like `ESC [ Cy ; Cx R`.
-
-        read_chars.pop(); // remove trailing R.
-        let read_str = String::from_utf8(read_chars).unwrap();
-        let beg = read_str.rfind('[').unwrap();
-        let coords: String = read_str.chars().skip(beg + 1).collect();
-        let mut nums = coords.split(';');
+        parse_cursor_report(read_chars)
+    }
+}
 
-        let cy = nums.next()
-            .unwrap()
-            .parse::<u16>()
-            .unwrap();
-        let cx = nums.next()
-            .unwrap()
-            .parse::<u16>()
-            .unwrap();
+/// Get the (1,1)-based cursor position of the terminal `tty`.
+///
+/// Unlike `DetectCursorPos`, which always asks the controlling terminal, this queries and reads
+/// the answer from `tty` itself, so it works for any of several terminals opened by the process.
+/// `tty` should be in raw mode; a read timeout (see `raw::RawModeBuilder::vtime`) avoids waiting
+/// forever for a terminal that does not answer.
+pub fn cursor_pos_of<T: Read + Write>(tty: &mut T) -> io::Result<(u16, u16)> {
+    write!(tty, "\x1B[6n")?;
+    tty.flush()?;
+    read_cursor_report(tty)
+}
 
-        Ok((cx, cy))
+/// Read the answer to a `ESC [ 6 n` query from `input`, failing if it ends first.
+pub(crate) fn read_cursor_report<R: Read>(input: &mut R) -> io::Result<(u16, u16)> {
+    let mut buf: [u8; 1] = [0];
+    let mut read_chars = Vec::new();
+    while buf[0] != b'R' {
+        if input.read(&mut buf)? == 0 {
+            return Err(Error::other("Cursor position detection timed out."));
+        }
+        read_chars.push(buf[0]);
     }
+
+    parse_cursor_report(read_chars)
+}
+
+/// Parse a cursor position report, which looks like `ESC [ Cy ; Cx R`.
+///
+/// Bytes before the last `[`, such as input typed before the report arrived, are ignored.
+fn parse_cursor_report(read_chars: Vec<u8>) -> io::Result<(u16, u16)> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "Invalid cursor position report.");
+
+    let report = read_chars.strip_suffix(b"R").ok_or_else(invalid)?;
+    let beg = report.iter().rposition(|&c| c == b'[').ok_or_else(invalid)?;
+    let num = |bytes: &[u8]| -> io::Result<u16> {
+        if bytes.is_empty() || !bytes.iter().all(u8::is_ascii_digit) {
+            return Err(invalid());
+        }
+        // Only ASCII digits, so valid UTF-8.
+        str::from_utf8(bytes).unwrap().parse().map_err(|_| invalid())
+    };
+
+    let mut nums = report[beg + 1..].split(|&c| c == b';');
+    let (Some(cy), Some(cx), None) = (nums.next(), nums.next(), nums.next()) else { return Err(invalid()) };
+    Ok((num(cx)?, num(cy)?))
 }
 
 /// Hide the cursor for the lifetime of this struct.
 /// It will hide the cursor on creation with from() and show it back on drop().
 pub struct HideCursor<W: Write> {
     /// The output target.
-    output: W,
+    output: ModeGuard<W>,
 }
 
 impl<W: Write> HideCursor<W> {
     /// Create a hide cursor wrapper struct for the provided output and hides the cursor.
-    pub fn from(mut output: W) -> Self {
-        write!(output, "{}", Hide).expect("hide the cursor");
-        HideCursor { output: output }
+    pub fn from(output: W) -> Self {
+        HideCursor { output: ModeGuard::enable(output, Hide::BYTES, Show::BYTES).expect("hide the cursor") }
     }
-}
 
-impl<W: Write> Drop for HideCursor<W> {
-    fn drop(&mut self) {
-        write!(self, "{}", Show).expect("show the cursor");
+    /// Show the cursor again and get the output target back.
+    ///
+    /// Unlike dropping, this keeps the wrapped writer, and any guard it is itself (such as a
+    /// `RawTerminal`), alive.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.output.into_inner()
     }
 }
 
@@ -210,17 +300,111 @@ impl<W: Write> ops::Deref for HideCursor<W> {
     type Target = W;
 
     fn deref(&self) -> &W {
-        &self.output
+        self.output.get_ref()
     }
 }
 
 impl<W: Write> ops::DerefMut for HideCursor<W> {
     fn deref_mut(&mut self) -> &mut W {
-        &mut self.output
+        self.output.get_mut()
     }
 }
 
 impl<W: Write> Write for HideCursor<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.output.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.get_mut().flush()
+    }
+}
+
+/// A stack of cursor positions, tracked client-side.
+///
+/// The terminal's own save/restore (`Save`/`Restore`) only remembers a single position, so nested
+/// drawing code that saves the cursor clobbers the position saved by its caller. This keeps the
+/// positions in memory instead and restores them with `Goto`, allowing arbitrary nesting.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::cursor::{CursorStack, Goto};
+/// use std::io::Write;
+///
+/// let mut stack = CursorStack::from(Vec::new());
+/// {
+///     let mut outer = stack.save(1, 1);
+///     write!(outer, "{}", Goto(10, 4)).unwrap();
+///     {
+///         let mut inner = outer.save(10, 4);
+///         write!(inner, "{}", Goto(20, 8)).unwrap();
+///     } // Back at (10, 4).
+/// } // Back at (1, 1).
+/// ```
+pub struct CursorStack<W: Write> {
+    /// The output target.
+    output: W,
+    /// The saved positions, innermost last.
+    stack: Vec<(u16, u16)>,
+}
+
+impl<W: Write> CursorStack<W> {
+    /// Create an empty cursor stack writing to `output`.
+    pub fn from(output: W) -> Self {
+        CursorStack { output, stack: Vec::new() }
+    }
+
+    /// Remember `(x, y)` as the current (1,1)-based cursor position.
+    pub fn push(&mut self, x: u16, y: u16) {
+        self.stack.push((x, y));
+    }
+
+    /// Move the cursor back to the most recently pushed position and forget it.
+    ///
+    /// Returns the restored position, or `None` if the stack was empty.
+    pub fn pop(&mut self) -> io::Result<Option<(u16, u16)>> {
+        match self.stack.pop() {
+            Some((x, y)) => {
+                write!(self.output, "{}", Goto(x, y))?;
+                Ok(Some((x, y)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// The number of saved positions.
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// Push `(x, y)` and pop it again when the returned guard is dropped.
+    pub fn save(&mut self, x: u16, y: u16) -> SavedCursor<'_, W> {
+        self.push(x, y);
+        SavedCursor { stack: self }
+    }
+
+    /// Get the output target back, discarding the saved positions.
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+impl<W: Write> ops::Deref for CursorStack<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.output
+    }
+}
+
+impl<W: Write> ops::DerefMut for CursorStack<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+}
+
+impl<W: Write> Write for CursorStack<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
         self.output.write(buf)
     }
@@ -229,3 +413,117 @@ impl<W: Write> Write for HideCursor<W> {
         self.output.flush()
     }
 }
+
+/// A saved cursor position, restored when this is dropped.
+///
+/// This is obtained through `CursorStack::save`, and can itself be saved again to nest further.
+pub struct SavedCursor<'a, W: Write> {
+    stack: &'a mut CursorStack<W>,
+}
+
+impl<'a, W: Write> Drop for SavedCursor<'a, W> {
+    fn drop(&mut self) {
+        // Best effort: there is nothing sensible to do with a failed write during unwinding.
+        let _ = self.stack.pop();
+    }
+}
+
+impl<'a, W: Write> ops::Deref for SavedCursor<'a, W> {
+    type Target = CursorStack<W>;
+
+    fn deref(&self) -> &CursorStack<W> {
+        self.stack
+    }
+}
+
+impl<'a, W: Write> ops::DerefMut for SavedCursor<'a, W> {
+    fn deref_mut(&mut self) -> &mut CursorStack<W> {
+        self.stack
+    }
+}
+
+impl<'a, W: Write> Write for SavedCursor<'a, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.stack.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.stack.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// A terminal answering cursor position queries.
+    struct FakeTty {
+        answer: io::Cursor<Vec<u8>>,
+        written: Vec<u8>,
+    }
+
+    impl Read for FakeTty {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.answer.read(buf)
+        }
+    }
+
+    impl Write for FakeTty {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.written.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cursor_pos_of() {
+        let mut tty = FakeTty { answer: io::Cursor::new(b"\x1B[12;34R".to_vec()), written: Vec::new() };
+        assert_eq!(cursor_pos_of(&mut tty).unwrap(), (34, 12));
+        assert_eq!(tty.written, b"\x1B[6n");
+
+        let mut silent = FakeTty { answer: io::Cursor::new(Vec::new()), written: Vec::new() };
+        assert!(cursor_pos_of(&mut silent).is_err());
+    }
+
+    #[test]
+    fn test_malformed_cursor_report() {
+        // Typed input before the report is skipped.
+        assert_eq!(parse_cursor_report(b"a\xC3[b\x1B[3;4R".to_vec()).unwrap(), (4, 3));
+        for report in [&b"\x1B[12;xR"[..], b"\x1B[12R", b"\x1B[1;2;3R", b"12;34R", b"\x1B[\xFF;1R", b"\x1B[99999;1R"] {
+            let err = parse_cursor_report(report.to_vec()).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidData, "{:?}", report);
+        }
+    }
+
+    #[test]
+    fn test_goto_relative_to_region() {
+        assert!(Goto::relative_to_region(3, 16, Region(5, 20)) == Some(Goto(3, 20)));
+        assert!(Goto::relative_to_region(3, 0, Region(5, 20)).is_none());
+        assert!(Goto::relative_to_region(3, 17, Region(5, 20)).is_none());
+        // Neither the height nor the line overflow.
+        assert_eq!(Region(0, u16::MAX).height(), u16::MAX);
+        assert!(Goto::relative_to_region(1, u16::MAX, Region(2, u16::MAX)).is_none());
+        assert!(Goto::relative_to_region(1, u16::MAX, Region(1, u16::MAX)) == Some(Goto(1, u16::MAX)));
+    }
+
+    #[test]
+    fn test_cursor_stack_nesting() {
+        let mut stack = CursorStack::from(Vec::new());
+        {
+            let mut outer = stack.save(1, 2);
+            outer.write_all(b"a").unwrap();
+            {
+                let mut inner = outer.save(3, 4);
+                assert_eq!(inner.depth(), 2);
+                inner.write_all(b"b").unwrap();
+            }
+            outer.write_all(b"c").unwrap();
+        }
+        assert_eq!(stack.depth(), 0);
+        assert_eq!(stack.pop().unwrap(), None);
+        assert_eq!(stack.into_inner(), b"ab\x1B[4;3Hc\x1B[2;1H");
+    }
+}
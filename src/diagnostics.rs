@@ -0,0 +1,135 @@
+//! Diagnosing heavy terminal output.
+//!
+//! A TUI that redraws more than it needs to flickers, most visibly over slow links such as SSH.
+//! Wrapping its output in a `BudgetWriter` counts what is written in each time window and reports
+//! the windows that exceed a budget, e.g. to a logging framework.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::diagnostics::{BudgetWriter, EscapeBudget};
+//! use std::io::{self, Write};
+//!
+//! let mut out = BudgetWriter::new(io::stdout(), EscapeBudget::default(), |usage| {
+//!     eprintln!("over budget: {} bytes, {} sequences", usage.bytes, usage.sequences);
+//! });
+//! write!(out, "{}", pres::clear::All).unwrap();
+//! ```
+
+use std::io::{self, Write};
+use std::ops;
+use std::time::{Duration, Instant};
+
+/// The output allowed in a time window.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct EscapeBudget {
+    /// The length of a window.
+    pub window: Duration,
+    /// The bytes allowed per window.
+    pub bytes: usize,
+    /// The escape sequences allowed per window.
+    pub sequences: usize,
+}
+
+impl Default for EscapeBudget {
+    /// 64 KiB and 2000 escape sequences per second, a lot for a TUI that only redraws what changed.
+    fn default() -> EscapeBudget {
+        EscapeBudget { window: Duration::from_secs(1), bytes: 64 * 1024, sequences: 2000 }
+    }
+}
+
+/// The output written during a time window.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct OutputUsage {
+    /// The bytes written.
+    pub bytes: usize,
+    /// The escape sequences written, counted by their ESC bytes.
+    pub sequences: usize,
+}
+
+/// A writer reporting the time windows in which more was written than its budget allows.
+///
+/// A window is checked on the first write or flush after it ends, so the last window before output
+/// stops is never reported.
+pub struct BudgetWriter<W: Write, F: FnMut(OutputUsage)> {
+    inner: W,
+    budget: EscapeBudget,
+    on_exceeded: F,
+    /// When the current window started.
+    start: Instant,
+    /// The output of the current window so far.
+    usage: OutputUsage,
+}
+
+impl<W: Write, F: FnMut(OutputUsage)> BudgetWriter<W, F> {
+    /// Wrap `inner`, calling `on_exceeded` with the usage of every window over `budget`.
+    pub fn new(inner: W, budget: EscapeBudget, on_exceeded: F) -> BudgetWriter<W, F> {
+        BudgetWriter { inner, budget, on_exceeded, start: Instant::now(), usage: OutputUsage::default() }
+    }
+
+    /// Get the wrapped writer back.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Start a new window if the current one is over, reporting it if needed.
+    fn roll_window(&mut self) {
+        if self.start.elapsed() < self.budget.window {
+            return;
+        }
+        if self.usage.bytes > self.budget.bytes || self.usage.sequences > self.budget.sequences {
+            (self.on_exceeded)(self.usage);
+        }
+        self.start = Instant::now();
+        self.usage = OutputUsage::default();
+    }
+}
+
+impl<W: Write, F: FnMut(OutputUsage)> ops::Deref for BudgetWriter<W, F> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: Write, F: FnMut(OutputUsage)> ops::DerefMut for BudgetWriter<W, F> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write, F: FnMut(OutputUsage)> Write for BudgetWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.roll_window();
+        let n = self.inner.write(buf)?;
+        self.usage.bytes += n;
+        self.usage.sequences += buf[..n].iter().filter(|&&b| b == b'\x1B').count();
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.roll_window();
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_budget_writer() {
+        let mut reports = Vec::new();
+        let budget = EscapeBudget { window: Duration::ZERO, bytes: 8, sequences: 1 };
+        let mut out = BudgetWriter::new(Vec::new(), budget, |usage| reports.push(usage));
+
+        // With an empty window, every write ends the window of the previous one.
+        out.write_all(b"\x1B[H\x1B[2J").unwrap();
+        out.write_all(b"\x1B[H").unwrap();
+        out.write_all(b"abcdefghi").unwrap();
+        out.flush().unwrap();
+        assert_eq!(out.into_inner(), b"\x1B[H\x1B[2J\x1B[Habcdefghi");
+        assert_eq!(reports, [OutputUsage { bytes: 7, sequences: 2 }, OutputUsage { bytes: 9, sequences: 0 }]);
+    }
+}
@@ -0,0 +1,658 @@
+//! Adapters transforming a stream of events.
+
+use std::collections::{HashMap, VecDeque};
+use std::io;
+use std::time::{Duration, Instant};
+
+use crate::clock::{Clock, SystemClock};
+use crate::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventEx};
+
+/// An adapter turning mouse wheel events into arrow keys, keeping the modifiers held.
+///
+/// This does client-side what alternate scroll mode (`input::AlternateScroll`) asks the terminal
+/// to do, for programs that only handle the keyboard but have mouse reporting enabled anyway.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, KeyCode};
+/// use pres::event::adapters::WheelToArrows;
+/// use pres::input::TermRead;
+///
+/// let mut events = WheelToArrows::new(b"\x1B[<64;1;1M".events());
+/// assert_eq!(events.next().unwrap().unwrap(), Event::Key(KeyCode::Up.into()));
+///
+/// // Three lines per wheel step, as many desktop environments scroll.
+/// let mut events = WheelToArrows::new(b"\x1B[<65;1;1M".events()).with_multiplier(3);
+/// assert_eq!(events.by_ref().filter(|e| matches!(e, Ok(Event::Key(_)))).count(), 3);
+/// ```
+pub struct WheelToArrows<I> {
+    inner: I,
+    /// The arrow keys sent per wheel step.
+    multiplier: u16,
+    /// The arrow key of the last wheel step, and how many more times to report it.
+    repeat: Option<(KeyEvent, u16)>,
+}
+
+impl<I> WheelToArrows<I> {
+    /// Translate the wheel events coming from `inner`, one arrow key per wheel step.
+    pub fn new(inner: I) -> Self {
+        WheelToArrows { inner, multiplier: 1, repeat: None }
+    }
+
+    /// Send `multiplier` arrow keys per wheel step instead of one.
+    ///
+    /// A multiplier of 0 is taken as 1.
+    pub fn with_multiplier(mut self, multiplier: u16) -> Self {
+        self.multiplier = multiplier.max(1);
+        self
+    }
+}
+
+impl<I: Iterator<Item=Result<Event, io::Error>>> Iterator for WheelToArrows<I> {
+    type Item = Result<Event, io::Error>;
+
+    fn next(&mut self) -> Option<Result<Event, io::Error>> {
+        if let Some((key, left)) = self.repeat.take() {
+            if left > 1 {
+                self.repeat = Some((key, left - 1));
+            }
+            return Some(Ok(Event::Key(key)));
+        }
+        self.inner.next().map(|event| event.map(|event| {
+            let key = match event {
+                Event::Mouse(MouseEventEx { event: MouseEvent::Press(button, _, _), modifiers }) => match button {
+                    MouseButton::WheelUp => KeyEvent::new(KeyCode::Up, modifiers),
+                    MouseButton::WheelDown => KeyEvent::new(KeyCode::Down, modifiers),
+                    _ => return event,
+                },
+                event => return event,
+            };
+            if self.multiplier > 1 {
+                self.repeat = Some((key, self.multiplier - 1));
+            }
+            Event::Key(key)
+        }))
+    }
+}
+
+/// An adapter reporting double and triple clicks.
+///
+/// Terminals only report presses. When a button is pressed again within `interval` of its
+/// previous press, no further than `distance` cells away, a `MouseEvent::DoubleClick` follows the
+/// press, and a `MouseEvent::TripleClick` on the third one. A fourth press starts counting again.
+/// The wheel is not counted.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, MouseButton, MouseEvent};
+/// use pres::event::adapters::ClickCounter;
+/// use pres::input::TermRead;
+///
+/// let events = ClickCounter::new(b"\x1B[<0;5;5M\x1B[<0;5;5m\x1B[<0;5;5M".events());
+/// let clicks: Vec<MouseEvent> = events
+///     .filter_map(|event| match event.unwrap() { Event::Mouse(mouse) => Some(mouse.event), _ => None })
+///     .collect();
+/// assert_eq!(clicks.last(), Some(&MouseEvent::DoubleClick(MouseButton::Left, 5, 5)));
+/// ```
+pub struct ClickCounter<I, C = SystemClock> {
+    inner: I,
+    /// Where the time of each press is read.
+    clock: C,
+    /// The longest time between two presses of a multiple click.
+    interval: Duration,
+    /// The farthest, in cells along each axis, between two presses of a multiple click.
+    distance: u16,
+    /// The last press counted, when it happened and the presses in a row so far.
+    last: Option<(MouseButton, u16, u16, Instant, u8)>,
+    /// A synthesized click, to report after the press.
+    queued: Option<Event>,
+}
+
+impl<I> ClickCounter<I> {
+    /// Count the clicks coming from `inner`, at most 500 ms apart and on the same cell.
+    pub fn new(inner: I) -> Self {
+        ClickCounter {
+            inner,
+            clock: SystemClock,
+            interval: Duration::from_millis(500),
+            distance: 0,
+            last: None,
+            queued: None,
+        }
+    }
+}
+
+impl<I, C: Clock> ClickCounter<I, C> {
+    /// Time the presses with `clock` rather than the system clock.
+    pub fn with_clock<D: Clock>(self, clock: D) -> ClickCounter<I, D> {
+        let ClickCounter { inner, interval, distance, last, queued, .. } = self;
+        ClickCounter { inner, clock, interval, distance, last, queued }
+    }
+
+    /// Set the longest time between two presses of a multiple click.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Set how many cells, along each axis, the pointer may move between two presses of a
+    /// multiple click.
+    pub fn with_distance(mut self, distance: u16) -> Self {
+        self.distance = distance;
+        self
+    }
+
+    /// Count a press, returning how many there were in a row.
+    fn count(&mut self, button: MouseButton, x: u16, y: u16) -> u8 {
+        let now = self.clock.now();
+        let count = match self.last {
+            Some((last_button, last_x, last_y, at, count))
+                if last_button == button
+                    && count < 3
+                    && now.duration_since(at) <= self.interval
+                    && last_x.abs_diff(x) <= self.distance
+                    && last_y.abs_diff(y) <= self.distance => count + 1,
+            _ => 1,
+        };
+        self.last = Some((button, x, y, now, count));
+        count
+    }
+}
+
+impl<I: Iterator<Item=Result<Event, io::Error>>, C: Clock> Iterator for ClickCounter<I, C> {
+    type Item = Result<Event, io::Error>;
+
+    fn next(&mut self) -> Option<Result<Event, io::Error>> {
+        if let Some(queued) = self.queued.take() {
+            return Some(Ok(queued));
+        }
+        let event = self.inner.next()?;
+        if let Ok(Event::Mouse(MouseEventEx { event: MouseEvent::Press(button, x, y), modifiers })) = event {
+            if !matches!(button, MouseButton::WheelUp | MouseButton::WheelDown) {
+                let click = match self.count(button, x, y) {
+                    2 => Some(MouseEvent::DoubleClick(button, x, y)),
+                    3 => Some(MouseEvent::TripleClick(button, x, y)),
+                    _ => None,
+                };
+                self.queued = click.map(|click| Event::Mouse(MouseEventEx::new(click, modifiers)));
+            }
+        }
+        Some(event)
+    }
+}
+
+/// An adapter reporting mouse motion with a button held as drags from where it was pressed.
+///
+/// Each `MouseEvent::Hold` after a button press is replaced with a `MouseEvent::Drag` carrying
+/// the button and the coordinates of the press, until the button is released. Motion with no
+/// press seen before, e.g. when the press happened before mouse reporting was on, is passed
+/// through unchanged.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, MouseButton, MouseEvent};
+/// use pres::event::adapters::DragTracker;
+/// use pres::input::TermRead;
+///
+/// let mut events = DragTracker::new(b"\x1B[<0;2;3M\x1B[<32;5;6M".events()).skip(1);
+/// let drag = MouseEvent::Drag { button: MouseButton::Left, from: (2, 3), to: (5, 6) };
+/// assert_eq!(events.next().unwrap().unwrap(), Event::Mouse(drag.into()));
+/// ```
+pub struct DragTracker<I> {
+    inner: I,
+    /// The button held and where it was pressed.
+    origin: Option<(MouseButton, (u16, u16))>,
+}
+
+impl<I> DragTracker<I> {
+    /// Track the drags in the events coming from `inner`.
+    pub fn new(inner: I) -> Self {
+        DragTracker { inner, origin: None }
+    }
+}
+
+impl<I: Iterator<Item=Result<Event, io::Error>>> Iterator for DragTracker<I> {
+    type Item = Result<Event, io::Error>;
+
+    fn next(&mut self) -> Option<Result<Event, io::Error>> {
+        self.inner.next().map(|event| event.map(|event| {
+            let Event::Mouse(mut mouse) = event else { return event };
+            match mouse.event {
+                MouseEvent::Press(MouseButton::WheelUp | MouseButton::WheelDown, _, _) => {}
+                MouseEvent::Press(button, x, y) => self.origin = Some((button, (x, y))),
+                MouseEvent::Release(..) => self.origin = None,
+                MouseEvent::Hold(x, y) => {
+                    if let Some((button, from)) = self.origin {
+                        mouse.event = MouseEvent::Drag { button, from, to: (x, y) };
+                    }
+                }
+                _ => {}
+            }
+            Event::Mouse(mouse)
+        }))
+    }
+}
+
+/// Combining marks, the letters they compose with, and the resulting characters, in order.
+const COMPOSITIONS: [(char, &str, &str); 8] = [
+    ('\u{300}', "AaEeIiOoUu", "ÀàÈèÌìÒòÙù"),
+    ('\u{301}', "AaCcEeIiNnOoSsUuYyZzRrLl", "ÁáĆćÉéÍíŃńÓóŚśÚúÝýŹźŔŕĹĺ"),
+    ('\u{302}', "AaCcEeGgIiOoSsUuYy", "ÂâĈĉÊêĜĝÎîÔôŜŝÛûŶŷ"),
+    ('\u{303}', "AaIiNnOoUu", "ÃãĨĩÑñÕõŨũ"),
+    ('\u{308}', "AaEeIiOoUuYy", "ÄäËëÏïÖöÜüŸÿ"),
+    ('\u{30A}', "AaUu", "ÅåŮů"),
+    ('\u{327}', "CcGgNnSsRrTtLl", "ÇçĢģŅņŞşŖŗŢţĻļ"),
+    ('\u{30C}', "CcEeNnSsZzRrDdTtLl", "ČčĚěŇňŠšŽžŘřĎďŤťĽľ"),
+];
+
+/// The precomposed character for `base` with the combining `mark`, if there is one.
+fn compose(mark: char, base: char) -> Option<char> {
+    let (_, bases, composed) = COMPOSITIONS.iter().find(|(m, _, _)| *m == mark)?;
+    let index = bases.chars().position(|b| b == base)?;
+    composed.chars().nth(index)
+}
+
+/// The character typed by a plain key press, if `event` is one. Lock states do not count.
+fn typed_char(event: &Event) -> Option<char> {
+    let Event::Key(KeyEvent { code: KeyCode::Char(c), modifiers, kind, .. }) = *event else { return None };
+    let held = modifiers - KeyModifiers::SHIFT - KeyModifiers::CAPS_LOCK - KeyModifiers::NUM_LOCK;
+    (held.is_empty() && kind == KeyEventKind::Press).then_some(c)
+}
+
+/// An adapter composing dead keys sent as combining marks with the following letter.
+///
+/// Some terminal emulators pass a dead key through as a combining mark (U+0300 to U+036F) typed
+/// before the letter, instead of composing the character themselves. The mark is held until the
+/// next event and, if the two compose into a Latin character, that character is reported instead
+/// of both. Otherwise both are reported unchanged, as they are when the letter comes later than
+/// the timeout after the mark, one second unless set with `with_timeout`. The composed character
+/// keeps the modifiers of the letter, such as Shift.
+///
+/// Repeats and releases, reported with `input::KeyboardEnhancement` or win32-input-mode, are passed
+/// through as they come, leaving a held mark waiting for its letter.
+///
+/// The mark is held while `next` waits for the event after it: reading from a blocking source, it
+/// is only reported once another event comes or the input ends. A source that returns `None` when
+/// no input is available, such as `AsyncReader`, gets it back right away instead.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, Key};
+/// use pres::event::adapters::Compose;
+/// use pres::input::TermRead;
+///
+/// let mut events = Compose::new("\u{301}e".as_bytes().events());
+/// assert_eq!(events.next().unwrap().unwrap(), Event::Key(Key::Char('é').into()));
+/// ```
+pub struct Compose<I, C = SystemClock> {
+    inner: I,
+    /// Where the time of each mark and letter is read.
+    clock: C,
+    /// The longest time between a mark and the letter it composes with.
+    timeout: Duration,
+    /// The combining mark waiting for a letter, and when it was read.
+    mark: Option<(Event, Instant)>,
+    /// What was read after a mark that did not compose, to report next.
+    queued: Option<Result<Event, io::Error>>,
+}
+
+impl<I> Compose<I> {
+    /// Compose the dead keys coming from `inner`, with letters typed at most a second after them.
+    pub fn new(inner: I) -> Self {
+        Compose { inner, clock: SystemClock, timeout: Duration::from_secs(1), mark: None, queued: None }
+    }
+}
+
+impl<I, C: Clock> Compose<I, C> {
+    /// Time the marks and letters with `clock` rather than the system clock.
+    pub fn with_clock<D: Clock>(self, clock: D) -> Compose<I, D> {
+        let Compose { inner, timeout, mark, queued, .. } = self;
+        Compose { inner, clock, timeout, mark, queued }
+    }
+
+    /// Set the longest time between a mark and the letter it composes with.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+impl<I: Iterator<Item=Result<Event, io::Error>>, C: Clock> Iterator for Compose<I, C> {
+    type Item = Result<Event, io::Error>;
+
+    fn next(&mut self) -> Option<Result<Event, io::Error>> {
+        if let Some(queued) = self.queued.take() {
+            return Some(queued);
+        }
+        loop {
+            let event = match self.inner.next() {
+                Some(Ok(event)) => event,
+                other => {
+                    // Report the held mark before the error or the end.
+                    let Some((mark, _)) = self.mark.take() else { return other };
+                    self.queued = other;
+                    return Some(Ok(mark));
+                }
+            };
+            // Such as the release of the mark itself.
+            if matches!(&event, Event::Key(key) if key.kind != KeyEventKind::Press) {
+                return Some(Ok(event));
+            }
+            let c = typed_char(&event);
+            let now = self.clock.now();
+
+            if let Some((mark, at)) = self.mark.take() {
+                let composed = c
+                    .filter(|_| now.duration_since(at) <= self.timeout)
+                    .and_then(|c| compose(typed_char(&mark)?, c));
+                if let (Some(composed), Event::Key(letter)) = (composed, &event) {
+                    let key = KeyEvent::new(KeyCode::Char(composed), letter.modifiers).with_kind(letter.kind);
+                    return Some(Ok(Event::Key(key)));
+                }
+                self.queued = Some(Ok(event));
+                return Some(Ok(mark));
+            }
+
+            match c {
+                Some('\u{300}'..='\u{36F}') => self.mark = Some((event, now)),
+                _ => return Some(Ok(event)),
+            }
+        }
+    }
+}
+
+/// An adapter recording events into named registers and replaying them, for vim-style `q` and
+/// `@` macros.
+///
+/// Replayed events are yielded before any new input, and are not recorded again if a recording is
+/// running, so a macro replaying another one is recorded as the keys that started the replay.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, Key};
+/// use pres::event::adapters::MacroRecorder;
+/// use pres::input::TermRead;
+///
+/// // `q` starts and stops recording, `@` replays.
+/// let mut events = MacroRecorder::new(b"qxyq@".events());
+/// let mut keys = Vec::new();
+/// while let Some(event) = events.next() {
+///     match Key::from(match event.unwrap() { Event::Key(key) => key, _ => continue }) {
+///         Key::Char('q') if events.recording().is_some() => { events.stop_recording(); }
+///         Key::Char('q') => events.start_recording('m'),
+///         Key::Char('@') => { events.replay('m'); }
+///         key => keys.push(key),
+///     }
+/// }
+/// assert_eq!(keys, [Key::Char('x'), Key::Char('y'), Key::Char('x'), Key::Char('y')]);
+/// ```
+pub struct MacroRecorder<I> {
+    inner: I,
+    registers: HashMap<char, Vec<Event>>,
+    /// The register being recorded and what was recorded so far.
+    recording: Option<(char, Vec<Event>)>,
+    /// Whether the last event yielded was recorded.
+    last_recorded: bool,
+    /// Replayed events still to yield.
+    replaying: VecDeque<Event>,
+}
+
+impl<I> MacroRecorder<I> {
+    /// Record and replay the events coming from `inner`.
+    pub fn new(inner: I) -> Self {
+        MacroRecorder {
+            inner,
+            registers: HashMap::new(),
+            recording: None,
+            last_recorded: false,
+            replaying: VecDeque::new(),
+        }
+    }
+
+    /// Start recording the events read from now on into `register`, stopping any recording
+    /// already running.
+    pub fn start_recording(&mut self, register: char) {
+        self.stop_recording();
+        self.recording = Some((register, Vec::new()));
+    }
+
+    /// Stop recording, saving the register, and return its name.
+    ///
+    /// The event read last is left out, since it is the one that made the program stop. The
+    /// register is replaced, even if nothing else was recorded.
+    pub fn stop_recording(&mut self) -> Option<char> {
+        let (register, mut events) = self.recording.take()?;
+        if self.last_recorded {
+            events.pop();
+        }
+        self.registers.insert(register, events);
+        Some(register)
+    }
+
+    /// The register being recorded, if any.
+    pub fn recording(&self) -> Option<char> {
+        self.recording.as_ref().map(|&(register, _)| register)
+    }
+
+    /// The events saved in `register`.
+    pub fn register(&self, register: char) -> Option<&[Event]> {
+        self.registers.get(&register).map(Vec::as_slice)
+    }
+
+    /// Queue the events of `register` to be yielded next, returning whether it exists.
+    pub fn replay(&mut self, register: char) -> bool {
+        let Some(events) = self.registers.get(&register) else { return false };
+        // Before what is left of an outer replay.
+        for event in events.iter().rev() {
+            self.replaying.push_front(event.clone());
+        }
+        true
+    }
+}
+
+impl<I: Iterator<Item=Result<Event, io::Error>>> Iterator for MacroRecorder<I> {
+    type Item = Result<Event, io::Error>;
+
+    fn next(&mut self) -> Option<Result<Event, io::Error>> {
+        self.last_recorded = false;
+        if let Some(event) = self.replaying.pop_front() {
+            return Some(Ok(event));
+        }
+        let event = self.inner.next()?;
+        if let (Ok(event), Some((_, events))) = (&event, &mut self.recording) {
+            events.push(event.clone());
+            self.last_recorded = true;
+        }
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::Key;
+    use crate::input::TermRead;
+
+    #[test]
+    fn test_compose() {
+        let keys = |input: &str| -> Vec<Key> {
+            Compose::new(input.as_bytes().events())
+                .map(|event| match event.unwrap() {
+                    Event::Key(key) => key.into(),
+                    event => panic!("unexpected {:?}", event),
+                })
+                .collect()
+        };
+
+        assert_eq!(keys("\u{308}U\u{30C}s"), [Key::Char('Ü'), Key::Char('š')]);
+        // Marks that do not compose are passed through, as is a mark at the end of the input.
+        assert_eq!(keys("\u{301}xy\u{300}"),
+                   [Key::Char('\u{301}'), Key::Char('x'), Key::Char('y'), Key::Char('\u{300}')]);
+        assert_eq!(keys("\u{301}\u{1B}[A"), [Key::Char('\u{301}'), Key::Up]);
+        // Letters typed with Alt are not composed.
+        assert_eq!(keys("\u{301}\u{1B}e"), [Key::Char('\u{301}'), Key::Alt('e')]);
+        // Num Lock, on by default on Windows, is not a modifier held.
+        assert_eq!(keys("\u{1B}[0;0;769;1;32;1_\u{1B}[69;18;101;1;32;1_"), [Key::Char('é')]);
+    }
+
+    #[test]
+    fn test_compose_timeout() {
+        use crate::test::ManualClock;
+
+        let clock = ManualClock::new();
+        // Each key comes after the delay before it.
+        let typed = [(0, "\u{301}"), (1000, "e"), (0, "\u{301}"), (1001, "e")];
+        let input = typed.into_iter().flat_map(|(delay, key)| {
+            clock.advance(Duration::from_millis(delay));
+            key.as_bytes().events()
+        });
+        let keys: Vec<Key> = Compose::new(input)
+            .with_clock(clock.clone())
+            .map(|event| match event.unwrap() {
+                Event::Key(key) => key.into(),
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        // Exactly the timeout apart still composes, a millisecond more does not.
+        assert_eq!(keys, [Key::Char('é'), Key::Char('\u{301}'), Key::Char('e')]);
+    }
+
+    #[test]
+    fn test_compose_key_records() {
+        // The mark pressed and released, then Shift+E pressed and released, as the kitty keyboard
+        // protocol reports them with event types.
+        let input = "\u{1B}[769u\u{1B}[769;1:3u\u{1B}[101;2u\u{1B}[101;2:3u";
+        let events: Vec<Event> = Compose::new(input.as_bytes().events()).map(Result::unwrap).collect();
+        let key = |c, kind| Event::Key(KeyEvent::new(KeyCode::Char(c), KeyModifiers::SHIFT).with_kind(kind));
+        assert_eq!(events, [
+            Event::Key(KeyEvent::from(KeyCode::Char('\u{301}')).with_kind(KeyEventKind::Release)),
+            key('é', KeyEventKind::Press),
+            key('e', KeyEventKind::Release),
+        ]);
+
+        // A mark and a plain e with win32-input-mode, each pressed and released.
+        let input = "\u{1B}[0;0;769;1;0;1_\u{1B}[0;0;769;0;0;1_\u{1B}[69;18;101;1;0;1_\u{1B}[69;18;101;0;0;1_";
+        let keys: Vec<Key> = Compose::new(input.as_bytes().events())
+            .map(|event| match event.unwrap() {
+                Event::Key(key) => key.into(),
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        assert_eq!(keys, [Key::Char('\u{301}'), Key::Char('é'), Key::Char('e')]);
+    }
+
+    #[test]
+    fn test_wheel_to_arrows() {
+        let mut events = WheelToArrows::new(b"\x1B[<69;1;1M\x1B[<0;1;1M".events());
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(KeyEvent::new(KeyCode::Down, KeyModifiers::SHIFT)));
+        assert_eq!(events.next().unwrap().unwrap(), Event::Mouse(MouseEvent::Press(MouseButton::Left, 1, 1).into()));
+        assert!(events.next().is_none());
+
+        let mut events = WheelToArrows::new(b"\x1B[<64;1;1Mx".events()).with_multiplier(2);
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(KeyCode::Up.into()));
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(KeyCode::Up.into()));
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(KeyCode::Char('x').into()));
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn test_click_counter() {
+        let clicks = |counter: ClickCounter<_>| -> Vec<MouseEvent> {
+            counter
+                .filter_map(|event| match event.unwrap() {
+                    Event::Mouse(mouse) => Some(mouse.event),
+                    _ => None,
+                })
+                .filter(|event| !matches!(event, MouseEvent::Press(..) | MouseEvent::Release(..)))
+                .collect()
+        };
+        let input: &[u8] = b"\x1B[<0;5;5M\x1B[<0;5;5M\x1B[<0;6;5M\x1B[<0;6;5M\x1B[<2;6;5M\x1B[<64;6;5M\x1B[<2;6;5M";
+
+        // By default, only presses on the same cell count.
+        assert_eq!(clicks(ClickCounter::new(input.events())), [
+            MouseEvent::DoubleClick(MouseButton::Left, 5, 5),
+            MouseEvent::DoubleClick(MouseButton::Left, 6, 5),
+            MouseEvent::DoubleClick(MouseButton::Right, 6, 5),
+        ]);
+        // The fourth press starts counting again, as does another button.
+        assert_eq!(clicks(ClickCounter::new(input.events()).with_distance(1)), [
+            MouseEvent::DoubleClick(MouseButton::Left, 5, 5),
+            MouseEvent::TripleClick(MouseButton::Left, 6, 5),
+            MouseEvent::DoubleClick(MouseButton::Right, 6, 5),
+        ]);
+    }
+
+    #[test]
+    fn test_click_counter_interval() {
+        use crate::test::ManualClock;
+
+        let clock = ManualClock::new();
+        let press: &[u8] = b"\x1B[<0;5;5M";
+        // Each press comes after the delay before it.
+        let delays = [0, 500, 600, 501, 0, 0];
+        let input = delays.into_iter().flat_map(|delay| {
+            clock.advance(Duration::from_millis(delay));
+            press.events()
+        });
+        let clicks: Vec<MouseEvent> = ClickCounter::new(input)
+            .with_clock(clock.clone())
+            .filter_map(|event| match event.unwrap() {
+                Event::Mouse(MouseEventEx { event: event @ MouseEvent::DoubleClick(..), .. }) => Some(event),
+                Event::Mouse(MouseEventEx { event: event @ MouseEvent::TripleClick(..), .. }) => Some(event),
+                _ => None,
+            })
+            .collect();
+        // Exactly the interval apart still counts, a millisecond more does not.
+        assert_eq!(clicks, [
+            MouseEvent::DoubleClick(MouseButton::Left, 5, 5),
+            MouseEvent::DoubleClick(MouseButton::Left, 5, 5),
+            MouseEvent::TripleClick(MouseButton::Left, 5, 5),
+        ]);
+    }
+
+    #[test]
+    fn test_drag_tracker() {
+        let events: Vec<MouseEvent> = DragTracker::new(b"\x1B[<32;1;1M\x1B[<2;4;4M\x1B[<64;1;1M\x1B[<34;5;6M\x1B[<2;5;6m\x1B[<32;7;7M".events())
+            .map(|event| match event.unwrap() {
+                Event::Mouse(mouse) => mouse.event,
+                event => panic!("unexpected {:?}", event),
+            })
+            .collect();
+        // Motion before a press and after the release is left alone, as is the wheel.
+        assert_eq!(events, [
+            MouseEvent::Hold(1, 1),
+            MouseEvent::Press(MouseButton::Right, 4, 4),
+            MouseEvent::Press(MouseButton::WheelUp, 1, 1),
+            MouseEvent::Drag { button: MouseButton::Right, from: (4, 4), to: (5, 6) },
+            MouseEvent::Release(Some(MouseButton::Right), 5, 6),
+            MouseEvent::Hold(7, 7),
+        ]);
+    }
+
+    #[test]
+    fn test_macro_recorder() {
+        let mut events = MacroRecorder::new(b"ab\x1B[Acd".events());
+        events.next();
+        events.start_recording('r');
+        assert_eq!(events.recording(), Some('r'));
+        // The last event, which stops the recording, is left out.
+        events.by_ref().take(3).for_each(drop);
+        assert_eq!(events.stop_recording(), Some('r'));
+        assert_eq!(events.register('r').unwrap(), [Event::Key(Key::Char('b').into()), Event::Key(Key::Up.into())]);
+
+        // A replay comes before new input and is not recorded again.
+        events.start_recording('s');
+        assert!(events.replay('r'));
+        assert!(!events.replay('t'));
+        let replayed: Vec<Event> = events.by_ref().take(3).map(Result::unwrap).collect();
+        assert_eq!(replayed, [Event::Key(Key::Char('b').into()), Event::Key(Key::Up.into()),
+                              Event::Key(Key::Char('d').into())]);
+        events.stop_recording();
+        assert_eq!(events.register('s').unwrap(), []);
+    }
+}
@@ -0,0 +1,310 @@
+//! Encoding events back into the bytes a terminal sends.
+//!
+//! This is the inverse of `parse_event`: parsing the output of `encode` yields the original event
+//! again. Events have several possible encodings; the one produced is the most common one (xterm
+//! sequences, SGR mouse reports).
+
+use crate::event::{Event, Key, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::event::{MouseAction, MouseButton, MouseEvent, MouseEventEx};
+use crate::shell::ShellEvent;
+
+/// Encode `event` as terminal input.
+///
+/// Returns `None` for events that cannot be told apart from others once encoded, such as
+/// `Key::Char('\x01')`, which is read back as `Key::Ctrl('a')`, and for `Event::PastePart`, a piece
+/// of a paste rather than one. A truncated paste is encoded as a paste of the text kept.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, Key};
+/// use pres::event::encode::encode;
+///
+/// assert_eq!(encode(&Event::Key(Key::Left.into())), Some(b"\x1B[D".to_vec()));
+/// ```
+pub fn encode(event: &Event) -> Option<Vec<u8>> {
+    match event {
+        Event::Key(key) => encode_key_event(*key),
+        Event::Mouse(mouse) => Some(encode_mouse_event(*mouse)),
+        Event::Shell(shell) => Some(encode_shell(shell)),
+        Event::Paste(text) | Event::PasteTruncated { text, .. } => {
+            Some(format!("\x1B[200~{}\x1B[201~", text).into_bytes())
+        }
+        Event::PastePart(_) => None,
+        Event::Text(text) => Some(text.clone().into_bytes()),
+        Event::Resize(cols, rows) => Some(format!("\x1B[48;{};{};0;0t", rows, cols).into_bytes()),
+        Event::UnknownCsi { params, intermediates, final_byte } => {
+            let params: Vec<String> = params.iter().map(u16::to_string).collect();
+            let mut bytes = format!("\x1B[{}", params.join(";")).into_bytes();
+            bytes.extend_from_slice(intermediates);
+            bytes.push(*final_byte);
+            Some(bytes)
+        }
+        Event::Unsupported(bytes) => Some(bytes.clone()),
+    }
+}
+
+/// Encode a key press with modifiers, see `encode`.
+///
+/// Modified cursor, editing and function keys use xterm's `CSI 1 ; modifiers X` and
+/// `CSI number ; modifiers ~` forms, and other keys the kitty keyboard protocol's
+/// `CSI code ; modifiers u`. Repeats and releases append the kind to the modifiers, as the kitty
+/// protocol does. Returns `None` for keys none of these can express.
+pub fn encode_key_event(event: KeyEvent) -> Option<Vec<u8>> {
+    let key = Key::from(event);
+    if KeyEvent::from(key) == event {
+        return encode_key(key);
+    }
+
+    let mut modifiers = event.modifiers;
+    let (number, last) = match event.code {
+        KeyCode::Up => (1, b'A'),
+        KeyCode::Down => (1, b'B'),
+        KeyCode::Right => (1, b'C'),
+        KeyCode::Left => (1, b'D'),
+        KeyCode::Home => (1, b'H'),
+        KeyCode::End => (1, b'F'),
+        KeyCode::F(n @ 1..=4) => (1, b'P' + n - 1),
+        KeyCode::F(n @ 5..=20) => (function_key_number(n)?.into(), b'~'),
+        KeyCode::F(n @ 21..=35) => (57376 + u32::from(n) - 13, b'u'),
+        KeyCode::Insert => (2, b'~'),
+        KeyCode::Delete => (3, b'~'),
+        KeyCode::PageUp => (5, b'~'),
+        KeyCode::PageDown => (6, b'~'),
+        KeyCode::Tab => (9, b'u'),
+        KeyCode::BackTab => {
+            modifiers |= KeyModifiers::SHIFT;
+            (9, b'u')
+        }
+        KeyCode::Enter => (13, b'u'),
+        KeyCode::Esc => (27, b'u'),
+        KeyCode::Backspace => (127, b'u'),
+        KeyCode::Media(key) => (key.code(), b'u'),
+        KeyCode::Keypad(key) => (key.code(), b'u'),
+        // Control characters are read back as the keys above, and private use characters stand
+        // for keys without a `KeyCode`.
+        KeyCode::Char(c) if c >= ' ' && c != '\x7F' && !('\u{E000}'..='\u{F8FF}').contains(&c) => {
+            (c as u32, b'u')
+        }
+        _ => return None,
+    };
+    let kind = match event.kind {
+        KeyEventKind::Press => "",
+        KeyEventKind::Repeat => ":2",
+        KeyEventKind::Release => ":3",
+    };
+    let param = modifiers.bits() as u16 + 1;
+    Some(format!("\x1B[{};{}{}{}", number, param, kind, last as char).into_bytes())
+}
+
+/// The number of function key `n` in `CSI number ~` sequences, for F5 and up.
+fn function_key_number(n: u8) -> Option<u8> {
+    match n {
+        5 => Some(15),
+        6..=10 => Some(n + 11),
+        11..=14 => Some(n + 12),
+        15..=16 => Some(n + 13),
+        17..=20 => Some(n + 14),
+        _ => None,
+    }
+}
+
+/// Encode a legacy key press, see `encode`.
+pub fn encode_key(key: Key) -> Option<Vec<u8>> {
+    fn char_bytes(c: char) -> Vec<u8> {
+        c.to_string().into_bytes()
+    }
+
+    Some(match key {
+        Key::Backspace => b"\x7F".to_vec(),
+        Key::Left => b"\x1B[D".to_vec(),
+        Key::Right => b"\x1B[C".to_vec(),
+        Key::Up => b"\x1B[A".to_vec(),
+        Key::Down => b"\x1B[B".to_vec(),
+        Key::Home => b"\x1B[H".to_vec(),
+        Key::End => b"\x1B[F".to_vec(),
+        Key::PageUp => b"\x1B[5~".to_vec(),
+        Key::PageDown => b"\x1B[6~".to_vec(),
+        Key::BackTab => b"\x1B[Z".to_vec(),
+        Key::Delete => b"\x1B[3~".to_vec(),
+        Key::Insert => b"\x1B[2~".to_vec(),
+        Key::F(n @ 1..=4) => vec![0x1B, b'O', b'P' + n - 1],
+        Key::F(n) => format!("\x1B[{}~", function_key_number(n)?).into_bytes(),
+        Key::Media(key) => format!("\x1B[{}u", key.code()).into_bytes(),
+        Key::Char(c @ '\n') | Key::Char(c @ '\t') => char_bytes(c),
+        Key::Char(c) if c.is_control() => return None,
+        Key::Char(c) => char_bytes(c),
+        // `ESC O` and `ESC [` introduce sequences.
+        Key::Alt('O') | Key::Alt('[') | Key::Alt('\r') => return None,
+        Key::Alt(c) => {
+            let mut bytes = vec![0x1B];
+            bytes.extend(char_bytes(c));
+            bytes
+        }
+        // Ctrl+H, Ctrl+I, Ctrl+J and Ctrl+M are read back as Backspace, Tab and Enter.
+        Key::Ctrl(c @ 'a'..='z') if !matches!(c, 'h' | 'i' | 'j' | 'm') => vec![c as u8 - b'a' + 1],
+        Key::Ctrl(c @ '4'..='7') => vec![c as u8 - b'4' + 0x1C],
+        Key::Ctrl(' ') | Key::Null => vec![0],
+        Key::Ctrl(_) => return None,
+        Key::Esc => vec![0x1B],
+    })
+}
+
+/// Encode a mouse event as an SGR mouse report.
+pub fn encode_mouse(mouse: MouseEvent) -> Vec<u8> {
+    encode_mouse_event(mouse.into())
+}
+
+/// Encode a mouse event with modifiers as an SGR mouse report.
+///
+/// Reports only carry Shift, Alt and Ctrl; other modifiers are left out. `MouseEvent::Pixels`
+/// gives an SGR-Pixels report, with its pixel coordinates. Synthesized double and triple clicks
+/// and drags give the press or motion they were synthesized from.
+pub fn encode_mouse_event(mouse: MouseEventEx) -> Vec<u8> {
+    let (action, x, y) = match mouse.event {
+        MouseEvent::Press(button, x, y)
+        | MouseEvent::DoubleClick(button, x, y)
+        | MouseEvent::TripleClick(button, x, y) => (MouseAction::Press(button), x, y),
+        MouseEvent::Release(button, x, y) => (MouseAction::Release(button), x, y),
+        MouseEvent::Hold(x, y) | MouseEvent::Drag { to: (x, y), .. } => (MouseAction::Hold, x, y),
+        MouseEvent::Moved(x, y) => (MouseAction::Moved, x, y),
+        MouseEvent::Pixels { action, x, y, .. } => (action, x, y),
+    };
+    let (cb, last) = match action {
+        MouseAction::Press(button) => (button.code(), 'M'),
+        MouseAction::Release(button) => (button.map_or(3, MouseButton::code), 'm'),
+        MouseAction::Hold => (32, 'M'),
+        MouseAction::Moved => (35, 'M'),
+    };
+    format!("\x1B[<{};{};{}{}", cb | mouse.modifiers_code(), x, y, last).into_bytes()
+}
+
+/// Encode shell integration information as an OSC 633 sequence.
+fn encode_shell(shell: &ShellEvent) -> Vec<u8> {
+    fn escape(value: &str) -> String {
+        value.replace('\\', "\\\\").replace(';', "\\x3b")
+    }
+
+    match shell {
+        ShellEvent::Mark(mark) => mark.to_string().into_bytes(),
+        ShellEvent::CommandLine(command) => format!("\x1B]633;E;{}\x1B\\", escape(command)).into_bytes(),
+        ShellEvent::Property { name, value } => {
+            format!("\x1B]633;P;{}={}\x1B\\", name, escape(value)).into_bytes()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use proptest::prelude::*;
+
+    use crate::event::{KeypadKey, MediaKey};
+    use crate::input::TermRead;
+
+    use super::*;
+
+    fn media_key() -> impl Strategy<Value=MediaKey> {
+        (57428..=57440u32).prop_map(|code| MediaKey::from_code(code).unwrap())
+    }
+
+    fn keypad_key() -> impl Strategy<Value=KeypadKey> {
+        (57399..=57426u32).prop_map(|code| KeypadKey::from_code(code).unwrap())
+    }
+
+    fn key() -> impl Strategy<Value=Key> {
+        prop_oneof![
+            Just(Key::Backspace),
+            Just(Key::Left),
+            Just(Key::Right),
+            Just(Key::Up),
+            Just(Key::Down),
+            Just(Key::Home),
+            Just(Key::End),
+            Just(Key::PageUp),
+            Just(Key::PageDown),
+            Just(Key::BackTab),
+            Just(Key::Delete),
+            Just(Key::Insert),
+            Just(Key::Null),
+            (1..=20u8).prop_map(Key::F),
+            media_key().prop_map(Key::Media),
+            any::<char>().prop_map(Key::Char),
+            any::<char>().prop_map(Key::Alt),
+            any::<char>().prop_map(Key::Ctrl),
+        ]
+    }
+
+    fn mouse() -> impl Strategy<Value=MouseEventEx> {
+        let button = prop_oneof![
+            Just(MouseButton::Left),
+            Just(MouseButton::Middle),
+            Just(MouseButton::Right),
+            Just(MouseButton::WheelUp),
+            Just(MouseButton::WheelDown),
+            Just(MouseButton::Back),
+            Just(MouseButton::Forward),
+            Just(MouseButton::Unknown(130)),
+        ];
+        let event = prop_oneof![
+            (button.clone(), 1..=u16::MAX, 1..=u16::MAX).prop_map(|(b, x, y)| MouseEvent::Press(b, x, y)),
+            (prop::option::of(button), 1..=u16::MAX, 1..=u16::MAX)
+                .prop_map(|(b, x, y)| MouseEvent::Release(b, x, y)),
+            (1..=u16::MAX, 1..=u16::MAX).prop_map(|(x, y)| MouseEvent::Hold(x, y)),
+            (1..=u16::MAX, 1..=u16::MAX).prop_map(|(x, y)| MouseEvent::Moved(x, y)),
+        ];
+        // Mouse reports only carry Shift, Alt and Ctrl.
+        (event, 0..8u8).prop_map(|(event, bits)| MouseEventEx::new(event, KeyModifiers::from_bits(bits)))
+    }
+
+    fn modified_key() -> impl Strategy<Value=KeyEvent> {
+        let code = prop_oneof![
+            Just(KeyCode::Up),
+            Just(KeyCode::Left),
+            Just(KeyCode::Home),
+            Just(KeyCode::End),
+            Just(KeyCode::Delete),
+            Just(KeyCode::PageDown),
+            (1..=35u8).prop_map(KeyCode::F),
+            media_key().prop_map(KeyCode::Media),
+            keypad_key().prop_map(KeyCode::Keypad),
+            Just(KeyCode::Enter),
+            Just(KeyCode::Esc),
+            (b' '..=b'~').prop_map(|c| KeyCode::Char(c as char)),
+        ];
+        let kind = prop_oneof![
+            Just(KeyEventKind::Press),
+            Just(KeyEventKind::Repeat),
+            Just(KeyEventKind::Release),
+        ];
+        (code, 1..=u8::MAX, kind).prop_map(|(code, bits, kind)| {
+            KeyEvent::new(code, KeyModifiers::from_bits(bits)).with_kind(kind)
+        })
+    }
+
+    fn event() -> impl Strategy<Value=Event> {
+        prop_oneof![
+            key().prop_map(|key| Event::Key(key.into())),
+            modified_key().prop_map(Event::Key),
+            mouse().prop_map(Event::Mouse),
+            "[ -~\n]{0,16}".prop_map(Event::Paste),
+            (0..=u16::MAX, 0..=u16::MAX).prop_map(|(cols, rows)| Event::Resize(cols, rows)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(events in prop::collection::vec(event(), 0..32)) {
+            let events: Vec<Event> = events.into_iter().filter(|e| encode(e).is_some()).collect();
+            let bytes: Vec<u8> = events.iter().flat_map(|e| encode(e).unwrap()).collect();
+
+            let parsed: Vec<Event> = bytes.events().map(|e| e.unwrap()).collect();
+            prop_assert_eq!(parsed, events);
+        }
+    }
+
+    #[test]
+    fn test_esc_alone() {
+        let bytes = encode(&Event::Key(Key::Esc.into())).unwrap();
+        assert_eq!(bytes.keys().next().unwrap().unwrap(), Key::Esc);
+    }
+}
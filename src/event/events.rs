@@ -1,31 +1,113 @@
+use std::collections::VecDeque;
+use std::fmt;
 use std::io;
 use std::io::Read;
+use std::iter;
+use std::mem;
+use std::ops;
+use std::str;
 
 use crate::event;
-use crate::event::{Key, MouseEvent};
+use crate::r#async::PendingResize;
+use crate::metrics;
+use crate::event::{KeyCode, KeyEvent, MouseEventEx, ParseOptions, PasteBuf};
+use crate::shell::ShellEvent;
 
 /// An event reported by the terminal.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Event {
     /// A key press.
-    Key(Key),
-    /// A mouse button press, release or wheel use at specific coordinates.
-    Mouse(MouseEvent),
+    Key(KeyEvent),
+    /// A mouse button press, release or wheel use at specific coordinates, with the modifiers held.
+    Mouse(MouseEventEx),
+    /// Shell integration information, see `ParseOptions::shell_integration`.
+    Shell(ShellEvent),
+    /// Text pasted while `input::BracketedPaste` was active.
+    Paste(String),
+    /// A paste longer than `ParseOptions::max_paste_len`, cut short.
+    PasteTruncated {
+        /// The start of the pasted text, up to the limit.
+        text: String,
+        /// The number of bytes dropped after it.
+        dropped: usize,
+    },
+    /// A piece of a paste, see `ParseOptions::paste_chunk_len`.
+    PastePart(Paste),
+    /// Several printable characters received at once, see `ParseOptions::coalesce_text`.
+    Text(String),
+    /// The terminal window was resized to the given number of columns and rows.
+    ///
+    /// See `async_stdin_with_resize`, or the in-band reports of terminals supporting mode 2048.
+    Resize(u16, u16),
+    /// A CSI sequence this crate does not know, such as a terminal-specific extension.
+    ///
+    /// The sequence is `ESC [`, the parameters separated by `;`, the intermediate bytes and the
+    /// final byte. Its exact bytes are available from `Events::last_raw` or `EventsAndRaw`.
+    UnknownCsi {
+        /// The numeric parameters, with empty ones given as 0.
+        params: Vec<u16>,
+        /// The intermediate bytes, from 0x20 to 0x2F.
+        intermediates: Vec<u8>,
+        /// The final byte, from 0x40 to 0x7E.
+        final_byte: u8,
+    },
     /// An event that cannot currently be evaluated.
     Unsupported(Vec<u8>),
 }
 
+/// A piece of a paste reported as it arrives, see `ParseOptions::paste_chunk_len`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Paste {
+    /// Pasted text, with more to come.
+    Chunk(String),
+    /// The end of the paste.
+    Last {
+        /// The text after the last chunk.
+        text: String,
+        /// The number of bytes dropped past `ParseOptions::max_paste_len`.
+        dropped: usize,
+    },
+}
+
 
 /// An iterator over input events.
 pub struct Events<R> {
     pub inner: EventsAndRaw<R>,
+    /// The bytes of the last event yielded, see `last_raw`.
+    pub(crate) last_raw: RawBytes,
 }
 
 impl<R: Read> Iterator for Events<R> {
     type Item = Result<Event, io::Error>;
 
     fn next(&mut self) -> Option<Result<Event, io::Error>> {
-        self.inner.next().map(|tuple| tuple.map(|(event, _raw)| event))
+        match self.inner.next() {
+            Some(Ok((event, raw))) => {
+                self.last_raw = raw;
+                Some(Ok(event))
+            }
+            other => {
+                self.last_raw = RawBytes::new();
+                other.map(|tuple| tuple.map(|(event, _raw)| event))
+            }
+        }
+    }
+}
+
+impl<R> Events<R> {
+    /// The bytes the event last yielded was parsed from.
+    ///
+    /// This is empty before the first event and after an error. Unlike `TermReadEventsAndRaw`,
+    /// this lets a program look at the bytes of only the events it cares about, e.g. to forward
+    /// an `Event::Unsupported` sequence to a child process.
+    pub fn last_raw(&self) -> &[u8] {
+        &self.last_raw
+    }
+
+    /// Recognize the optional input syntax enabled in `options`.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.inner.options = options;
+        self
     }
 }
 
@@ -33,42 +115,102 @@ impl<R: Read> Iterator for Events<R> {
 pub struct EventsAndRaw<R> {
     pub source: R,
     pub leftover: Option<u8>,
+    pub options: ParseOptions,
+    /// Whether the last event was a carriage return, see `ParseOptions::normalize_newlines`.
+    pub(crate) after_cr: bool,
+    /// Bytes read ahead with `ParseOptions::coalesce_text`, not parsed yet.
+    pub(crate) pending: VecDeque<u8>,
+    /// Whether reading failed for good, see `Iterator::next`.
+    pub(crate) failed: bool,
+    /// The window size to report, for a reader from `async_stdin_with_resize`.
+    pub(crate) resize: Option<PendingResize>,
+    /// The paste being reported in pieces, see `ParseOptions::paste_chunk_len`.
+    pub(crate) paste: Option<PasteBuf>,
+}
+
+impl<R> EventsAndRaw<R> {
+    /// Recognize the optional input syntax enabled in `options`.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
 }
 
 impl<R: Read> Iterator for EventsAndRaw<R> {
-    type Item = Result<(Event, Vec<u8>), io::Error>;
+    type Item = Result<(Event, RawBytes), io::Error>;
 
-    fn next(&mut self) -> Option<Result<(Event, Vec<u8>), io::Error>> {
-        let source = &mut self.source;
+    /// The next event, or `None` at the end of the input.
+    ///
+    /// A read error other than `WouldBlock` is returned once and then ends the iteration, since
+    /// retrying a closed or invalid input would fail forever. Interrupted reads are retried.
+    fn next(&mut self) -> Option<Result<(Event, RawBytes), io::Error>> {
+        if self.failed {
+            return None;
+        }
+        if let Some((cols, rows)) = self.resize.as_ref().and_then(|resize| resize.lock().unwrap().take()) {
+            let event = Event::Resize(cols, rows);
+            if let Some(metrics) = metrics::get() {
+                metrics::record_event(metrics, &event, 0);
+            }
+            return Some(Ok((event, RawBytes::new())));
+        }
+        loop {
+            let res = match self.leftover.take() {
+                _ if self.paste.is_some() => self.read_paste(),
+                // we have a leftover byte, use it
+                Some(c) => self.parse(c),
+                None if !self.pending.is_empty() => self.parse_pending(),
+                None if self.options.coalesce_text => self.read_chunk()?,
+                None => self.read_event()?,
+            };
 
-        if let Some(c) = self.leftover {
-            // we have a leftover byte, use it
-            self.leftover = None;
-            return Some(parse_event(c, &mut source.bytes()));
+            let after_cr = mem::replace(&mut self.after_cr, false);
+            // A piece of a paste may be a lone CR too.
+            if let (true, Ok((Event::Key(_), raw))) = (self.options.normalize_newlines, &res) {
+                match &raw[..] {
+                    b"\r" => self.after_cr = true,
+                    // The second half of a CRLF, already reported as Enter.
+                    b"\n" if after_cr => {
+                        if let Some(metrics) = metrics::get() {
+                            metrics.bytes_read(1);
+                        }
+                        continue;
+                    }
+                    _ => {}
+                }
+            }
+
+            match (&res, metrics::get()) {
+                (Ok((event, raw)), Some(metrics)) => metrics::record_event(metrics, event, raw.len()),
+                (Ok(_), None) => {}
+                (Err(e), _) if e.kind() == io::ErrorKind::Interrupted => continue,
+                (Err(e), _) if e.kind() == io::ErrorKind::WouldBlock => {}
+                (Err(_), _) => self.failed = true,
+            }
+            return Some(res);
         }
+    }
+}
 
+impl<R: Read> EventsAndRaw<R> {
+    /// Read and parse an event, starting with fresh input.
+    fn read_event(&mut self) -> Option<Result<(Event, RawBytes), io::Error>> {
         // Here we read two bytes at a time. We need to distinguish between single ESC key presses,
         // and escape sequences (which start with ESC or a x1B byte). The idea is that if this is
         // an escape sequence, we will read multiple bytes (the first byte being ESC) but if this
         // is a single ESC keypress, we will only read a single byte.
         let mut buf = [0u8; 2];
-        let res = match source.read(&mut buf) {
+        let res = match self.source.read(&mut buf) {
             Ok(0) => return None,
             Ok(1) => {
                 match buf[0] {
-                    b'\x1B' => Ok((Event::Key(Key::Esc), vec![b'\x1B'])),
-                    c => parse_event(c, &mut source.bytes()),
+                    b'\x1B' => Ok((Event::Key(KeyCode::Esc.into()), RawBytes::from(&b"\x1B"[..]))),
+                    c => self.parse(c),
                 }
             }
             Ok(2) => {
-                let option_iter = &mut Some(buf[1]).into_iter();
-                let result = {
-                    let mut iter = option_iter.map(|c| Ok(c)).chain(source.bytes());
-                    parse_event(buf[0], &mut iter)
-                };
-                // If the option_iter wasn't consumed, keep the byte for later.
-                self.leftover = option_iter.next();
-                result
+                self.pending.push_front(buf[1]);
+                self.parse(buf[0])
             }
             Ok(_) => unreachable!(),
             Err(e) => Err(e),
@@ -76,17 +218,450 @@ impl<R: Read> Iterator for EventsAndRaw<R> {
 
         Some(res)
     }
+
+    /// Read whatever input is available at once, to be parsed by `parse_pending`.
+    fn read_chunk(&mut self) -> Option<Result<(Event, RawBytes), io::Error>> {
+        let mut buf = [0u8; 64];
+        match self.source.read(&mut buf) {
+            Ok(0) => None,
+            Ok(n) => {
+                // As in `read_event`, an ESC read on its own is the Esc key.
+                if &buf[..n] == b"\x1B" {
+                    return Some(Ok((Event::Key(KeyCode::Esc.into()), RawBytes::from(&b"\x1B"[..]))));
+                }
+                self.pending.extend(&buf[..n]);
+                Some(self.parse_pending())
+            }
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    /// Parse an event from the bytes read ahead, reading more from the source if they end in
+    /// the middle of a sequence.
+    fn parse_pending(&mut self) -> Result<(Event, RawBytes), io::Error> {
+        if self.options.coalesce_text {
+            let text = printable_prefix(self.pending.make_contiguous());
+            if text.chars().nth(1).is_some() {
+                let text = text.to_owned();
+                self.pending.drain(..text.len());
+                let raw = RawBytes::from(text.as_bytes());
+                return Ok((Event::Text(text), raw));
+            }
+        }
+        let c = self.pending.pop_front().unwrap();
+        self.parse(c)
+    }
+
+    /// Parse an event starting with `c`, reading on from the bytes read ahead, then the source.
+    ///
+    /// The bytes from the one that broke a malformed sequence on are kept to be parsed again, so
+    /// parsing resumes there.
+    fn parse(&mut self, c: u8) -> Result<(Event, RawBytes), io::Error> {
+        if c == b'\x1B' && self.options.paste_chunk_len.is_some() && self.paste_starts() {
+            self.paste = Some(PasteBuf::new(self.options.max_paste_len, self.options.paste_chunk_len));
+            let (event, piece) = self.read_paste()?;
+            let raw: Vec<u8> = PASTE_START.iter().chain(&*piece).copied().collect();
+            return Ok((event, RawBytes::from(&raw[..])));
+        }
+        let mut unread = Vec::new();
+        let mut dropped = 0;
+        let res = {
+            let pending = &mut self.pending;
+            let mut iter = iter::from_fn(|| pending.pop_front().map(Ok)).chain(self.source.by_ref().bytes());
+            parse_event(c, &mut iter, &self.options, &mut unread, &mut dropped)
+        };
+        for &byte in unread.iter().rev() {
+            self.pending.push_front(byte);
+        }
+        if let (1.., Some(metrics)) = (dropped, metrics::get()) {
+            metrics.bytes_read(dropped);
+        }
+        res
+    }
+
+    /// Whether the ESC just read starts a bracketed paste, reading on as far as it may. The bytes
+    /// after the ESC are left to parse unless they do.
+    fn paste_starts(&mut self) -> bool {
+        for (i, &expected) in PASTE_START[1..].iter().enumerate() {
+            if i == self.pending.len() {
+                let mut byte = [0];
+                // A read error comes again when parsing the sequence.
+                match self.source.read(&mut byte) {
+                    Ok(1) => self.pending.push_back(byte[0]),
+                    _ => return false,
+                }
+            }
+            if self.pending[i] != expected {
+                return false;
+            }
+        }
+        self.pending.drain(..PASTE_START.len() - 1);
+        true
+    }
+
+    /// Read on in the paste reported in pieces, up to its next piece. The raw bytes are the text
+    /// of the piece, and the end of the paste after the last one.
+    fn read_paste(&mut self) -> Result<(Event, RawBytes), io::Error> {
+        let Some(paste) = &mut self.paste else { unreachable!() };
+        loop {
+            let Some(byte) = self.leftover.take().or_else(|| self.pending.pop_front()) else {
+                let mut buf = [0u8; 1024];
+                match self.source.read(&mut buf)? {
+                    0 => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "input ended in a paste")),
+                    n => self.pending.extend(&buf[..n]),
+                }
+                continue;
+            };
+            let event = if paste.push(byte) {
+                self.paste.take().unwrap().finish()
+            } else if let Some(event) = paste.chunk() {
+                event
+            } else {
+                continue;
+            };
+            let raw = match &event {
+                Event::PastePart(Paste::Chunk(text)) => RawBytes::from(text.as_bytes()),
+                Event::PastePart(Paste::Last { text, .. }) => {
+                    let raw: Vec<u8> = text.bytes().chain(event::PASTE_END.iter().copied()).collect();
+                    RawBytes::from(&raw[..])
+                }
+                _ => unreachable!(),
+            };
+            return Ok((event, raw));
+        }
+    }
+}
+
+/// The longest run of printable characters at the start of `bytes`.
+pub(crate) fn printable_prefix(bytes: &[u8]) -> &str {
+    let valid = match str::from_utf8(bytes) {
+        Ok(text) => text,
+        Err(e) => str::from_utf8(&bytes[..e.valid_up_to()]).unwrap(),
+    };
+    let end = valid.find(char::is_control).unwrap_or(valid.len());
+    &valid[..end]
 }
 
-fn parse_event<I>(item: u8, iter: &mut I) -> Result<(Event, Vec<u8>), io::Error>
+/// Parse an event from `item` and `iter`, putting the bytes to parse again into `unread` when a
+/// malformed sequence is reported as `Event::Unsupported`.
+///
+/// `dropped` is set to the number of bytes read past the returned raw bytes and dropped: the
+/// rest of a truncated paste, or of a sequence cut by `ParseOptions::max_sequence_len`.
+pub(crate) fn parse_event<I>(item: u8, iter: &mut I, options: &ParseOptions, unread: &mut Vec<u8>,
+                             dropped: &mut usize)
+    -> Result<(Event, RawBytes), io::Error>
     where I: Iterator<Item=Result<u8, io::Error>>
 {
-    let mut buf = vec![item];
+    let mut buf = RawBytes::new();
+    buf.push(item);
+    *dropped = 0;
+    let mut ended = false;
+    // Whether `max_sequence_len` ended the sequence.
+    let mut cut = false;
+    let max_len = options.max_sequence_len.unwrap_or(usize::MAX);
+    // A paste cut by `max_paste_len` is only recorded up to the text kept.
+    let max_paste_raw = options.max_paste_len.map(|len| len.saturating_add(PASTE_START.len()));
     let result = {
-        let mut iter = iter.inspect(|byte| if let &Ok(byte) = byte {
-            buf.push(byte);
+        let mut iter = iter::from_fn(|| {
+            if let Some(max_paste_raw) = max_paste_raw {
+                if buf.len() >= max_paste_raw.min(max_len) && buf.starts_with(PASTE_START) {
+                    let next = iter.next();
+                    match next {
+                        Some(Ok(_)) => *dropped += 1,
+                        _ => ended = true,
+                    }
+                    return next;
+                }
+            }
+            // Too long, end the sequence here.
+            if buf.len() >= max_len {
+                ended = true;
+                cut = true;
+                return None;
+            }
+            let next = iter.next();
+            match next {
+                Some(Ok(byte)) => buf.push(byte),
+                _ => ended = true,
+            }
+            next
         });
-        event::parse_event(item, &mut iter)
+        event::parse_event_with(item, &mut iter, options)
     };
-    result.or(Ok(Event::Unsupported(buf.clone()))).map(|e| (e, buf))
+    if let Ok(event) = result {
+        return Ok((event, buf));
+    }
+
+    let mut resume = resume_point(&buf, ended, options);
+    let mut skipped = 0;
+    if options.resync {
+        while resume < buf.len() && !starts_event(buf[resume], options) {
+            resume += 1;
+            skipped += 1;
+        }
+        while resume == buf.len() && !ended && buf.len() < max_len {
+            match iter.next() {
+                Some(Ok(byte)) if starts_event(byte, options) => buf.push(byte),
+                Some(Ok(byte)) => {
+                    buf.push(byte);
+                    resume += 1;
+                    skipped += 1;
+                }
+                _ => ended = true,
+            }
+        }
+    }
+    unread.extend_from_slice(&buf[resume..]);
+    if cut {
+        *dropped = skip_rest(&buf, iter, options, unread);
+    }
+    let buf = RawBytes::from(&buf[..resume]);
+    if let Some(metrics) = metrics::get() {
+        metrics.parse_error(&buf);
+        if skipped > 0 {
+            metrics.bytes_skipped(skipped);
+        }
+    }
+    Ok((Event::Unsupported(buf.to_vec()), buf))
+}
+
+/// The start of a bracketed paste.
+pub(super) const PASTE_START: &[u8] = b"\x1B[200~";
+
+/// Read and drop the rest of the sequence starting with `raw`, cut by
+/// `ParseOptions::max_sequence_len`, up to its terminator, see `Skip`.
+///
+/// A byte breaking the sequence instead, such as an ESC starting another one, is put into
+/// `unread`. Returns the number of bytes dropped.
+fn skip_rest<I>(raw: &[u8], iter: &mut I, options: &ParseOptions, unread: &mut Vec<u8>) -> usize
+    where I: Iterator<Item=Result<u8, io::Error>>
+{
+    let Some(mut skip) = Skip::of(raw, options) else { return 0 };
+    while let Some(Ok(byte)) = iter.next() {
+        match skip.push(byte) {
+            Skipped::More => {}
+            Skipped::Ended => break,
+            Skipped::Broken(bytes) => {
+                unread.extend_from_slice(&bytes);
+                break;
+            }
+        }
+    }
+    skip.dropped
+}
+
+/// The rest of a sequence cut by `ParseOptions::max_sequence_len`, dropped a byte at a time up to
+/// its terminator: BEL or ST for a string sequence such as OSC, the final byte for a CSI
+/// sequence, the end of a paste. Without this, the rest of the payload would be parsed as key
+/// presses.
+#[derive(Debug, Clone)]
+pub(crate) struct Skip {
+    kind: SkipKind,
+    /// The last bytes seen, to find a terminator split across the cut.
+    tail: Vec<u8>,
+    /// The number of bytes dropped so far.
+    pub(crate) dropped: usize,
+}
+
+#[derive(Debug, Copy, Clone)]
+enum SkipKind {
+    Paste,
+    String,
+    Csi,
+}
+
+/// What a byte given to `Skip::push` did.
+pub(crate) enum Skipped {
+    /// It was dropped, and the sequence goes on.
+    More,
+    /// It ended the sequence, and was dropped.
+    Ended,
+    /// It broke the sequence: these bytes, ending with it, are to be parsed again.
+    Broken(Vec<u8>),
+}
+
+impl Skip {
+    /// Start dropping the rest of the sequence `raw` starts, if it has a terminator.
+    pub(crate) fn of(raw: &[u8], options: &ParseOptions) -> Option<Skip> {
+        const ESC: u8 = b'\x1B';
+        let kind = match raw {
+            _ if raw.starts_with(PASTE_START) => SkipKind::Paste,
+            [ESC, b']' | b'P' | b'_' | b'^' | b'X', ..] => SkipKind::String,
+            [ESC, b'[', ..] => SkipKind::Csi,
+            [event::C1_CSI, ..] if options.c1_controls => SkipKind::Csi,
+            _ => return None,
+        };
+        let tail = raw[raw.len().saturating_sub(event::PASTE_END.len())..].to_vec();
+        Some(Skip { kind, tail, dropped: 0 })
+    }
+
+    /// Drop the next byte of the sequence.
+    pub(crate) fn push(&mut self, byte: u8) -> Skipped {
+        const ESC: u8 = b'\x1B';
+        const BEL: u8 = b'\x07';
+        let after_esc = self.tail.last() == Some(&ESC);
+        if self.tail.len() == event::PASTE_END.len() {
+            self.tail.remove(0);
+        }
+        self.tail.push(byte);
+        match self.kind {
+            SkipKind::Paste if self.tail == event::PASTE_END => {}
+            SkipKind::String if byte == BEL || (after_esc && byte == b'\\') => {}
+            // The ESC started another sequence, and is given back unless it was before the cut.
+            SkipKind::String if after_esc && self.dropped > 0 => {
+                self.dropped -= 1;
+                return Skipped::Broken(vec![ESC, byte]);
+            }
+            SkipKind::String if after_esc => return Skipped::Broken(vec![byte]),
+            SkipKind::Csi => match byte {
+                0x20..=0x3F => {
+                    self.dropped += 1;
+                    return Skipped::More;
+                }
+                0x40..=0x7E => {}
+                _ => return Skipped::Broken(vec![byte]),
+            },
+            SkipKind::Paste | SkipKind::String => {
+                self.dropped += 1;
+                return Skipped::More;
+            }
+        }
+        self.dropped += 1;
+        Skipped::Ended
+    }
+}
+
+/// Whether `byte` can start an event, see `ParseOptions::resync`.
+fn starts_event(byte: u8, options: &ParseOptions) -> bool {
+    match byte {
+        b'\x1B' | b' '..=b'~' => true,
+        // The first byte of a UTF-8 character.
+        0xC2..=0xF4 => true,
+        event::C1_CSI | event::C1_SS3 => options.c1_controls,
+        _ => false,
+    }
+}
+
+/// Where parsing resumes after the malformed sequence `raw`: at the byte that broke it, which is
+/// the last one read, or at the ESC of an interrupted OSC terminator. Nothing broke a sequence
+/// cut short by the end of the input or `ParseOptions::max_sequence_len` (`ended`).
+fn resume_point(raw: &[u8], ended: bool, options: &ParseOptions) -> usize {
+    const ESC: u8 = b'\x1B';
+    let len = raw.len();
+    if ended || len < 2 {
+        return len;
+    }
+    let last = raw[len - 1];
+    // An ESC starts another sequence, and non-ASCII bytes start a character. Other controls are
+    // taken as part of the garbage, as CAN and SUB cancel a sequence on a VT.
+    let breaks_sequence = last == ESC || !last.is_ascii();
+    let is_continuation = last & 0xC0 == 0x80;
+    match raw {
+        [ESC, b']', .., ESC, end] if *end != b'\\' => len - 2,
+        [ESC, b'[' | b'O', _, ..] if breaks_sequence => len - 1,
+        [event::C1_CSI | event::C1_SS3, ..] if options.c1_controls && breaks_sequence => len - 1,
+        // A UTF-8 character, possibly typed with Alt.
+        [0x80..=0xFF, ..] | [ESC, 0x80..=0xFF, ..] if !is_continuation => len - 1,
+        _ => len,
+    }
+}
+
+/// The number of bytes `RawBytes` stores without allocating.
+const INLINE_RAW_BYTES: usize = 32;
+
+/// The bytes an event was parsed from.
+///
+/// Escape sequences are short, so these are kept inline up to a small size and only moved to the
+/// heap for long input such as OSC payloads.
+#[derive(Clone)]
+pub struct RawBytes {
+    len: usize,
+    inline: [u8; INLINE_RAW_BYTES],
+    heap: Vec<u8>,
+}
+
+impl RawBytes {
+    pub(crate) fn new() -> RawBytes {
+        RawBytes { len: 0, inline: [0; INLINE_RAW_BYTES], heap: Vec::new() }
+    }
+
+    fn push(&mut self, byte: u8) {
+        if self.len < INLINE_RAW_BYTES {
+            self.inline[self.len] = byte;
+        } else {
+            if self.len == INLINE_RAW_BYTES {
+                self.heap.extend_from_slice(&self.inline);
+            }
+            self.heap.push(byte);
+        }
+        self.len += 1;
+    }
+}
+
+impl<'a> From<&'a [u8]> for RawBytes {
+    fn from(bytes: &'a [u8]) -> RawBytes {
+        let mut raw = RawBytes::new();
+        bytes.iter().for_each(|&b| raw.push(b));
+        raw
+    }
+}
+
+impl From<RawBytes> for Vec<u8> {
+    fn from(raw: RawBytes) -> Vec<u8> {
+        raw.to_vec()
+    }
+}
+
+impl ops::Deref for RawBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        if self.len <= INLINE_RAW_BYTES { &self.inline[..self.len] } else { &self.heap }
+    }
+}
+
+impl AsRef<[u8]> for RawBytes {
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl<'a> IntoIterator for &'a RawBytes {
+    type Item = &'a u8;
+    type IntoIter = std::slice::Iter<'a, u8>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl PartialEq for RawBytes {
+    fn eq(&self, other: &RawBytes) -> bool {
+        **self == **other
+    }
+}
+
+impl Eq for RawBytes {}
+
+impl fmt::Debug for RawBytes {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        (**self).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_raw_bytes_spill() {
+        let short = RawBytes::from(&b"\x1B[A"[..]);
+        assert_eq!(&*short, b"\x1B[A");
+        assert_eq!(short.heap.capacity(), 0);
+
+        let long: Vec<u8> = (0..100).collect();
+        let raw = RawBytes::from(&long[..]);
+        assert_eq!(&*raw, &long[..]);
+        assert_eq!(Vec::from(raw), long);
+    }
 }
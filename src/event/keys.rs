@@ -1,4 +1,11 @@
-/// A key.
+use std::fmt;
+use std::ops;
+
+/// A key, as reported by `TermRead::keys`.
+///
+/// Modifiers are only kept where the legacy encoding has them, as `Alt` and `Ctrl`; see `KeyEvent`
+/// for the full picture.
+#[non_exhaustive]
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum Key {
     /// Backspace.
@@ -26,9 +33,9 @@ pub enum Key {
     /// Insert key.
     Insert,
     /// Function keys.
-    ///
-    /// Only function keys 1 through 12 are supported.
     F(u8),
+    /// A media key.
+    Media(MediaKey),
     /// Normal character.
     Char(char),
     /// Alt modified character.
@@ -36,12 +43,552 @@ pub enum Key {
     /// Ctrl modified character.
     ///
     /// Note that certain keys may not be modifiable with `ctrl`, due to limitations of terminals.
+    /// Ctrl+Space is `Ctrl(' ')`.
     Ctrl(char),
     /// Null byte.
+    ///
+    /// Terminals send it for Ctrl+Space (and Ctrl+@ or Ctrl+2), so it is read as `Ctrl(' ')`.
+    Null,
+    /// Esc key.
+    Esc,
+}
+/// A key, without modifiers.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    /// Backspace.
+    Backspace,
+    /// Enter key.
+    ///
+    /// Shift and Ctrl are only reported with `input::KeyboardEnhancement` or
+    /// `input::ModifyOtherKeys`; otherwise the terminal sends a plain Enter for them.
+    Enter,
+    /// Left arrow.
+    Left,
+    /// Right arrow.
+    Right,
+    /// Up arrow.
+    Up,
+    /// Down arrow.
+    Down,
+    /// Home key.
+    Home,
+    /// End key.
+    End,
+    /// Page Up key.
+    PageUp,
+    /// Page Down key.
+    PageDown,
+    /// Tab key.
+    Tab,
+    /// Backward Tab key.
+    BackTab,
+    /// Delete key.
+    Delete,
+    /// Insert key.
+    Insert,
+    /// Function keys. `KeyCode::F(1)` is F1.
+    F(u8),
+    /// A media key, only reported with the kitty keyboard protocol.
+    Media(MediaKey),
+    /// A keypad key, reported in application keypad mode and with the kitty keyboard protocol.
+    ///
+    /// Otherwise the terminal sends the keys the keypad stands for, such as `Char('5')`.
+    Keypad(KeypadKey),
+    /// A character.
+    Char(char),
+    /// Null byte.
+    ///
+    /// Never read: terminals send it for Ctrl+Space, so it is read as `Char(' ')` with Ctrl, as
+    /// the kitty keyboard protocol reports it.
     Null,
     /// Esc key.
     Esc,
+}
+
+/// A media key, as found on multimedia keyboards.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MediaKey {
+    /// Play.
+    Play,
+    /// Pause.
+    Pause,
+    /// Play/Pause toggle.
+    PlayPause,
+    /// Play in reverse.
+    Reverse,
+    /// Stop.
+    Stop,
+    /// Fast-forward.
+    FastForward,
+    /// Rewind.
+    Rewind,
+    /// Next track.
+    TrackNext,
+    /// Previous track.
+    TrackPrevious,
+    /// Record.
+    Record,
+    /// Lower the volume.
+    LowerVolume,
+    /// Raise the volume.
+    RaiseVolume,
+    /// Mute the volume.
+    MuteVolume,
+}
+
+impl MediaKey {
+    /// The media keys, in the order of their kitty keyboard protocol codes.
+    const ALL: [MediaKey; 13] = [
+        MediaKey::Play,
+        MediaKey::Pause,
+        MediaKey::PlayPause,
+        MediaKey::Reverse,
+        MediaKey::Stop,
+        MediaKey::FastForward,
+        MediaKey::Rewind,
+        MediaKey::TrackNext,
+        MediaKey::TrackPrevious,
+        MediaKey::Record,
+        MediaKey::LowerVolume,
+        MediaKey::RaiseVolume,
+        MediaKey::MuteVolume,
+    ];
+
+    /// The kitty keyboard protocol code of the first media key.
+    const FIRST_CODE: u32 = 57428;
+
+    /// The media key with the kitty keyboard protocol code `code`, if it is one.
+    pub(crate) fn from_code(code: u32) -> Option<MediaKey> {
+        MediaKey::ALL.get(code.checked_sub(MediaKey::FIRST_CODE)? as usize).copied()
+    }
+
+    /// The kitty keyboard protocol code of this key.
+    pub(crate) fn code(self) -> u32 {
+        MediaKey::FIRST_CODE + MediaKey::ALL.iter().position(|&key| key == self).unwrap() as u32
+    }
+}
+
+/// A key on the numeric keypad.
+#[non_exhaustive]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum KeypadKey {
+    /// A digit key, from 0 to 9.
+    Digit(u8),
+    /// Decimal point.
+    Decimal,
+    /// Divide (`/`).
+    Divide,
+    /// Multiply (`*`).
+    Multiply,
+    /// Subtract (`-`).
+    Subtract,
+    /// Add (`+`).
+    Add,
+    /// Keypad Enter.
+    Enter,
+    /// Equals (`=`).
+    Equal,
+    /// Separator (`,`).
+    Separator,
+    /// Left arrow.
+    Left,
+    /// Right arrow.
+    Right,
+    /// Up arrow.
+    Up,
+    /// Down arrow.
+    Down,
+    /// Page Up key.
+    PageUp,
+    /// Page Down key.
+    PageDown,
+    /// Home key.
+    Home,
+    /// End key.
+    End,
+    /// Insert key.
+    Insert,
+    /// Delete key.
+    Delete,
+}
+
+impl KeypadKey {
+    /// The keypad keys after the digits, in the order of their kitty keyboard protocol codes.
+    const OTHERS: [KeypadKey; 18] = [
+        KeypadKey::Decimal,
+        KeypadKey::Divide,
+        KeypadKey::Multiply,
+        KeypadKey::Subtract,
+        KeypadKey::Add,
+        KeypadKey::Enter,
+        KeypadKey::Equal,
+        KeypadKey::Separator,
+        KeypadKey::Left,
+        KeypadKey::Right,
+        KeypadKey::Up,
+        KeypadKey::Down,
+        KeypadKey::PageUp,
+        KeypadKey::PageDown,
+        KeypadKey::Home,
+        KeypadKey::End,
+        KeypadKey::Insert,
+        KeypadKey::Delete,
+    ];
+
+    /// The kitty keyboard protocol code of keypad 0.
+    const FIRST_CODE: u32 = 57399;
+
+    /// The keypad key with the kitty keyboard protocol code `code`, if it is one.
+    pub(crate) fn from_code(code: u32) -> Option<KeypadKey> {
+        match code.checked_sub(KeypadKey::FIRST_CODE)? {
+            n @ 0..=9 => Some(KeypadKey::Digit(n as u8)),
+            n => KeypadKey::OTHERS.get(n as usize - 10).copied(),
+        }
+    }
+
+    /// The kitty keyboard protocol code of this key.
+    pub(crate) fn code(self) -> u32 {
+        KeypadKey::FIRST_CODE + match self {
+            KeypadKey::Digit(n) => u32::from(n),
+            key => 10 + KeypadKey::OTHERS.iter().position(|&other| other == key).unwrap() as u32,
+        }
+    }
+
+    /// The key this keypad key stands for on the main keyboard, such as `Char('5')` for
+    /// `Digit(5)`.
+    ///
+    /// This is what the terminal sends for the keypad outside application keypad mode.
+    ///
+    /// # Panics
+    ///
+    /// Panics for a `Digit` over 9.
+    pub fn main_key(self) -> KeyCode {
+        match self {
+            KeypadKey::Digit(n) => KeyCode::Char(char::from_digit(n.into(), 10).expect("keypad digit over 9")),
+            KeypadKey::Decimal => KeyCode::Char('.'),
+            KeypadKey::Divide => KeyCode::Char('/'),
+            KeypadKey::Multiply => KeyCode::Char('*'),
+            KeypadKey::Subtract => KeyCode::Char('-'),
+            KeypadKey::Add => KeyCode::Char('+'),
+            KeypadKey::Enter => KeyCode::Enter,
+            KeypadKey::Equal => KeyCode::Char('='),
+            KeypadKey::Separator => KeyCode::Char(','),
+            KeypadKey::Left => KeyCode::Left,
+            KeypadKey::Right => KeyCode::Right,
+            KeypadKey::Up => KeyCode::Up,
+            KeypadKey::Down => KeyCode::Down,
+            KeypadKey::PageUp => KeyCode::PageUp,
+            KeypadKey::PageDown => KeyCode::PageDown,
+            KeypadKey::Home => KeyCode::Home,
+            KeypadKey::End => KeyCode::End,
+            KeypadKey::Insert => KeyCode::Insert,
+            KeypadKey::Delete => KeyCode::Delete,
+        }
+    }
+}
+
+impl KeyCode {
+    /// The character this key types, counting Enter and Tab.
+    fn as_char(self) -> Option<char> {
+        match self {
+            KeyCode::Char(c) => Some(c),
+            KeyCode::Enter => Some('\n'),
+            KeyCode::Tab => Some('\t'),
+            _ => None,
+        }
+    }
+
+    /// The key typing `c`, counting Enter and Tab.
+    fn from_char(c: char) -> KeyCode {
+        match c {
+            '\n' => KeyCode::Enter,
+            '\t' => KeyCode::Tab,
+            c => KeyCode::Char(c),
+        }
+    }
+}
+
+/// The modifier keys held during a key press.
+///
+/// The bits are those of the xterm and kitty keyboard protocols, where the modifier parameter of a
+/// sequence is one more than these bits.
+#[derive(Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct KeyModifiers(u8);
+
+impl KeyModifiers {
+    /// No modifiers.
+    pub const NONE: KeyModifiers = KeyModifiers(0);
+    /// Shift.
+    pub const SHIFT: KeyModifiers = KeyModifiers(1);
+    /// Alt, or Option on macOS.
+    pub const ALT: KeyModifiers = KeyModifiers(1 << 1);
+    /// Ctrl.
+    pub const CONTROL: KeyModifiers = KeyModifiers(1 << 2);
+    /// Super, the Windows or Command key.
+    pub const SUPER: KeyModifiers = KeyModifiers(1 << 3);
+    /// Hyper.
+    pub const HYPER: KeyModifiers = KeyModifiers(1 << 4);
+    /// Meta.
+    pub const META: KeyModifiers = KeyModifiers(1 << 5);
+    /// Caps Lock is on.
+    pub const CAPS_LOCK: KeyModifiers = KeyModifiers(1 << 6);
+    /// Num Lock is on.
+    pub const NUM_LOCK: KeyModifiers = KeyModifiers(1 << 7);
+
+    /// The modifiers with the given bits.
+    pub const fn from_bits(bits: u8) -> KeyModifiers {
+        KeyModifiers(bits)
+    }
+
+    /// The bits of these modifiers.
+    pub const fn bits(self) -> u8 {
+        self.0
+    }
+
+    /// Whether no modifier is held.
+    pub const fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+
+    /// Whether all of `other` is held.
+    pub const fn contains(self, other: KeyModifiers) -> bool {
+        self.0 & other.0 == other.0
+    }
+}
+
+impl ops::BitOr for KeyModifiers {
+    type Output = KeyModifiers;
+
+    fn bitor(self, other: KeyModifiers) -> KeyModifiers {
+        KeyModifiers(self.0 | other.0)
+    }
+}
+
+impl ops::BitOrAssign for KeyModifiers {
+    fn bitor_assign(&mut self, other: KeyModifiers) {
+        self.0 |= other.0;
+    }
+}
+
+impl ops::Sub for KeyModifiers {
+    type Output = KeyModifiers;
+
+    /// The modifiers of `self` not in `other`.
+    fn sub(self, other: KeyModifiers) -> KeyModifiers {
+        KeyModifiers(self.0 & !other.0)
+    }
+}
+
+impl fmt::Debug for KeyModifiers {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        const NAMES: [&str; 8] = ["SHIFT", "ALT", "CONTROL", "SUPER", "HYPER", "META", "CAPS_LOCK", "NUM_LOCK"];
+
+        if self.is_empty() {
+            return write!(f, "KeyModifiers(NONE)");
+        }
+        let names: Vec<&str> = (0..8).filter(|i| self.0 & (1 << i) != 0).map(|i| NAMES[i]).collect();
+        write!(f, "KeyModifiers({})", names.join(" | "))
+    }
+}
+
+/// Whether a key was pressed, held down or released.
+///
+/// Only the kitty keyboard protocol with `KeyboardEnhancementFlags::REPORT_EVENT_TYPES` reports
+/// repeats and releases; every other key event is a press.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum KeyEventKind {
+    /// The key was pressed.
+    #[default]
+    Press,
+    /// The key is held down and auto-repeats.
+    Repeat,
+    /// The key was released.
+    Release,
+}
+
+/// A key press, with the modifiers held.
+///
+/// Terminals only report some combinations: with the legacy encoding, Ctrl and Alt are only seen
+/// together with characters, and Shift only with a few navigation keys.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Key, KeyCode, KeyEvent, KeyModifiers};
+///
+/// let event = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL);
+/// assert_eq!(KeyEvent::from(Key::Ctrl('a')), event);
+/// assert_eq!(Key::from(event), Key::Ctrl('a'));
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct KeyEvent {
+    /// The key itself.
+    pub code: KeyCode,
+    /// The modifiers held.
+    pub modifiers: KeyModifiers,
+    /// Whether this is a press, repeat or release.
+    pub kind: KeyEventKind,
+    /// The key in the same place on a US keyboard, when it is another one. Only reported by the
+    /// kitty keyboard protocol, with `input::KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS`.
+    pub base_layout: Option<KeyCode>,
+}
+
+impl KeyEvent {
+    /// A press of `code` with `modifiers` held.
+    pub const fn new(code: KeyCode, modifiers: KeyModifiers) -> KeyEvent {
+        KeyEvent { code, modifiers, kind: KeyEventKind::Press, base_layout: None }
+    }
+
+    /// The same key event, as a press, repeat or release.
+    pub const fn with_kind(self, kind: KeyEventKind) -> KeyEvent {
+        KeyEvent { kind, ..self }
+    }
+
+    /// The same key event, with `base_layout` in the same place on a US keyboard.
+    pub const fn with_base_layout(self, base_layout: KeyCode) -> KeyEvent {
+        KeyEvent { base_layout: Some(base_layout), ..self }
+    }
+
+    /// Whether this press or repeat triggers `shortcut`, whatever the keyboard layout.
+    ///
+    /// A key typing a non-ASCII character also matches by its place on a US keyboard, when known,
+    /// so Ctrl+Z is found at the same place on a Cyrillic layout. Other keys match by what they
+    /// type, so Ctrl+Z is the key labelled Z on an AZERTY layout. Lock states are ignored.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// use pres::event::{KeyCode, KeyEvent, KeyModifiers};
+    ///
+    /// let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+    /// assert!(ctrl('я').with_base_layout(KeyCode::Char('z')).matches(ctrl('z')));
+    /// // On AZERTY, A is where Q is on a US keyboard.
+    /// assert!(ctrl('a').with_base_layout(KeyCode::Char('q')).matches(ctrl('a')));
+    /// assert!(!ctrl('a').with_base_layout(KeyCode::Char('q')).matches(ctrl('q')));
+    /// ```
+    pub fn matches(&self, shortcut: KeyEvent) -> bool {
+        let locks = KeyModifiers::CAPS_LOCK | KeyModifiers::NUM_LOCK;
+        let non_ascii = matches!(self.code, KeyCode::Char(c) if !c.is_ascii());
+        self.kind != KeyEventKind::Release
+            && self.modifiers - locks == shortcut.modifiers - locks
+            && (self.code == shortcut.code || non_ascii && self.base_layout == Some(shortcut.code))
+    }
+}
+
+impl From<KeyCode> for KeyEvent {
+    fn from(code: KeyCode) -> KeyEvent {
+        KeyEvent::new(code, KeyModifiers::NONE)
+    }
+}
+
+impl From<Key> for KeyEvent {
+    /// The key event for a legacy key. `Key::Char('\n')` and `Key::Char('\t')` are Enter and Tab,
+    /// `Key::Alt('\x7F')` is Backspace with Alt, and `Key::Null` is Ctrl+Space like
+    /// `Key::Ctrl(' ')`.
+    ///
+    /// `Key::Ctrl('h')` is H with Ctrl, even though the byte a terminal sends for it is parsed as
+    /// Backspace with Ctrl, which the terminal sends for that too.
+    fn from(key: Key) -> KeyEvent {
+        let code = match key {
+            Key::Backspace => KeyCode::Backspace,
+            Key::Left => KeyCode::Left,
+            Key::Right => KeyCode::Right,
+            Key::Up => KeyCode::Up,
+            Key::Down => KeyCode::Down,
+            Key::Home => KeyCode::Home,
+            Key::End => KeyCode::End,
+            Key::PageUp => KeyCode::PageUp,
+            Key::PageDown => KeyCode::PageDown,
+            Key::BackTab => KeyCode::BackTab,
+            Key::Delete => KeyCode::Delete,
+            Key::Insert => KeyCode::Insert,
+            Key::F(n) => KeyCode::F(n),
+            Key::Media(key) => KeyCode::Media(key),
+            Key::Char(c) => KeyCode::from_char(c),
+            // Terminals send this for Alt+Backspace.
+            Key::Alt('\x7F') => return KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT),
+            Key::Alt(c) => return KeyEvent::new(KeyCode::from_char(c), KeyModifiers::ALT),
+            Key::Ctrl(c) => return KeyEvent::new(KeyCode::from_char(c), KeyModifiers::CONTROL),
+            Key::Null => return KeyEvent::new(KeyCode::Char(' '), KeyModifiers::CONTROL),
+            Key::Esc => KeyCode::Esc,
+        };
+        KeyEvent::from(code)
+    }
+}
+
+impl From<KeyEvent> for Key {
+    /// The closest legacy key.
+    ///
+    /// This is lossy: modifiers of non-character keys other than Backspace are dropped, Ctrl wins
+    /// over Alt, Shift is ignored since it is already applied to characters, and so is the kind.
+    /// Keypad keys are read as the keys they stand for.
+    fn from(event: KeyEvent) -> Key {
+        if let KeyCode::Keypad(key) = event.code {
+            return Key::from(KeyEvent { code: key.main_key(), ..event });
+        }
+        if let Some(c) = event.code.as_char() {
+            return if event.modifiers.contains(KeyModifiers::CONTROL) {
+                Key::Ctrl(c)
+            } else if event.modifiers.contains(KeyModifiers::ALT) {
+                Key::Alt(c)
+            } else {
+                Key::Char(c)
+            };
+        }
+        match event.code {
+            KeyCode::Backspace if event.modifiers.contains(KeyModifiers::CONTROL) => Key::Ctrl('h'),
+            KeyCode::Backspace if event.modifiers.contains(KeyModifiers::ALT) => Key::Alt('\x7F'),
+            KeyCode::Backspace => Key::Backspace,
+            KeyCode::Left => Key::Left,
+            KeyCode::Right => Key::Right,
+            KeyCode::Up => Key::Up,
+            KeyCode::Down => Key::Down,
+            KeyCode::Home => Key::Home,
+            KeyCode::End => Key::End,
+            KeyCode::PageUp => Key::PageUp,
+            KeyCode::PageDown => Key::PageDown,
+            KeyCode::BackTab => Key::BackTab,
+            KeyCode::Delete => Key::Delete,
+            KeyCode::Insert => Key::Insert,
+            KeyCode::F(n) => Key::F(n),
+            KeyCode::Media(key) => Key::Media(key),
+            KeyCode::Null => Key::Null,
+            KeyCode::Esc => Key::Esc,
+            KeyCode::Char(_) | KeyCode::Enter | KeyCode::Tab | KeyCode::Keypad(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_key_conversion() {
+        let keys = [Key::Char('\n'), Key::Char('x'), Key::Alt('\t'), Key::Ctrl('a'), Key::F(3), Key::Esc,
+                    Key::Backspace, Key::Ctrl('h'), Key::Alt('\x7F'), Key::Ctrl(' ')];
+        for &key in keys.iter() {
+            assert_eq!(Key::from(KeyEvent::from(key)), key);
+        }
+        assert_eq!(KeyEvent::from(Key::Char('\n')), KeyEvent::from(KeyCode::Enter));
+        assert_eq!(KeyEvent::from(Key::Null), KeyEvent::from(Key::Ctrl(' ')));
+        assert_eq!(KeyEvent::from(Key::Ctrl('h')), KeyEvent::new(KeyCode::Char('h'), KeyModifiers::CONTROL));
+        assert_eq!(Key::from(KeyEvent::new(KeyCode::Backspace, KeyModifiers::CONTROL)), Key::Ctrl('h'));
+
+        let ctrl_alt = KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL | KeyModifiers::ALT);
+        assert_eq!(Key::from(ctrl_alt), Key::Ctrl('a'));
+        assert_eq!(Key::from(KeyEvent::new(KeyCode::Up, KeyModifiers::SHIFT)), Key::Up);
+    }
 
-    #[doc(hidden)]
-    __IsNotComplete,
-}
\ No newline at end of file
+    #[test]
+    fn test_modifiers() {
+        let mods = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        assert!(mods.contains(KeyModifiers::CONTROL));
+        assert!(!mods.contains(KeyModifiers::CONTROL | KeyModifiers::ALT));
+        assert_eq!(mods - KeyModifiers::SHIFT, KeyModifiers::CONTROL);
+        assert_eq!(format!("{:?}", mods), "KeyModifiers(SHIFT | CONTROL)");
+        assert_eq!(format!("{:?}", KeyModifiers::NONE), "KeyModifiers(NONE)");
+    }
+}
@@ -0,0 +1,179 @@
+//! A compact binary log of input events.
+//!
+//! The log is cheap enough to keep recording for a whole session, e.g. to reproduce bug reports.
+//! It starts with the `MAGIC` bytes and the format `VERSION`, followed by one record per event:
+//! the milliseconds since the previous record and the length of the event, both as LEB128
+//! integers, then the event encoded as terminal input (see `encode`).
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::event::{Event, Key};
+//! use pres::event::log::{LogReader, LogWriter};
+//!
+//! let mut writer = LogWriter::new(Vec::new()).unwrap();
+//! writer.write_event(&Event::Key(Key::Up.into())).unwrap();
+//! let log = writer.into_inner();
+//!
+//! let mut reader = LogReader::new(log.as_slice()).unwrap();
+//! let (_at, event) = reader.next().unwrap().unwrap();
+//! assert_eq!(event, Event::Key(Key::Up.into()));
+//! ```
+
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+use crate::event::encode::encode;
+use crate::event::{Event, ParseOptions};
+use crate::input::TermRead;
+
+/// The bytes a log starts with.
+pub const MAGIC: &[u8; 7] = b"PRESLOG";
+
+/// The version of the log format, written after `MAGIC`.
+pub const VERSION: u8 = 1;
+
+fn write_varint<W: Write>(out: &mut W, mut n: u64) -> io::Result<()> {
+    let mut buf = Vec::with_capacity(10);
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            buf.push(byte);
+            return out.write_all(&buf);
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Read a LEB128 integer, or `None` at the end of the input.
+fn read_varint<R: Read>(input: &mut R) -> io::Result<Option<u64>> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0u8];
+        if input.read(&mut byte)? == 0 {
+            return if shift == 0 { Ok(None) } else { Err(io::ErrorKind::UnexpectedEof.into()) };
+        }
+        n |= u64::from(byte[0] & 0x7F) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(n));
+        }
+    }
+    Err(io::Error::new(io::ErrorKind::InvalidData, "integer too long"))
+}
+
+/// Writes events to a log.
+pub struct LogWriter<W: Write> {
+    out: W,
+    /// When the last record was written.
+    last: Instant,
+}
+
+impl<W: Write> LogWriter<W> {
+    /// Start a log on `out`, writing its header.
+    pub fn new(mut out: W) -> io::Result<LogWriter<W>> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[VERSION])?;
+        Ok(LogWriter { out, last: Instant::now() })
+    }
+
+    /// Append `event`, timestamped now.
+    ///
+    /// Fails with `InvalidInput` for the few events that have no encoding, see `encode`.
+    pub fn write_event(&mut self, event: &Event) -> io::Result<()> {
+        let bytes = encode(event)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "event cannot be encoded"))?;
+        let now = Instant::now();
+        write_varint(&mut self.out, now.duration_since(self.last).as_millis() as u64)?;
+        write_varint(&mut self.out, bytes.len() as u64)?;
+        self.out.write_all(&bytes)?;
+        self.last = now;
+        Ok(())
+    }
+
+    /// Get the underlying writer back.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Reads the events of a log, with the time since the log started.
+pub struct LogReader<R: Read> {
+    input: R,
+    options: ParseOptions,
+    /// The time of the last record read.
+    at: Duration,
+}
+
+impl<R: Read> LogReader<R> {
+    /// Read the log in `input`, checking its header.
+    pub fn new(mut input: R) -> io::Result<LogReader<R>> {
+        let mut header = [0u8; 8];
+        input.read_exact(&mut header)?;
+        if header[..7] != MAGIC[..] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "not an event log"));
+        }
+        if header[7] != VERSION {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unsupported event log version"));
+        }
+        Ok(LogReader { input, options: ParseOptions::default(), at: Duration::ZERO })
+    }
+
+    /// Parse the recorded events with `options`, for events only recognized with them such as
+    /// `Event::Shell`.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    fn read_record(&mut self) -> io::Result<Option<(Duration, Event)>> {
+        let Some(delay) = read_varint(&mut self.input)? else { return Ok(None) };
+        let len = read_varint(&mut self.input)?.ok_or(io::ErrorKind::UnexpectedEof)?;
+        let mut bytes = Vec::new();
+        (&mut self.input).take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+
+        let event = bytes.as_slice().events().with_options(self.options).next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "empty record"))??;
+        self.at += Duration::from_millis(delay);
+        Ok(Some((self.at, event)))
+    }
+}
+
+impl<R: Read> Iterator for LogReader<R> {
+    type Item = io::Result<(Duration, Event)>;
+
+    fn next(&mut self) -> Option<io::Result<(Duration, Event)>> {
+        self.read_record().transpose()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::{Key, MouseButton, MouseEvent};
+
+    #[test]
+    fn test_log_round_trip() {
+        let events = [
+            Event::Key(Key::Char('é').into()),
+            Event::Mouse(MouseEvent::Press(MouseButton::Left, 300, 2).into()),
+            Event::Paste("x".repeat(200)),
+        ];
+        let mut writer = LogWriter::new(Vec::new()).unwrap();
+        for event in &events {
+            writer.write_event(event).unwrap();
+        }
+        let log = writer.into_inner();
+        assert_eq!(&log[..9], b"PRESLOG\x01\x00");
+
+        let read: Vec<Event> = LogReader::new(log.as_slice()).unwrap().map(|r| r.unwrap().1).collect();
+        assert_eq!(read, events);
+
+        assert!(LogReader::new(&log[..8]).unwrap().next().is_none());
+        assert!(LogReader::new(&log[..14]).unwrap().nth(1).unwrap().is_err());
+        assert!(LogReader::new(&b"PRESLOG\x02"[..]).is_err());
+    }
+}
@@ -1,87 +1,202 @@
 //! Mouse and key events.
 
-use std::io::{Error, ErrorKind};
+use std::io::Error;
 use std::str;
 
-pub use events::{Event, Events, EventsAndRaw};
-pub use keys::Key;
-pub use mouses::{MouseButton, MouseEvent};
+use crate::shell::ShellEvent;
 
+pub use events::{Event, Events, EventsAndRaw, Paste, RawBytes};
+pub use keys::{Key, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeypadKey, MediaKey};
+pub use mouses::{MouseAction, MouseButton, MouseEvent, MouseEventEx};
+pub use parser::Parser;
+
+pub mod adapters;
+pub mod encode;
 pub mod events;
 pub mod keys;
+pub mod log;
 pub mod mouses;
+pub mod parser;
 
 const ESC: u8 = b'\x1B';
+/// The 8-bit form of `ESC [`.
+const C1_CSI: u8 = 0x9B;
+/// The 8-bit form of `ESC O`.
+const C1_SS3: u8 = 0x8F;
+
+/// Optional parts of the input syntax.
+///
+/// Everything here is disabled by default, since recognizing these sequences can change how
+/// ordinary key presses are interpreted.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct ParseOptions {
+    /// Parse OSC 133/633 shell integration sequences into `Event::Shell`.
+    ///
+    /// This is meant for programs reading the output of a shell through a PTY. On a keyboard,
+    /// Alt+] sends the same `ESC ]` introducer and would wait for a terminator that never comes.
+    pub shell_integration: bool,
+    /// Recognize the 8-bit C1 control introducers CSI (`0x9B`) and SS3 (`0x8F`) in place of
+    /// `ESC [` and `ESC O`.
+    ///
+    /// Some terminals and serial lines send these; without the option they are invalid UTF-8 and
+    /// come back as `Event::Unsupported`.
+    pub c1_controls: bool,
+    /// Report a carriage return followed by a line feed as a single Enter.
+    ///
+    /// This is meant for input read from files and pipes, where lines may end with CR, LF or
+    /// CRLF. A terminal sends a single CR for Enter, so a following LF there is a key press of
+    /// its own.
+    pub normalize_newlines: bool,
+    /// Report printable text that arrives in a single read as one `Event::Text`.
+    ///
+    /// Input methods, dead keys and emoji pickers send a grapheme cluster made of several code
+    /// points (such as an emoji joined with ZWJ, or a letter and a combining mark) at once, which
+    /// would otherwise be split into one key event per code point. A single typed character is
+    /// still a key event, so key bindings keep working.
+    pub coalesce_text: bool,
+    /// The most bytes an event may be parsed from.
+    ///
+    /// A longer sequence, such as an OSC payload or a paste that never ends, is cut at this
+    /// length and reported as `Event::Unsupported`. The rest of it is read up to its terminator
+    /// and dropped, so its payload does not come out as key presses; parsing carries on after.
+    /// This bounds the memory used on corrupted or hostile input. Pastes count too, so the limit
+    /// should leave room for them, unless `max_paste_len` is set. There is no limit by default.
+    pub max_sequence_len: Option<usize>,
+    /// The most bytes of pasted text kept.
+    ///
+    /// The rest of a longer paste is read and dropped, and the paste is reported as
+    /// `Event::PasteTruncated`, so an accidental multi-megabyte paste neither fills memory nor
+    /// turns into a flood of key events. Pastes are then exempt from `max_sequence_len`, and
+    /// `Events::last_raw` only holds the part kept. There is no limit by default.
+    pub max_paste_len: Option<usize>,
+    /// Report pastes in pieces of about this many bytes, as `Event::PastePart`, instead of as a
+    /// whole once they end.
+    ///
+    /// A program can then show or process a large paste as it arrives, while only a piece of it
+    /// is held at a time. Pieces end on a character boundary, so they may be a few bytes shorter
+    /// or longer; the end of the paste comes as `Paste::Last`, possibly with no text. Pastes are
+    /// then exempt from `max_sequence_len`, and `max_paste_len` still bounds the text of the
+    /// whole paste. This applies to `Events` and `Parser`: `parse_event_with`, which parses a
+    /// single event, still reports the whole paste. Pastes are reported whole by default.
+    pub paste_chunk_len: Option<usize>,
+    /// After a malformed sequence, skip ahead to the next byte that can start an event: an ESC,
+    /// a printable character or, with `c1_controls`, a C1 introducer.
+    ///
+    /// On a noisy line, the bytes after a broken sequence are often the rest of it, and parsing
+    /// them on their own gives spurious key presses. The skipped bytes are reported with the
+    /// sequence as one `Event::Unsupported`, and counted by `Metrics::bytes_skipped`. Control
+    /// keys pressed right after the garbage, such as Enter, are skipped too. The byte that ends
+    /// the garbage has to be read first, so on a blocking input the event is only reported once
+    /// more input arrives.
+    pub resync: bool,
+    /// Parse SGR mouse reports as pixel coordinates, into `MouseEvent::Pixels`, given the size of
+    /// a cell in pixels as `(width, height)`.
+    ///
+    /// This is for `input::MouseTerminal::pixel_coordinates`: SGR-Pixels reports look the same as
+    /// SGR ones, so only the program knows which mode it asked for. The cell size can be computed
+    /// from `terminal_size` and `terminal_size_pixels`.
+    pub sgr_pixels: Option<(u16, u16)>,
+}
 
 /// Parse an Event from `item` and possibly subsequent bytes through `iter`.
 pub fn parse_event<I>(item: u8, iter: &mut I) -> Result<Event, Error>
     where I: Iterator<Item=Result<u8, Error>>
 {
-    let error = Error::new(ErrorKind::Other, "Could not parse an event");
+    parse_event_with(item, iter, &ParseOptions::default())
+}
+
+/// Parse an Event from `item` and possibly subsequent bytes through `iter`, recognizing the
+/// optional syntax enabled in `options`.
+pub fn parse_event_with<I>(item: u8, iter: &mut I, options: &ParseOptions) -> Result<Event, Error>
+    where I: Iterator<Item=Result<u8, Error>>
+{
+    let error = Error::other("Could not parse an event");
     match item {
-        b'\x1B' => {
+        ESC => {
             // This is an escape character, leading a control sequence.
             Ok(match iter.next() {
                 Some(Ok(b'O')) => {
-                    match iter.next() {
-                        // F1-F4
-                        Some(Ok(val @ b'P'..=b'S')) => Event::Key(Key::F(1 + val - b'P')),
-                        _ => return Err(error),
-                    }
+                    // This is an SS3 sequence.
+                    parse_ss3(iter).ok_or(error)?
                 }
                 Some(Ok(b'[')) => {
                     // This is a CSI sequence.
-                    parse_csi(iter).ok_or(error)?
+                    parse_csi(iter, options).ok_or(error)?
+                }
+                Some(Ok(b'\r')) => Event::Key(KeyEvent::new(KeyCode::Enter, KeyModifiers::ALT)),
+                Some(Ok(b']')) if options.shell_integration => {
+                    // This is an OSC sequence.
+                    parse_osc(iter).ok_or(error)?
                 }
                 Some(Ok(c)) => {
                     let ch = parse_utf8_char(c, iter)?;
-                    Event::Key(Key::Alt(ch))
+                    Event::Key(Key::Alt(ch).into())
                 }
                 Some(Err(_)) | None => return Err(error),
             })
         }
-        b'\n' | b'\r' => Ok(Event::Key(Key::Char('\n'))),
-        b'\t' => Ok(Event::Key(Key::Char('\t'))),
-        b'\x7F' => Ok(Event::Key(Key::Backspace)),
-        c @ b'\x01'..=b'\x1A' => Ok(Event::Key(Key::Ctrl((c as u8 - 0x1 + b'a') as char))),
-        c @ b'\x1C'..=b'\x1F' => Ok(Event::Key(Key::Ctrl((c as u8 - 0x1C + b'4') as char))),
-        b'\0' => Ok(Event::Key(Key::Null)),
+        C1_CSI if options.c1_controls => parse_csi(iter, options).ok_or(error),
+        C1_SS3 if options.c1_controls => parse_ss3(iter).ok_or(error),
+        b'\n' | b'\r' => Ok(Event::Key(KeyCode::Enter.into())),
+        b'\t' => Ok(Event::Key(KeyCode::Tab.into())),
+        b'\x7F' => Ok(Event::Key(KeyCode::Backspace.into())),
+        // Terminals send it for Ctrl+Backspace, as well as for Ctrl+H.
+        b'\x08' => Ok(Event::Key(KeyEvent::new(KeyCode::Backspace, KeyModifiers::CONTROL))),
+        c @ b'\x01'..=b'\x1A' => Ok(Event::Key(Key::Ctrl((c - 0x1 + b'a') as char).into())),
+        c @ b'\x1C'..=b'\x1F' => Ok(Event::Key(Key::Ctrl((c - 0x1C + b'4') as char).into())),
+        // Ctrl+Space, reported the same way as with the kitty keyboard protocol.
+        b'\0' => Ok(Event::Key(Key::Ctrl(' ').into())),
         c => {
             Ok({
                 let ch = parse_utf8_char(c, iter)?;
-                Event::Key(Key::Char(ch))
+                Event::Key(Key::Char(ch).into())
             })
         }
     }
 }
 
+/// Parses an SS3 sequence, just after reading ^[O
+///
+/// Returns None if an unrecognized sequence is found.
+fn parse_ss3<I>(iter: &mut I) -> Option<Event>
+    where I: Iterator<Item=Result<u8, Error>>
+{
+    match iter.next() {
+        // F1-F4, and the cursor keys in application cursor mode.
+        Some(Ok(c @ (b'A'..=b'D' | b'H' | b'F' | b'P'..=b'S'))) => Some(Event::Key(csi_final_key(c)?.into())),
+        // The keypad in application keypad mode.
+        Some(Ok(c)) => Some(Event::Key(KeyCode::Keypad(ss3_keypad_key(c)?).into())),
+        _ => None,
+    }
+}
+
 /// Parses a CSI sequence, just after reading ^[
 ///
 /// Returns None if an unrecognized sequence is found.
-fn parse_csi<I>(iter: &mut I) -> Option<Event>
+fn parse_csi<I>(iter: &mut I, options: &ParseOptions) -> Option<Event>
     where I: Iterator<Item=Result<u8, Error>>
 {
     Some(match iter.next() {
         Some(Ok(b'[')) => match iter.next() {
-            Some(Ok(val @ b'A'..=b'E')) => Event::Key(Key::F(1 + val - b'A')),
+            Some(Ok(val @ b'A'..=b'E')) => Event::Key(KeyCode::F(1 + val - b'A').into()),
             _ => return None,
         },
-        Some(Ok(b'D')) => Event::Key(Key::Left),
-        Some(Ok(b'C')) => Event::Key(Key::Right),
-        Some(Ok(b'A')) => Event::Key(Key::Up),
-        Some(Ok(b'B')) => Event::Key(Key::Down),
-        Some(Ok(b'H')) => Event::Key(Key::Home),
-        Some(Ok(b'F')) => Event::Key(Key::End),
-        Some(Ok(b'Z')) => Event::Key(Key::BackTab),
+        Some(Ok(c @ (b'A'..=b'D' | b'H' | b'F'))) => Event::Key(csi_final_key(c)?.into()),
+        Some(Ok(b'Z')) => Event::Key(KeyCode::BackTab.into()),
         Some(Ok(b'M')) => {
             // X10 emulation mouse encoding: ESC [ CB Cx Cy (6 characters only).
-            let mut next = || iter.next().unwrap().unwrap();
+            let mut next = || iter.next()?.ok();
 
-            let cb = next() as i8 - 32;
+            let b = next()?;
+            let cb = b as i8 - 32;
             // (1, 1) are the coords for upper left.
-            let cx = next().saturating_sub(32) as u16;
-            let cy = next().saturating_sub(32) as u16;
-            Event::Mouse(match cb & 0b11 {
+            let cx = next()?.saturating_sub(32) as u16;
+            let cy = next()?.saturating_sub(32) as u16;
+            let modifiers = MouseEventEx::modifiers_from_code(u16::from(b.saturating_sub(32)));
+            let event = match cb & 0b11 {
+                // Extra buttons, such as back and forward.
+                _ if b >= 128 + 32 => MouseEvent::Press(MouseButton::from_code((b - 32) & 0b1100_0011), cx, cy),
+                3 if cb & 32 != 0 => MouseEvent::Moved(cx, cy),
                 0 => {
                     if cb & 0x40 != 0 {
                         MouseEvent::Press(MouseButton::WheelUp, cx, cy)
@@ -97,140 +212,506 @@ fn parse_csi<I>(iter: &mut I) -> Option<Event>
                     }
                 }
                 2 => MouseEvent::Press(MouseButton::Right, cx, cy),
-                3 => MouseEvent::Release(cx, cy),
+                3 => MouseEvent::Release(None, cx, cy),
                 _ => return None,
-            })
+            };
+            Event::Mouse(MouseEventEx::new(event, modifiers))
         }
         Some(Ok(b'<')) => {
             // xterm mouse encoding:
             // ESC [ < Cb ; Cx ; Cy (;) (M or m)
             let mut buf = Vec::new();
-            let mut c = iter.next().unwrap().unwrap();
-            while match c {
-                b'm' | b'M' => false,
-                _ => true,
-            } {
+            let mut c = iter.next()?.ok()?;
+            while !matches!(c, b'm' | b'M') {
+                // Anything else breaks the report.
+                if !matches!(c, b'0'..=b'9' | b';') {
+                    return None;
+                }
                 buf.push(c);
-                c = iter.next().unwrap().unwrap();
+                c = iter.next()?.ok()?;
             }
-            let str_buf = String::from_utf8(buf).unwrap();
+            let str_buf = String::from_utf8(buf).ok()?;
             let nums = &mut str_buf.split(';');
 
-            let cb = nums.next()
-                .unwrap()
-                .parse::<u16>()
-                .unwrap();
-            let cx = nums.next()
-                .unwrap()
-                .parse::<u16>()
-                .unwrap();
-            let cy = nums.next()
-                .unwrap()
-                .parse::<u16>()
-                .unwrap();
-
-            let event = match cb {
-                0..=2 | 64..=65 => {
-                    let button = match cb {
-                        0 => MouseButton::Left,
-                        1 => MouseButton::Middle,
-                        2 => MouseButton::Right,
-                        64 => MouseButton::WheelUp,
-                        65 => MouseButton::WheelDown,
-                        _ => unreachable!(),
-                    };
-                    match c {
-                        b'M' => MouseEvent::Press(button, cx, cy),
-                        b'm' => MouseEvent::Release(cx, cy),
-                        _ => return None,
-                    }
-                }
-                32 => MouseEvent::Hold(cx, cy),
-                3 => MouseEvent::Release(cx, cy),
-                _ => return None,
+            let cb = nums.next()?.parse::<u16>().ok()?;
+            let cx = nums.next()?.parse::<u16>().ok()?;
+            let cy = nums.next()?.parse::<u16>().ok()?;
+
+            // The button code, without the modifier (4, 8 and 16) and motion (32) bits.
+            let button = u8::try_from(cb & !0b11_1100).ok()?;
+            let action = match c {
+                b'm' if button == 3 => MouseAction::Release(None),
+                b'm' => MouseAction::Release(Some(MouseButton::from_code(button))),
+                _ if cb & 32 != 0 && button == 3 => MouseAction::Moved,
+                _ if cb & 32 != 0 => MouseAction::Hold,
+                _ if button == 3 => MouseAction::Release(None),
+                _ => MouseAction::Press(MouseButton::from_code(button)),
+            };
+            let event = match options.sgr_pixels {
+                Some((width, height)) => MouseEvent::Pixels {
+                    action,
+                    x: cx,
+                    y: cy,
+                    column: cx.saturating_sub(1) / width.max(1) + 1,
+                    row: cy.saturating_sub(1) / height.max(1) + 1,
+                },
+                None => action.at(cx, cy),
             };
 
-            Event::Mouse(event)
+            Event::Mouse(MouseEventEx::new(event, MouseEventEx::modifiers_from_code(cb)))
         }
         Some(Ok(c @ b'0'..=b'9')) => {
             // Numbered escape code.
-            let mut buf = Vec::new();
-            buf.push(c);
-            let mut c = iter.next().unwrap().unwrap();
-            // The final byte of a CSI sequence can be in the range 64-126, so
-            // let's keep reading anything else.
-            while c < 64 || c > 126 {
-                buf.push(c);
-                c = iter.next().unwrap().unwrap();
-            }
+            let mut buf = vec![c];
+            // rxvt also ends Shift+key with `$`.
+            let c = read_csi_body(&mut buf, iter, b"$")?;
+            parse_numbered_csi(&buf, c, iter, options).or_else(|| unknown_csi(&buf, c))?
+        }
+        Some(Ok(c @ 64..=126)) => unknown_csi(&[], c)?,
+        // A private marker or intermediate byte, in sequences this crate does not parse. They are
+        // read to their end, so their parameters don't come out as key presses.
+        Some(Ok(c @ (b'=' | b'>' | b'?' | 0x20..=0x2F))) => {
+            let mut buf = vec![c];
+            let c = read_csi_body(&mut buf, iter, b"")?;
+            unknown_csi(&buf, c)?
+        }
+        _ => return None,
+    })
+}
 
-            match c {
-                // rxvt mouse encoding:
-                // ESC [ Cb ; Cx ; Cy ; M
-                b'M' => {
-                    let str_buf = String::from_utf8(buf).unwrap();
-
-                    let nums: Vec<u16> = str_buf.split(';').map(|n| n.parse().unwrap()).collect();
-
-                    let cb = nums[0];
-                    let cx = nums[1];
-                    let cy = nums[2];
-
-                    let event = match cb {
-                        32 => MouseEvent::Press(MouseButton::Left, cx, cy),
-                        33 => MouseEvent::Press(MouseButton::Middle, cx, cy),
-                        34 => MouseEvent::Press(MouseButton::Right, cx, cy),
-                        35 => MouseEvent::Release(cx, cy),
-                        64 => MouseEvent::Hold(cx, cy),
-                        96 | 97 => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
-                        _ => return None,
-                    };
-
-                    Event::Mouse(event)
-                }
-                // Special key code.
-                b'~' => {
-                    let str_buf = String::from_utf8(buf).unwrap();
+/// Read the parameter and intermediate bytes of a CSI sequence into `buf`, and return its final
+/// byte, which is in the range 64-126 or one of `finals`.
+///
+/// Returns `None` at the first byte that can't be part of the sequence, such as an ESC starting
+/// another one.
+fn read_csi_body<I>(buf: &mut Vec<u8>, iter: &mut I, finals: &[u8]) -> Option<u8>
+    where I: Iterator<Item=Result<u8, Error>>
+{
+    loop {
+        match iter.next()?.ok()? {
+            c if (64..=126).contains(&c) || finals.contains(&c) => return Some(c),
+            c @ 0x20..=0x3F => buf.push(c),
+            _ => return None,
+        }
+    }
+}
 
-                    // This CSI sequence can be a list of semicolon-separated
-                    // numbers.
-                    let nums: Vec<u8> = str_buf.split(';').map(|n| n.parse().unwrap()).collect();
+/// A well-formed CSI sequence that is not understood, from the bytes between `CSI` and the final
+/// byte `c`.
+///
+/// Only plain numeric parameters are supported; sequences with sub-parameters (`:`) or a private
+/// marker (such as `?`) are left unparsed.
+fn unknown_csi(buf: &[u8], c: u8) -> Option<Event> {
+    if !(64..=126).contains(&c) {
+        return None;
+    }
+    let split = buf.iter().position(|b| !(0x30..=0x3F).contains(b)).unwrap_or(buf.len());
+    let (params, intermediates) = buf.split_at(split);
+    if !intermediates.iter().all(|b| (0x20..=0x2F).contains(b)) {
+        return None;
+    }
+    let params = match str::from_utf8(params).ok()? {
+        "" => Vec::new(),
+        // An empty parameter has its default value, given as 0.
+        params => params
+            .split(';')
+            .map(|n| if n.is_empty() { Some(0) } else { n.parse().ok() })
+            .collect::<Option<_>>()?,
+    };
+    Some(Event::UnknownCsi { params, intermediates: intermediates.to_vec(), final_byte: c })
+}
 
-                    if nums.is_empty() {
-                        return None;
-                    }
+/// Parse a CSI sequence made of a number, the other bytes in `buf`, and the final byte `c`.
+fn parse_numbered_csi<I>(buf: &[u8], c: u8, iter: &mut I, options: &ParseOptions) -> Option<Event>
+    where I: Iterator<Item=Result<u8, Error>>
+{
+    Some(match c {
+        // rxvt mouse encoding:
+        // ESC [ Cb ; Cx ; Cy ; M
+        b'M' => {
+            let str_buf = str::from_utf8(buf).ok()?;
 
-                    // TODO: handle multiple values for key modififiers (ex: values
-                    // [3, 2] means Shift+Delete)
-                    if nums.len() > 1 {
-                        return None;
-                    }
+            let nums: Vec<u16> = str_buf.split(';').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+            let [cb, cx, cy] = nums[..] else { return None };
+            let modifiers = MouseEventEx::modifiers_from_code(cb.saturating_sub(32));
 
-                    match nums[0] {
-                        1 | 7 => Event::Key(Key::Home),
-                        2 => Event::Key(Key::Insert),
-                        3 => Event::Key(Key::Delete),
-                        4 | 8 => Event::Key(Key::End),
-                        5 => Event::Key(Key::PageUp),
-                        6 => Event::Key(Key::PageDown),
-                        v @ 11..=15 => Event::Key(Key::F(v - 10)),
-                        v @ 17..=21 => Event::Key(Key::F(v - 11)),
-                        v @ 23..=24 => Event::Key(Key::F(v - 12)),
-                        _ => return None,
-                    }
+            let event = match cb & !0b1_1100 {
+                32 => MouseEvent::Press(MouseButton::Left, cx, cy),
+                33 => MouseEvent::Press(MouseButton::Middle, cx, cy),
+                34 => MouseEvent::Press(MouseButton::Right, cx, cy),
+                35 => MouseEvent::Release(None, cx, cy),
+                64 => MouseEvent::Hold(cx, cy),
+                67 => MouseEvent::Moved(cx, cy),
+                96 | 97 => MouseEvent::Press(MouseButton::WheelUp, cx, cy),
+                cb => MouseEvent::Press(MouseButton::from_code(u8::try_from(cb.checked_sub(32)?).ok()?), cx, cy),
+            };
+
+            Event::Mouse(MouseEventEx::new(event, modifiers))
+        }
+        // Special key code.
+        b'~' => {
+            let str_buf = str::from_utf8(buf).ok()?;
+
+            // This CSI sequence can be a list of semicolon-separated
+            // numbers: the key, then optionally its modifiers (ex: values
+            // [3, 2] means Shift+Delete).
+            let fields: Vec<&str> = str_buf.split(';').collect();
+            match fields[..] {
+                // xterm modifyOtherKeys: ESC [ 27 ; Cm ; code ~
+                ["27", mods, code] => {
+                    Event::Key(unicode_key(code.parse().ok()?, modifiers_from_param(mods.parse().ok()?))?)
+                }
+                // Bracketed paste: ESC [ 200 ~ text ESC [ 201 ~
+                ["200"] => parse_paste(iter, options.max_paste_len)?,
+                [key] => Event::Key(tilde_key(key.parse().ok()?)?.into()),
+                [key, mods] => {
+                    let (modifiers, kind) = modifier_field(mods)?;
+                    Event::Key(KeyEvent::new(tilde_key(key.parse().ok()?)?, modifiers).with_kind(kind))
                 }
                 _ => return None,
             }
         }
+        // rxvt modified special key code:
+        // ESC [ key (^ for Ctrl, $ for Shift, @ for both)
+        b'^' | b'$' | b'@' => {
+            let code = tilde_key(str::from_utf8(buf).ok()?.parse().ok()?)?;
+            let modifiers = match c {
+                b'^' => KeyModifiers::CONTROL,
+                b'$' => KeyModifiers::SHIFT,
+                _ => KeyModifiers::CONTROL | KeyModifiers::SHIFT,
+            };
+            Event::Key(KeyEvent::new(code, modifiers))
+        }
+        // Modified cursor or function key:
+        // ESC [ 1 ; Cm (A-D, H, F or P-S)
+        b'A'..=b'D' | b'H' | b'F' | b'P'..=b'S' => {
+            let str_buf = str::from_utf8(buf).ok()?;
+            let (key, mods) = str_buf.split_once(';')?;
+            if key != "1" {
+                return None;
+            }
+            let code = csi_final_key(c)?;
+            let (modifiers, kind) = modifier_field(mods)?;
+            Event::Key(KeyEvent::new(code, modifiers).with_kind(kind))
+        }
+        // xterm in-band resize report:
+        // ESC [ 48 ; rows ; cols [; height ; width] t
+        b't' => {
+            let str_buf = str::from_utf8(buf).ok()?;
+            let nums: Vec<u16> = str_buf.split(';').map(|n| n.parse().ok()).collect::<Option<_>>()?;
+            match nums[..] {
+                [48, rows, cols] | [48, rows, cols, _, _] => Event::Resize(cols, rows),
+                _ => return None,
+            }
+        }
+        // Kitty keyboard protocol:
+        // ESC [ code[:shifted[:base]] [; Cm[:type] [; text]] u
+        b'u' => {
+            let str_buf = str::from_utf8(buf).ok()?;
+            let mut fields = str_buf.split(';');
+            let mut codes = fields.next()?.split(':');
+            let code = codes.next()?.parse().ok()?;
+            let base = codes.nth(1).filter(|base| !base.is_empty());
+            let (modifiers, kind) = modifier_field(fields.next().unwrap_or(""))?;
+            let mut key = unicode_key(code, modifiers)?.with_kind(kind);
+            if let Some(base) = base {
+                let base = unicode_key(base.parse().ok()?, KeyModifiers::NONE)?.code;
+                if base != key.code {
+                    key = key.with_base_layout(base);
+                }
+            }
+            Event::Key(key)
+        }
+        // Windows Terminal win32-input-mode, a Windows key record:
+        // ESC [ Vk ; Sc ; Uc ; Kd ; Cs ; Rc _
+        b'_' => {
+            let str_buf = str::from_utf8(buf).ok()?;
+            let nums: Vec<u32> = str_buf
+                .split(';')
+                .map(|n| if n.is_empty() { Some(0) } else { n.parse().ok() })
+                .collect::<Option<_>>()?;
+            let [vk, _scan_code, uc, key_down, state, ..] = nums[..] else { return None };
+            Event::Key(win32_key(vk, uc, state)?.with_kind(if key_down == 0 {
+                KeyEventKind::Release
+            } else {
+                KeyEventKind::Press
+            }))
+        }
         _ => return None,
     })
 }
 
+/// The key a win32-input-mode record stands for, from its virtual key code `vk`, the character
+/// `uc` it types, if any, and the control key state.
+///
+/// A repeat count above one is reported as a single press. Lone modifier keys have no `KeyCode`.
+fn win32_key(vk: u32, uc: u32, state: u32) -> Option<KeyEvent> {
+    let mut modifiers = KeyModifiers::NONE;
+    if state & 0x10 != 0 {
+        modifiers |= KeyModifiers::SHIFT;
+    }
+    // Left or right Ctrl, left or right Alt.
+    if state & 0x0C != 0 {
+        modifiers |= KeyModifiers::CONTROL;
+    }
+    if state & 0x03 != 0 {
+        modifiers |= KeyModifiers::ALT;
+    }
+    // NUMLOCK_ON and CAPSLOCK_ON, reported like the kitty keyboard protocol does.
+    if state & 0x20 != 0 {
+        modifiers |= KeyModifiers::NUM_LOCK;
+    }
+    if state & 0x80 != 0 {
+        modifiers |= KeyModifiers::CAPS_LOCK;
+    }
+
+    match uc {
+        // Control characters typed with Ctrl, e.g. 0x01 for Ctrl+A, are told by their key.
+        0..=31 if !matches!(uc, 8 | 9 | 13 | 27) => {}
+        8 | 9 | 13 | 27 => return unicode_key(uc, modifiers),
+        _ => {
+            // The character typed already tells Shift, and AltGr comes as Ctrl+Alt.
+            let mut modifiers = modifiers - KeyModifiers::SHIFT;
+            if modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::ALT) {
+                modifiers = modifiers - KeyModifiers::CONTROL - KeyModifiers::ALT;
+            }
+            return unicode_key(uc, modifiers);
+        }
+    }
+
+    let code = match vk {
+        0x08 => KeyCode::Backspace,
+        0x09 if modifiers.contains(KeyModifiers::SHIFT) => {
+            return Some(KeyEvent::new(KeyCode::BackTab, modifiers - KeyModifiers::SHIFT));
+        }
+        0x09 => KeyCode::Tab,
+        0x0D => KeyCode::Enter,
+        0x1B => KeyCode::Esc,
+        0x20 => KeyCode::Char(' '),
+        0x21 => KeyCode::PageUp,
+        0x22 => KeyCode::PageDown,
+        0x23 => KeyCode::End,
+        0x24 => KeyCode::Home,
+        0x25 => KeyCode::Left,
+        0x26 => KeyCode::Up,
+        0x27 => KeyCode::Right,
+        0x28 => KeyCode::Down,
+        0x2D => KeyCode::Insert,
+        0x2E => KeyCode::Delete,
+        0x30..=0x39 => KeyCode::Char(char::from_u32(vk)?),
+        0x41..=0x5A => KeyCode::Char(char::from_u32(vk)?.to_ascii_lowercase()),
+        0x70..=0x87 => KeyCode::F((vk - 0x70 + 1) as u8),
+        _ => return None,
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// The key a `CSI n ~` sequence stands for.
+///
+/// xterm sends F13 to F24 as Shift+F1 to Shift+F12 instead, which can't be told apart from those.
+fn tilde_key(n: u16) -> Option<KeyCode> {
+    Some(match n {
+        1 | 7 => KeyCode::Home,
+        2 => KeyCode::Insert,
+        3 => KeyCode::Delete,
+        4 | 8 => KeyCode::End,
+        5 => KeyCode::PageUp,
+        6 => KeyCode::PageDown,
+        11..=15 => KeyCode::F(n as u8 - 10),
+        17..=21 => KeyCode::F(n as u8 - 11),
+        23..=26 => KeyCode::F(n as u8 - 12),
+        28..=29 => KeyCode::F(n as u8 - 13),
+        31..=34 => KeyCode::F(n as u8 - 14),
+        _ => return None,
+    })
+}
+
+/// The cursor or function key a CSI or SS3 sequence with final byte `c` stands for.
+fn csi_final_key(c: u8) -> Option<KeyCode> {
+    Some(match c {
+        b'A' => KeyCode::Up,
+        b'B' => KeyCode::Down,
+        b'C' => KeyCode::Right,
+        b'D' => KeyCode::Left,
+        b'H' => KeyCode::Home,
+        b'F' => KeyCode::End,
+        b'P'..=b'S' => KeyCode::F(1 + c - b'P'),
+        _ => return None,
+    })
+}
+
+/// The keypad key an SS3 sequence with final byte `c` stands for, in application keypad mode.
+fn ss3_keypad_key(c: u8) -> Option<KeypadKey> {
+    Some(match c {
+        b'M' => KeypadKey::Enter,
+        b'X' => KeypadKey::Equal,
+        b'j' => KeypadKey::Multiply,
+        b'k' => KeypadKey::Add,
+        b'l' => KeypadKey::Separator,
+        b'm' => KeypadKey::Subtract,
+        b'n' => KeypadKey::Decimal,
+        b'o' => KeypadKey::Divide,
+        b'p'..=b'y' => KeypadKey::Digit(c - b'p'),
+        _ => return None,
+    })
+}
+
+/// The key a kitty keyboard protocol or xterm modifyOtherKeys key code stands for.
+///
+/// Shift+Tab is reported as `BackTab`, as in the legacy encoding.
+fn unicode_key(code: u32, modifiers: KeyModifiers) -> Option<KeyEvent> {
+    let code = match code {
+        9 if modifiers.contains(KeyModifiers::SHIFT) => {
+            return Some(KeyEvent::new(KeyCode::BackTab, modifiers - KeyModifiers::SHIFT));
+        }
+        9 => KeyCode::Tab,
+        13 => KeyCode::Enter,
+        27 => KeyCode::Esc,
+        8 | 127 => KeyCode::Backspace,
+        // F13 to F35 live in the private use area.
+        57376..=57398 => KeyCode::F((code - 57376 + 13) as u8),
+        57399..=57426 => KeyCode::Keypad(KeypadKey::from_code(code)?),
+        57428..=57440 => KeyCode::Media(MediaKey::from_code(code)?),
+        // KP_BEGIN and lone modifier keys, which have no `KeyCode`.
+        57344..=63743 => return None,
+        _ => KeyCode::Char(char::from_u32(code)?),
+    };
+    Some(KeyEvent::new(code, modifiers))
+}
+
+/// The modifiers and kind encoded by a `Cm[:type]` parameter, where the kitty keyboard protocol
+/// adds the type. An empty parameter means no modifiers.
+fn modifier_field(field: &str) -> Option<(KeyModifiers, KeyEventKind)> {
+    let (mods, kind) = field.split_once(':').unwrap_or((field, ""));
+    let mods = if mods.is_empty() { 1 } else { mods.parse().ok()? };
+    let kind = match kind {
+        "" | "1" => KeyEventKind::Press,
+        "2" => KeyEventKind::Repeat,
+        "3" => KeyEventKind::Release,
+        _ => return None,
+    };
+    Some((modifiers_from_param(mods), kind))
+}
+
+/// The modifiers encoded by an xterm modifier parameter, which is one more than their bits.
+fn modifiers_from_param(param: u16) -> KeyModifiers {
+    KeyModifiers::from_bits(param.saturating_sub(1) as u8)
+}
+
+/// The end of a bracketed paste.
+const PASTE_END: &[u8] = b"\x1B[201~";
+
+/// Parses a bracketed paste, just after reading ^[[200~
+///
+/// Returns None if the input ends before the paste does.
+///
+/// Text past `max_len` bytes is dropped, see `ParseOptions::max_paste_len`.
+fn parse_paste<I>(iter: &mut I, max_len: Option<usize>) -> Option<Event>
+    where I: Iterator<Item=Result<u8, Error>>
+{
+    let mut paste = PasteBuf::new(max_len, None);
+    while !paste.push(iter.next()?.ok()?) {}
+    Some(paste.finish())
+}
+
+/// The text of a bracketed paste, read a byte at a time, holding no more than `max_len` bytes of
+/// it and the end of the paste.
+#[derive(Debug, Clone)]
+pub(crate) struct PasteBuf {
+    buf: Vec<u8>,
+    /// The most bytes of text still to keep, see `ParseOptions::max_paste_len`.
+    room: usize,
+    /// The number of bytes dropped past `max_len`.
+    dropped: usize,
+    /// The size of the pieces to report, see `ParseOptions::paste_chunk_len`.
+    chunk_len: Option<usize>,
+}
+
+impl PasteBuf {
+    pub(crate) fn new(max_len: Option<usize>, chunk_len: Option<usize>) -> PasteBuf {
+        PasteBuf { buf: Vec::new(), room: max_len.unwrap_or(usize::MAX), dropped: 0, chunk_len }
+    }
+
+    /// Add the next byte of the paste, returning whether it ended it.
+    pub(crate) fn push(&mut self, byte: u8) -> bool {
+        self.buf.push(byte);
+        // Past the limit, only keep enough to find the end of the paste.
+        if self.buf.len() > self.room.saturating_add(PASTE_END.len()) {
+            self.buf.remove(self.room);
+            self.dropped += 1;
+        }
+        self.buf.ends_with(PASTE_END)
+    }
+
+    /// The next piece of the paste, once there is enough text for one and the paste is reported
+    /// in pieces.
+    pub(crate) fn chunk(&mut self) -> Option<Event> {
+        let len = self.chunk_len?.max(1);
+        // The text so far, without what may be the start of the end of the paste.
+        let text_len = self.buf.len().saturating_sub(PASTE_END.len() - 1).min(self.room);
+        if text_len < len {
+            return None;
+        }
+        // End before the character going past `len`, or after it if it is the only one.
+        let starts_char = |i: usize| self.buf[i] & 0xC0 != 0x80;
+        let end = (1..=len).rev().find(|&i| starts_char(i))
+            .or_else(|| (len + 1..=text_len).find(|&i| starts_char(i)))?;
+        let text = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+        self.buf.drain(..end);
+        self.room -= end;
+        Some(Event::PastePart(Paste::Chunk(text)))
+    }
+
+    /// The paste, or its last piece, once `push` ended it.
+    pub(crate) fn finish(self) -> Event {
+        let PasteBuf { mut buf, mut dropped, chunk_len, .. } = self;
+        buf.truncate(buf.len() - PASTE_END.len());
+        // Don't keep half a character.
+        if dropped > 0 {
+            if let Err(e) = str::from_utf8(&buf) {
+                if e.error_len().is_none() {
+                    dropped += buf.len() - e.valid_up_to();
+                    buf.truncate(e.valid_up_to());
+                }
+            }
+        }
+        let text = String::from_utf8_lossy(&buf).into_owned();
+        match chunk_len {
+            Some(_) => Event::PastePart(Paste::Last { text, dropped }),
+            None if dropped == 0 => Event::Paste(text),
+            None => Event::PasteTruncated { text, dropped },
+        }
+    }
+}
+
+/// Parses an OSC sequence, just after reading ^[]
+///
+/// The sequence is read up to its BEL or ST terminator. Returns None if it is not shell
+/// integration information.
+fn parse_osc<I>(iter: &mut I) -> Option<Event>
+    where I: Iterator<Item=Result<u8, Error>>
+{
+    let mut buf = Vec::new();
+    loop {
+        match iter.next()? {
+            Ok(b'\x07') => break,
+            Ok(ESC) => match iter.next()? {
+                Ok(b'\\') => break,
+                _ => return None,
+            },
+            Ok(c) => buf.push(c),
+            Err(_) => return None,
+        }
+    }
+    let payload = str::from_utf8(&buf).ok()?;
+    ShellEvent::parse(payload).map(Event::Shell)
+}
+
 /// Parse `c` as either a single byte ASCII char or a variable size UTF-8 char.
 fn parse_utf8_char<I>(c: u8, iter: &mut I) -> Result<char, Error>
     where I: Iterator<Item=Result<u8, Error>>
 {
-    let error = Err(Error::new(ErrorKind::Other, "Input character is not valid UTF-8"));
+    let error = Err(Error::other("Input character is not valid UTF-8"));
     if c.is_ascii() {
         Ok(c as char)
     } else {
@@ -239,7 +720,8 @@ fn parse_utf8_char<I>(c: u8, iter: &mut I) -> Result<char, Error>
 
         loop {
             match iter.next() {
-                Some(Ok(next)) => {
+                // A continuation byte.
+                Some(Ok(next @ 0x80..=0xBF)) => {
                     bytes.push(next);
                     if let Ok(st) = str::from_utf8(bytes) {
                         return Ok(st.chars().next().unwrap());
@@ -258,7 +740,7 @@ fn parse_utf8_char<I>(c: u8, iter: &mut I) -> Result<char, Error>
 #[test]
 fn test_parse_utf8() {
     let st = "abcéŷ¤£€ù%323";
-    let ref mut bytes = st.bytes().map(|x| Ok(x));
+    let bytes = &mut st.bytes().map(Ok);
     let chars = st.chars();
     for c in chars {
         let b = bytes.next().unwrap().unwrap();
@@ -1,3 +1,5 @@
+use crate::event::KeyModifiers;
+
 /// A mouse related event.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum MouseEvent {
@@ -7,12 +9,76 @@ pub enum MouseEvent {
     Press(MouseButton, u16, u16),
     /// A mouse button was released.
     ///
-    /// The coordinates are one-based.
-    Release(u16, u16),
+    /// The button is only known with SGR reports; X10 and rxvt reports, which terminals without
+    /// SGR support send, leave it out. The coordinates are one-based.
+    Release(Option<MouseButton>, u16, u16),
     /// A mouse button is held over the given coordinates.
     ///
     /// The coordinates are one-based.
     Hold(u16, u16),
+    /// The mouse moved over the given coordinates with no button held.
+    ///
+    /// Only reported with any-motion tracking, see `input::MouseTerminal::track_motion`. The
+    /// coordinates are one-based.
+    Moved(u16, u16),
+    /// A mouse button was pressed a second time in a row, at about the same place.
+    ///
+    /// Terminals do not report this; it is synthesized by `adapters::ClickCounter`, right after
+    /// the press. The coordinates are one-based.
+    DoubleClick(MouseButton, u16, u16),
+    /// A mouse button was pressed a third time in a row, see `MouseEvent::DoubleClick`.
+    TripleClick(MouseButton, u16, u16),
+    /// The mouse moved while a button is held, since it was pressed at `from`.
+    ///
+    /// Terminals report this as `MouseEvent::Hold`; it is synthesized by `adapters::DragTracker`.
+    /// The coordinates are one-based.
+    Drag {
+        /// The button held.
+        button: MouseButton,
+        /// Where the button was pressed.
+        from: (u16, u16),
+        /// Where the mouse is now.
+        to: (u16, u16),
+    },
+    /// A report with pixel coordinates, from SGR-Pixels mode (see
+    /// `input::MouseTerminal::pixel_coordinates` and `ParseOptions::sgr_pixels`).
+    Pixels {
+        /// What the mouse did.
+        action: MouseAction,
+        /// The pixel column, one-based.
+        x: u16,
+        /// The pixel row, one-based.
+        y: u16,
+        /// The cell column the pixel is in, one-based.
+        column: u16,
+        /// The cell row the pixel is in, one-based.
+        row: u16,
+    },
+}
+
+/// What the mouse did, without the coordinates.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MouseAction {
+    /// A mouse button was pressed.
+    Press(MouseButton),
+    /// A mouse button was released, see `MouseEvent::Release`.
+    Release(Option<MouseButton>),
+    /// A mouse button is held.
+    Hold,
+    /// The mouse moved with no button held.
+    Moved,
+}
+
+impl MouseAction {
+    /// This action at the cell `(x, y)`.
+    pub fn at(self, x: u16, y: u16) -> MouseEvent {
+        match self {
+            MouseAction::Press(button) => MouseEvent::Press(button, x, y),
+            MouseAction::Release(button) => MouseEvent::Release(button, x, y),
+            MouseAction::Hold => MouseEvent::Hold(x, y),
+            MouseAction::Moved => MouseEvent::Moved(x, y),
+        }
+    }
 }
 
 /// A mouse button.
@@ -32,4 +98,94 @@ pub enum MouseButton {
     ///
     /// This event is typically only used with Mouse::Press.
     WheelDown,
+    /// The back button (button 8), found on the side of some mice.
+    Back,
+    /// The forward button (button 9), found on the side of some mice.
+    Forward,
+    /// Another button, with the button code of its report (the low two bits plus 64 for the wheel
+    /// and 128 for extra buttons).
+    Unknown(u8),
+}
+
+impl MouseButton {
+    /// The button a mouse report button code, without modifier and motion bits, stands for.
+    pub(crate) fn from_code(code: u8) -> MouseButton {
+        match code {
+            0 => MouseButton::Left,
+            1 => MouseButton::Middle,
+            2 => MouseButton::Right,
+            64 => MouseButton::WheelUp,
+            65 => MouseButton::WheelDown,
+            128 => MouseButton::Back,
+            129 => MouseButton::Forward,
+            code => MouseButton::Unknown(code),
+        }
+    }
+
+    /// The button code of reports of this button.
+    pub(crate) fn code(self) -> u8 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Middle => 1,
+            MouseButton::Right => 2,
+            MouseButton::WheelUp => 64,
+            MouseButton::WheelDown => 65,
+            MouseButton::Back => 128,
+            MouseButton::Forward => 129,
+            MouseButton::Unknown(code) => code,
+        }
+    }
+}
+
+/// A mouse event, with the modifier keys held.
+///
+/// Mouse reports carry Shift, Alt and Ctrl only; terminals often keep some combinations for
+/// themselves, such as Shift+Click to select text while mouse reporting is on.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{KeyModifiers, MouseButton, MouseEvent, MouseEventEx};
+///
+/// let click = MouseEvent::Press(MouseButton::Left, 3, 4);
+/// let event = MouseEventEx::new(click, KeyModifiers::CONTROL);
+/// assert_eq!(MouseEvent::from(event), click);
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct MouseEventEx {
+    /// The mouse event itself.
+    pub event: MouseEvent,
+    /// The modifiers held.
+    pub modifiers: KeyModifiers,
+}
+
+impl MouseEventEx {
+    /// `event` with `modifiers` held.
+    pub const fn new(event: MouseEvent, modifiers: KeyModifiers) -> MouseEventEx {
+        MouseEventEx { event, modifiers }
+    }
+
+    /// The modifiers given by the button code `cb` of a mouse report.
+    pub(crate) fn modifiers_from_code(cb: u16) -> KeyModifiers {
+        // Shift, Alt and Ctrl are the bits 4, 8 and 16, in the same order as in `KeyModifiers`.
+        KeyModifiers::from_bits((cb >> 2) as u8 & 0b111)
+    }
+
+    /// The bits of the modifiers in the button code of a mouse report.
+    pub(crate) fn modifiers_code(self) -> u8 {
+        (self.modifiers.bits() & 0b111) << 2
+    }
+}
+
+impl From<MouseEvent> for MouseEventEx {
+    fn from(event: MouseEvent) -> MouseEventEx {
+        MouseEventEx::new(event, KeyModifiers::NONE)
+    }
+}
+
+impl From<MouseEventEx> for MouseEvent {
+    /// The mouse event, without the modifiers.
+    fn from(event: MouseEventEx) -> MouseEvent {
+        event.event
+    }
 }
@@ -0,0 +1,363 @@
+//! Parsing input that is pushed in, rather than read.
+
+use std::iter;
+use std::mem;
+
+use crate::event::events::{parse_event, printable_prefix, Skip, Skipped, PASTE_START};
+use crate::event::{self, Event, KeyCode, ParseOptions, PasteBuf, ESC};
+
+/// A parser fed with input bytes as they arrive, e.g. from a PTY.
+///
+/// Unlike `TermRead::events`, this does not need a `Read`: each `advance` parses what it can and
+/// keeps an incomplete sequence at the end of the bytes for the next call.
+///
+/// `ParseOptions::max_sequence_len` and `max_paste_len` bound what is kept: a sequence going past
+/// the limit is reported as soon as it does, and the rest of it is dropped as it comes in. Only a
+/// truncated paste waits for its end, to tell how much was dropped, keeping no more than the limit.
+/// With `paste_chunk_len`, pastes are reported in pieces as they come in.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{Event, Key, Parser};
+///
+/// let mut parser = Parser::new();
+/// assert_eq!(parser.advance(b"a\x1B[").collect::<Vec<_>>(), [Event::Key(Key::Char('a').into())]);
+/// assert_eq!(parser.advance(b"A").collect::<Vec<_>>(), [Event::Key(Key::Up.into())]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Parser {
+    options: ParseOptions,
+    /// Input not parsed yet, the start of an incomplete sequence.
+    buf: Vec<u8>,
+    /// What the incomplete sequence in `buf` is in the body of, if only a terminator can complete
+    /// it, so that `advance` does not parse it again until one may have come.
+    waiting: Option<Body>,
+    /// A sequence held to a limit of the options, read as it comes in rather than kept in `buf`.
+    cut: Option<Cut>,
+    /// Whether the last event was a carriage return, see `ParseOptions::normalize_newlines`.
+    after_cr: bool,
+}
+
+impl Parser {
+    /// A parser for the default input syntax.
+    pub fn new() -> Parser {
+        Parser::default()
+    }
+
+    /// Recognize the optional input syntax enabled in `options`.
+    pub fn with_options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Parse `bytes` following the input given so far, returning the events completed by them.
+    ///
+    /// As with `TermRead::events`, an ESC given on its own is the Esc key; an ESC at the end of
+    /// longer input waits for the rest of its sequence. `ParseOptions::coalesce_text` applies to
+    /// the text given in one call.
+    pub fn advance(&mut self, bytes: &[u8]) -> impl Iterator<Item=Event> {
+        if self.buf.is_empty() && self.cut.is_none() && bytes == b"\x1B" {
+            return vec![Event::Key(KeyCode::Esc.into())].into_iter();
+        }
+        let mut events = Vec::new();
+        self.feed(bytes, &mut events);
+        events.into_iter()
+    }
+
+    /// Parse the incomplete sequence kept from the last `advance`, as if the input ended there.
+    ///
+    /// This is for when no more input came for a while, e.g. to report an ESC typed last as the
+    /// Esc key. The rest of a sequence over a limit is still dropped up to its terminator.
+    pub fn flush(&mut self) -> impl Iterator<Item=Event> {
+        self.waiting = None;
+        self.parse(true).into_iter()
+    }
+
+    /// Parse `bytes` following the input given so far, adding the events completed to `events`.
+    fn feed(&mut self, mut bytes: &[u8], events: &mut Vec<Event>) {
+        if let Some(cut) = &mut self.cut {
+            let Some(end) = bytes.iter().position(|&byte| match cut {
+                Cut::Paste(paste) => {
+                    let ended = paste.push(byte);
+                    if !ended {
+                        events.extend(paste.chunk());
+                    }
+                    ended
+                }
+                Cut::Sequence(skip) => match skip.push(byte) {
+                    Skipped::More => false,
+                    Skipped::Ended => true,
+                    Skipped::Broken(unread) => {
+                        self.buf.extend_from_slice(&unread);
+                        true
+                    }
+                },
+            }) else { return };
+            if let Some(Cut::Paste(paste)) = self.cut.take() {
+                events.push(paste.finish());
+                self.after_cr = false;
+            }
+            bytes = &bytes[end + 1..];
+        }
+
+        let old_len = self.buf.len();
+        self.buf.extend_from_slice(bytes);
+        let may_end = match self.waiting {
+            // Also look at the last bytes given before, for a terminator split across calls.
+            Some(body) => {
+                let from = old_len.saturating_sub(event::PASTE_END.len() - 1);
+                self.buf[from..].iter().any(|&byte| body.may_end(byte))
+            }
+            None => true,
+        };
+        if may_end {
+            events.extend(self.parse(false));
+        }
+        self.waiting = Body::of(&self.buf, &self.options);
+
+        if let Some(len) = self.cut_over_limit(events) {
+            let rest = self.buf.split_off(len);
+            self.buf.clear();
+            self.waiting = None;
+            self.feed(&rest, events);
+        }
+    }
+
+    /// Start dropping the incomplete sequence left in `buf` if it goes past a limit of the
+    /// options, adding what is reported of it right away to `events`. Returns how many bytes of
+    /// it were dealt with.
+    fn cut_over_limit(&mut self, events: &mut Vec<Event>) -> Option<usize> {
+        let ParseOptions { max_paste_len, paste_chunk_len, .. } = self.options;
+        if self.buf.starts_with(PASTE_START) && (max_paste_len.is_some() || paste_chunk_len.is_some()) {
+            // Exempt from `max_sequence_len`, and bounded by the paste buffer instead.
+            self.cut = Some(Cut::Paste(PasteBuf::new(max_paste_len, paste_chunk_len)));
+            return Some(PASTE_START.len());
+        }
+        let max_len = self.options.max_sequence_len?;
+        if self.buf.len() < max_len {
+            return None;
+        }
+        // The next byte would be over the limit, whatever it is.
+        let skip = Skip::of(&self.buf[..max_len], &self.options)?;
+        events.push(Event::Unsupported(self.buf[..max_len].to_vec()));
+        self.after_cr = false;
+        self.cut = Some(Cut::Sequence(skip));
+        Some(max_len)
+    }
+
+    /// Parse the buffered input, stopping at an incomplete sequence unless `complete`.
+    fn parse(&mut self, complete: bool) -> Vec<Event> {
+        let mut events = Vec::new();
+        // The input is only dropped once done, rather than after every event.
+        let mut start = 0;
+        while let Some((event, len)) = self.parse_one(&self.buf[start..], complete) {
+            let first = self.buf[start];
+            start += len;
+
+            let after_cr = mem::replace(&mut self.after_cr, false);
+            if self.options.normalize_newlines && len == 1 {
+                match first {
+                    b'\r' => self.after_cr = true,
+                    // The second half of a CRLF, already reported as Enter.
+                    b'\n' if after_cr => continue,
+                    _ => {}
+                }
+            }
+            events.push(event);
+        }
+        self.buf.drain(..start);
+        events
+    }
+
+    /// Parse the event at the start of `buf` and the number of bytes it was made of, or `None` if
+    /// it is not complete yet.
+    fn parse_one(&self, buf: &[u8], complete: bool) -> Option<(Event, usize)> {
+        match buf {
+            [] => return None,
+            [b'\x1B'] if complete => return Some((Event::Key(KeyCode::Esc.into()), 1)),
+            // Left for `cut_over_limit`, to report in pieces.
+            _ if buf.starts_with(PASTE_START) && self.options.paste_chunk_len.is_some() => return None,
+            _ => {}
+        }
+        if self.options.coalesce_text {
+            let text = printable_prefix(buf);
+            if text.chars().nth(1).is_some() {
+                return Some((Event::Text(text.to_owned()), text.len()));
+            }
+        }
+
+        let mut exhausted = false;
+        let mut unread = Vec::new();
+        let mut dropped = 0;
+        let (event, raw) = {
+            let rest = buf[1..].iter().map(|&byte| Ok(byte));
+            let mut iter = rest.chain(iter::from_fn(|| {
+                exhausted = true;
+                None
+            }));
+            parse_event(buf[0], &mut iter, &self.options, &mut unread, &mut dropped).ok()?
+        };
+        // The sequence went on past the input given so far.
+        if exhausted && !complete {
+            return None;
+        }
+        Some((event, raw.len() + dropped))
+    }
+}
+
+/// A sequence held to a limit of the options, read as it comes in.
+#[derive(Debug, Clone)]
+enum Cut {
+    /// A sequence cut by `max_sequence_len`, already reported.
+    Sequence(Skip),
+    /// A paste, whose text is kept up to `max_paste_len` until it ends or reported in pieces.
+    Paste(PasteBuf),
+}
+
+/// A sequence that goes on until a terminator.
+#[derive(Debug, Copy, Clone)]
+enum Body {
+    /// A bracketed paste, ended by `ESC [ 201 ~`.
+    Paste,
+    /// An OSC sequence, ended by BEL or `ESC \`, or broken by another ESC.
+    String,
+    /// A CSI sequence, made of parameter and intermediate bytes up to its final byte.
+    Csi,
+}
+
+impl Body {
+    /// What the incomplete sequence `seq` is in the body of, if nothing in it so far may end it.
+    fn of(seq: &[u8], options: &ParseOptions) -> Option<Body> {
+        if seq.starts_with(PASTE_START) {
+            return Some(Body::Paste);
+        }
+        let (body, rest) = match seq {
+            [ESC, b']', rest @ ..] if options.shell_integration => (Body::String, rest),
+            [ESC, b'[', rest @ ..] => (Body::Csi, rest),
+            [event::C1_CSI, rest @ ..] if options.c1_controls => (Body::Csi, rest),
+            _ => return None,
+        };
+        (!rest.iter().any(|&byte| body.may_end(byte))).then_some(body)
+    }
+
+    /// Whether `byte` may end the sequence, or break it.
+    fn may_end(self, byte: u8) -> bool {
+        match self {
+            Body::Paste => byte == ESC,
+            Body::String => byte == b'\x07' || byte == ESC,
+            Body::Csi => !(0x20..=0x3F).contains(&byte),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::{Key, KeyEvent, KeyModifiers, Paste};
+
+    fn keys(events: impl Iterator<Item=Event>) -> Vec<Event> {
+        events.collect()
+    }
+
+    #[test]
+    fn test_parser() {
+        let mut parser = Parser::new();
+        assert_eq!(keys(parser.advance(b"\x1B[1;")), []);
+        assert_eq!(keys(parser.advance(b"5")), []);
+        let up = KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL);
+        assert_eq!(keys(parser.advance(b"A\xC3")), [Event::Key(up)]);
+        assert_eq!(keys(parser.advance(b"\xA9\x1B")), [Event::Key(Key::Char('é').into())]);
+        assert_eq!(keys(parser.flush()), [Event::Key(Key::Esc.into())]);
+        assert_eq!(keys(parser.advance(b"\x1B")), [Event::Key(Key::Esc.into())]);
+
+        let options = ParseOptions { normalize_newlines: true, ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        assert_eq!(keys(parser.advance(b"\r")), [Event::Key(Key::Char('\n').into())]);
+        assert_eq!(keys(parser.advance(b"\n\n")), [Event::Key(Key::Char('\n').into())]);
+    }
+
+    #[test]
+    fn test_parser_waits_for_terminator() {
+        let mut parser = Parser::new();
+        for chunk in [&b"\x1B[200~a"[..], b"b\x1B", b"[20", b"1"] {
+            assert_eq!(keys(parser.advance(chunk)), []);
+        }
+        assert_eq!(keys(parser.advance(b"~")), [Event::Paste("ab".to_string())]);
+
+        let options = ParseOptions { shell_integration: true, ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        assert_eq!(keys(parser.advance(b"\x1B]633;A\x1B")), []);
+        assert_eq!(keys(parser.advance(b"\\x")).len(), 2);
+
+        let mut parser = Parser::new();
+        assert_eq!(keys(parser.advance(b"\x1B[1;")), []);
+        assert_eq!(keys(parser.advance(b"\x01")), [Event::Unsupported(b"\x1B[1;\x01".to_vec())]);
+    }
+
+    #[test]
+    fn test_parser_drops_cut_input() {
+        let options = ParseOptions { max_paste_len: Some(2), ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        assert_eq!(keys(parser.advance(b"\x1B[200~abcd\x1B[2")), []);
+        let paste = Event::PasteTruncated { text: "ab".to_string(), dropped: 2 };
+        assert_eq!(keys(parser.advance(b"01~x")), [paste, Event::Key(Key::Char('x').into())]);
+
+        // A cut sequence is reported at once, and its rest dropped as it comes.
+        let options = ParseOptions { max_sequence_len: Some(4), ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        assert_eq!(keys(parser.advance(b"\x1B[1;2;3")), [Event::Unsupported(b"\x1B[1;".to_vec())]);
+        assert_eq!(keys(parser.advance(b";4")), []);
+        assert_eq!(keys(parser.advance(b"Ay")), [Event::Key(Key::Char('y').into())]);
+        assert_eq!(keys(parser.advance(b"\x1B[1;")), [Event::Unsupported(b"\x1B[1;".to_vec())]);
+        assert_eq!(keys(parser.advance(b"2\x1B[A")), [Event::Key(Key::Up.into())]);
+    }
+
+    #[test]
+    fn test_parser_sequence_flood() {
+        let options = ParseOptions { max_sequence_len: Some(16), ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        let mut events = keys(parser.advance(b"\x1B["));
+        for _ in 0..10_000 {
+            events.extend(parser.advance(&b"1;".repeat(50)));
+            assert!(parser.buf.is_empty());
+        }
+        events.extend(parser.advance(b"mx"));
+        let cut = Event::Unsupported([&b"\x1B["[..], &b"1;".repeat(7)].concat());
+        assert_eq!(events, [cut, Event::Key(Key::Char('x').into())]);
+    }
+
+    #[test]
+    fn test_parser_paste_chunks() {
+        let chunk = |text: &str| Event::PastePart(Paste::Chunk(text.to_string()));
+        let options = ParseOptions { paste_chunk_len: Some(4), ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        assert_eq!(keys(parser.advance(b"\x1B[200~abc")), []);
+        assert_eq!(keys(parser.advance(b"defghi")), [chunk("abcd")]);
+        // Up to the character going past the length, and not the start of the end of the paste.
+        assert_eq!(keys(parser.advance("jk\u{e9}\x1B[20".as_bytes())), [chunk("efgh"), chunk("ijk")]);
+        let last = Event::PastePart(Paste::Last { text: "\u{e9}".to_string(), dropped: 0 });
+        assert_eq!(keys(parser.advance(b"1~x")), [last, Event::Key(Key::Char('x').into())]);
+
+        // `max_paste_len` counts all the pieces.
+        let options = ParseOptions { max_paste_len: Some(6), ..options };
+        let mut parser = Parser::new().with_options(options);
+        let last = Event::PastePart(Paste::Last { text: "ef".to_string(), dropped: 3 });
+        assert_eq!(keys(parser.advance(b"\x1B[200~abcdefghi\x1B[201~")), [chunk("abcd"), last]);
+    }
+
+    #[test]
+    fn test_parser_paste_flood() {
+        let options = ParseOptions { max_paste_len: Some(16), ..ParseOptions::default() };
+        let mut parser = Parser::new().with_options(options);
+        assert_eq!(keys(parser.advance(b"\x1B[200~")), []);
+        let chunk = [b'a'; 1000];
+        for _ in 0..10_000 {
+            assert_eq!(keys(parser.advance(&chunk)), []);
+            let Some(Cut::Paste(paste)) = &parser.cut else { panic!("not dropping the paste") };
+            assert!(parser.buf.is_empty() && paste.buf.capacity() <= 64);
+        }
+        let paste = Event::PasteTruncated { text: "a".repeat(16), dropped: 10_000_000 - 16 };
+        assert_eq!(keys(parser.advance(b"\x1B[201~x")), [paste, Event::Key(Key::Char('x').into())]);
+    }
+}
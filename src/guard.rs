@@ -0,0 +1,52 @@
+//! The output target of a guard that undoes a terminal mode when it is done.
+
+use std::io::{self, Write};
+
+use crate::batch;
+
+/// An output target on which a mode was enabled, undone once: when taken back with `into_inner`,
+/// or else when dropped.
+pub(crate) struct ModeGuard<W: Write> {
+    /// The output target, `None` once taken back by `into_inner`.
+    term: Option<W>,
+    /// The sequence undoing the mode, `None` if it was not enabled, that is in batch mode.
+    exit: Option<&'static [u8]>,
+}
+
+impl<W: Write> ModeGuard<W> {
+    /// Write `enter` to `term`, unless in batch mode, and `exit` when done.
+    pub(crate) fn enable(mut term: W, enter: &[u8], exit: &'static [u8]) -> io::Result<ModeGuard<W>> {
+        let active = !batch::is_batch();
+        if active {
+            term.write_all(enter)?;
+        }
+        Ok(ModeGuard { term: Some(term), exit: active.then_some(exit) })
+    }
+
+    pub(crate) fn get_ref(&self) -> &W {
+        self.term.as_ref().expect("the output target is only taken when consumed")
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut W {
+        self.term.as_mut().expect("the output target is only taken when consumed")
+    }
+
+    /// Undo the mode and get the output target back.
+    pub(crate) fn into_inner(mut self) -> io::Result<W> {
+        // Taking the output target leaves nothing for `drop` to undo.
+        let mut term = self.term.take().expect("the output target is only taken when consumed");
+        if let Some(exit) = self.exit {
+            term.write_all(exit)?;
+        }
+        Ok(term)
+    }
+}
+
+impl<W: Write> Drop for ModeGuard<W> {
+    fn drop(&mut self) {
+        // Best-effort: the output may well be gone by now, `into_inner` reports errors instead.
+        if let (Some(term), Some(exit)) = (&mut self.term, self.exit) {
+            let _ = term.write_all(exit);
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! Detecting idle sessions.
+//!
+//! Input read from the TTY through `async_stdin` and friends counts as activity, once a watcher
+//! exists. Input read by other means can be reported with `record_activity`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use pres::idle::{self, IdleEvent};
+//! use std::time::Duration;
+//!
+//! let idle = idle::watch(Duration::from_secs(300));
+//! for event in idle {
+//!     match event {
+//!         IdleEvent::Idle => println!("Locking the screen."),
+//!         IdleEvent::Active => println!("Welcome back."),
+//!     }
+//! }
+//! ```
+
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::mpsc::{self, Receiver};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often an idle session is checked for renewed activity.
+const ACTIVE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A transition between idle and active.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum IdleEvent {
+    /// No input was received for the watched threshold.
+    Idle,
+    /// Input was received again after being idle.
+    Active,
+}
+
+/// The reference point of `LAST_ACTIVITY`.
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Time of the last input, in milliseconds since `epoch()`.
+static LAST_ACTIVITY: AtomicU64 = AtomicU64::new(0);
+
+fn now() -> u64 {
+    epoch().elapsed().as_millis() as u64
+}
+
+/// Whether `watch` was called, so TTY input is worth recording.
+static WATCHED: AtomicBool = AtomicBool::new(false);
+
+/// Record that input was received just now.
+pub fn record_activity() {
+    LAST_ACTIVITY.fetch_max(now(), Ordering::Relaxed);
+}
+
+/// Record that the TTY readers received input, if anyone watches for it.
+pub(crate) fn record_tty_input() {
+    if WATCHED.load(Ordering::Relaxed) {
+        record_activity();
+    }
+}
+
+/// Watch for the session going idle for at least `threshold`, and for it becoming active again.
+///
+/// The session starts out active. Transitions are sent to the returned receiver from a background
+/// thread, which stops at the first transition after the receiver is dropped.
+pub fn watch(threshold: Duration) -> Receiver<IdleEvent> {
+    WATCHED.store(true, Ordering::Relaxed);
+    let (send, recv) = mpsc::channel();
+    let threshold = threshold.as_millis() as u64;
+    let start = now();
+
+    thread::spawn(move || {
+        let last = || LAST_ACTIVITY.load(Ordering::Relaxed).max(start);
+        loop {
+            // Active: sleep until the threshold could have passed.
+            let idle_for = now().saturating_sub(last());
+            if idle_for < threshold {
+                thread::sleep(Duration::from_millis(threshold - idle_for));
+                continue;
+            }
+            if send.send(IdleEvent::Idle).is_err() {
+                return;
+            }
+
+            // Idle: wait for any new input.
+            let idle_since = last();
+            while last() == idle_since {
+                thread::sleep(ACTIVE_POLL_INTERVAL);
+            }
+            if send.send(IdleEvent::Active).is_err() {
+                return;
+            }
+        }
+    });
+
+    recv
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_idle_and_active() {
+        let events = watch(Duration::from_millis(20));
+        let timeout = Duration::from_secs(5);
+
+        assert_eq!(events.recv_timeout(timeout), Ok(IdleEvent::Idle));
+        thread::sleep(Duration::from_millis(5));
+        record_activity();
+        assert_eq!(events.recv_timeout(timeout), Ok(IdleEvent::Active));
+    }
+}
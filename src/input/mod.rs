@@ -1,14 +1,20 @@
 //! User input.
 
 
-use std::io::{self, Read, Write};
+use std::collections::VecDeque;
+use std::fmt;
+use std::io::{self, BufWriter, Read, Write};
 use std::ops;
 
-use crate::event::{Event, Key};
-use crate::event::events::{Events, EventsAndRaw};
+use crate::batch;
+use crate::event::{Event, Key, KeyEventKind};
+use crate::event::events::{Events, EventsAndRaw, RawBytes};
+use crate::guard::ModeGuard;
 use crate::raw::IntoRawMode;
 
 /// An iterator over input keys.
+///
+/// Key releases are skipped.
 pub struct Keys<R> {
     iter: Events<R>,
 }
@@ -19,7 +25,7 @@ impl<R: Read> Iterator for Keys<R> {
     fn next(&mut self) -> Option<Result<Key, io::Error>> {
         loop {
             match self.iter.next() {
-                Some(Ok(Event::Key(k))) => return Some(Ok(k)),
+                Some(Ok(Event::Key(k))) if k.kind != KeyEventKind::Release => return Some(Ok(k.into())),
                 Some(Ok(_)) => continue,
                 Some(Err(e)) => return Some(Err(e)),
                 None => return None,
@@ -39,14 +45,15 @@ pub trait TermRead {
 
     /// Read a line.
     ///
-    /// EOT and ETX will abort the prompt, returning `None`. Newline or carriage return will
-    /// complete the input.
+    /// EOT and ETX will abort the prompt, returning `None`, as does the end of the input before
+    /// anything was typed (e.g. with `</dev/null`). Newline or carriage return will complete the
+    /// input. NUL, sent for Ctrl+Space, is ignored.
     fn read_line(&mut self) -> io::Result<Option<String>>;
 
     /// Read a password.
     ///
-    /// EOT and ETX will abort the prompt, returning `None`. Newline or carriage return will
-    /// complete the input.
+    /// EOT and ETX will abort the prompt, returning `None`, as does the end of the input before
+    /// anything was typed. Newline or carriage return will complete the input.
     fn read_passwd<W: Write>(&mut self, writer: &mut W) -> io::Result<Option<String>> {
         let _raw = writer.into_raw_mode()?;
         self.read_line()
@@ -57,7 +64,8 @@ pub trait TermRead {
 impl<R: Read + TermReadEventsAndRaw> TermRead for R {
     fn events(self) -> Events<Self> {
         Events {
-            inner: self.events_and_raw()
+            inner: self.events_and_raw(),
+            last_raw: RawBytes::new(),
         }
     }
     fn keys(self) -> Keys<Self> {
@@ -66,16 +74,25 @@ impl<R: Read + TermReadEventsAndRaw> TermRead for R {
 
     fn read_line(&mut self) -> io::Result<Option<String>> {
         let mut buf = Vec::with_capacity(30);
+        let mut complete = false;
 
         for c in self.bytes() {
             match c {
                 Err(e) => return Err(e),
-                Ok(0) | Ok(3) | Ok(4) => return Ok(None),
+                Ok(3) | Ok(4) => return Ok(None),
+                // Ctrl+Space.
+                Ok(0) => {}
                 Ok(0x7f) => { buf.pop(); }
-                Ok(b'\n') | Ok(b'\r') => break,
+                Ok(b'\n') | Ok(b'\r') => {
+                    complete = true;
+                    break;
+                }
                 Ok(c) => buf.push(c),
             }
         }
+        if !complete && buf.is_empty() {
+            return Ok(None);
+        }
 
         let string = String::from_utf8(buf)
             .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
@@ -94,33 +111,305 @@ impl<R: Read> TermReadEventsAndRaw for R {
         EventsAndRaw {
             source: self,
             leftover: None,
+            after_cr: false,
+            pending: VecDeque::new(),
+            failed: false,
+            resize: None,
+            paste: None,
+            options: Default::default(),
         }
     }
 }
 
-/// A sequence of escape codes to enable terminal mouse support.
-const ENTER_MOUSE_SEQUENCE: &'static str = csi!("?1000h\x1b[?1002h\x1b[?1015h\x1b[?1006h");
+/// The modes enabled by `MouseTerminal::from`: button and drag tracking, with SGR reports and
+/// urxvt ones as a fallback.
+const DEFAULT_MOUSE_MODES: [u16; 4] = [1000, 1002, 1015, 1006];
+
+derive_csi_sequence!("Report mouse motion even with no button held.", EnterMotionTracking, "?1003h");
+derive_csi_sequence!("Only report mouse motion while a button is held.", ExitMotionTracking, "?1003l");
+derive_csi_sequence!("Report mouse positions in pixels (SGR-Pixels).", EnterPixelCoordinates, "?1016h");
+derive_csi_sequence!("Report mouse positions in cells again.", ExitPixelCoordinates, "?1016l");
+
+/// Which mouse events the terminal reports.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum MouseTracking {
+    /// Button presses and releases, and the wheel (mode 1000).
+    Clicks,
+    /// Also motion while a button is held, as `MouseEvent::Hold` (mode 1002).
+    #[default]
+    Drags,
+    /// Also motion with no button held, as `MouseEvent::Moved` (mode 1003).
+    AnyMotion,
+}
 
-/// A sequence of escape codes to disable terminal mouse support.
-const EXIT_MOUSE_SEQUENCE: &'static str = csi!("?1006l\x1b[?1015l\x1b[?1002l\x1b[?1000l");
+impl MouseTracking {
+    fn modes(self) -> &'static [u16] {
+        match self {
+            MouseTracking::Clicks => &[1000],
+            MouseTracking::Drags => &[1000, 1002],
+            MouseTracking::AnyMotion => &[1000, 1003],
+        }
+    }
+}
+
+/// How the terminal encodes mouse reports.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum MouseEncoding {
+    /// The original X10 encoding, with one byte per coordinate: positions past column or row 223
+    /// cannot be reported.
+    X10,
+    /// The urxvt encoding (mode 1015), with decimal coordinates.
+    Urxvt,
+    /// The SGR encoding (mode 1006), which also tells which button was released.
+    Sgr,
+    /// The SGR encoding with pixel coordinates (mode 1016), see
+    /// `MouseTerminal::pixel_coordinates`.
+    SgrPixels,
+    /// SGR, falling back to urxvt on terminals that only have that.
+    ///
+    /// A few terminals mishandle having both enabled; pick one of them there.
+    #[default]
+    SgrOrUrxvt,
+}
+
+impl MouseEncoding {
+    fn modes(self) -> &'static [u16] {
+        match self {
+            MouseEncoding::X10 => &[],
+            MouseEncoding::Urxvt => &[1015],
+            MouseEncoding::Sgr => &[1006],
+            MouseEncoding::SgrPixels => &[1016],
+            MouseEncoding::SgrOrUrxvt => &[1015, 1006],
+        }
+    }
+}
+
+/// A builder for a `MouseTerminal` with a choice of tracking and encoding, from
+/// `MouseTerminal::builder`.
+///
+/// The defaults are those of `MouseTerminal::from`.
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pres::input::{MouseEncoding, MouseTerminal, MouseTracking};
+/// use std::io::stdout;
+///
+/// // Only clicks, in SGR reports.
+/// let stdout = MouseTerminal::builder()
+///     .tracking(MouseTracking::Clicks)
+///     .encoding(MouseEncoding::Sgr)
+///     .build(stdout())
+///     .unwrap();
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct MouseTerminalBuilder {
+    tracking: MouseTracking,
+    encoding: MouseEncoding,
+    buffered: bool,
+}
+
+impl MouseTerminalBuilder {
+    /// Start from the defaults: drags, in SGR or urxvt reports, unbuffered.
+    pub fn new() -> MouseTerminalBuilder {
+        MouseTerminalBuilder::default()
+    }
+
+    /// Set which mouse events are reported.
+    pub fn tracking(mut self, tracking: MouseTracking) -> MouseTerminalBuilder {
+        self.tracking = tracking;
+        self
+    }
+
+    /// Set how mouse reports are encoded.
+    pub fn encoding(mut self, encoding: MouseEncoding) -> MouseTerminalBuilder {
+        self.encoding = encoding;
+        self
+    }
+
+    /// Buffer writes internally, see `MouseTerminal::buffered`.
+    pub fn buffered(mut self, buffered: bool) -> MouseTerminalBuilder {
+        self.buffered = buffered;
+        self
+    }
+
+    /// Enable mouse support with these settings.
+    pub fn build<W: Write>(self, from: W) -> io::Result<MouseTerminal<W>> {
+        let capacity = if self.buffered { MOUSE_BUFFER_CAPACITY } else { 0 };
+        let modes = [self.tracking.modes(), self.encoding.modes()].concat();
+        MouseTerminal::with_modes(capacity, from, modes)
+    }
+}
+
+/// The buffer size of a buffered `MouseTerminal`.
+const MOUSE_BUFFER_CAPACITY: usize = 8 * 1024;
 
 /// A terminal with added mouse support.
 ///
-/// This can be obtained through the `From` implementations.
+/// This can be obtained through `MouseTerminal::new` or the `From` implementations, which pass
+/// every write straight through, through `MouseTerminal::buffered`, or through
+/// `MouseTerminal::builder` to choose which modes are enabled.
+///
+/// Dropping it disables mouse support again on a best-effort basis: errors are ignored, since the
+/// output may well be gone by then. Use `into_inner` to see them.
 pub struct MouseTerminal<W: Write> {
-    term: W,
+    /// The output target, `None` once taken back by `into_inner`. Unbuffered terminals use a
+    /// zero-sized buffer, which `BufWriter` bypasses.
+    term: Option<BufWriter<W>>,
+    /// Whether mouse support was enabled, that is we are not in batch mode.
+    active: bool,
+    /// The modes enabled, in order, to be disabled in reverse.
+    modes: Vec<u16>,
+    /// Whether the modes are disabled for now, see `suspend`.
+    suspended: bool,
 }
 
 impl<W: Write> From<W> for MouseTerminal<W> {
-    fn from(mut from: W) -> MouseTerminal<W> {
-        from.write_all(ENTER_MOUSE_SEQUENCE.as_bytes()).unwrap();
-        MouseTerminal { term: from }
+    /// Enable mouse support on `from`.
+    ///
+    /// # Panics
+    ///
+    /// If enabling it fails, e.g. because the output was closed. `MouseTerminal::new` returns the
+    /// error instead.
+    fn from(from: W) -> MouseTerminal<W> {
+        MouseTerminal::new(from).unwrap()
+    }
+}
+
+impl MouseTerminal<io::Stdout> {
+    /// A builder to choose which modes are enabled, for any output target.
+    ///
+    /// This is `MouseTerminalBuilder::new`, only defined for one output type so that calling it
+    /// needs no type annotations.
+    pub fn builder() -> MouseTerminalBuilder {
+        MouseTerminalBuilder::new()
+    }
+}
+
+impl<W: Write> MouseTerminal<W> {
+    /// Enable mouse support on `from`, passing every write straight through.
+    pub fn new(from: W) -> io::Result<MouseTerminal<W>> {
+        MouseTerminal::with_modes(0, from, DEFAULT_MOUSE_MODES.to_vec())
+    }
+
+    /// Enable mouse support, buffering writes internally.
+    ///
+    /// Writes are collected and only handed to `from` when the buffer fills up or on an explicit
+    /// `flush`, so wrapping an unbuffered stream such as `Stdout` in several guards does not turn
+    /// every small write into a system call. Remember to flush after each frame.
+    ///
+    /// Nothing is written to `from` yet, so this cannot fail.
+    pub fn buffered(from: W) -> MouseTerminal<W> {
+        // Writing to the empty buffer does not reach `from`.
+        MouseTerminal::with_modes(MOUSE_BUFFER_CAPACITY, from, DEFAULT_MOUSE_MODES.to_vec()).unwrap()
+    }
+
+    fn with_modes(capacity: usize, from: W, modes: Vec<u16>) -> io::Result<MouseTerminal<W>> {
+        let mut term = BufWriter::with_capacity(capacity, from);
+        let active = !batch::is_batch();
+        if !active {
+            return Ok(MouseTerminal { term: Some(term), active, modes: Vec::new(), suspended: false });
+        }
+        // One write for all the modes, so an unbuffered terminal does not pay for each.
+        term.write_all(enter_sequence(&modes).as_bytes())?;
+        Ok(MouseTerminal { term: Some(term), active, modes, suspended: false })
+    }
+
+    /// The output target, which is only taken by `into_inner`.
+    fn term(&mut self) -> &mut BufWriter<W> {
+        self.term.as_mut().expect("the output target is only taken when consumed")
+    }
+
+    /// Enable `mode` too, if it is not yet.
+    ///
+    /// This is best-effort: if the write fails, the mode is left out and the error shows again
+    /// on the next write.
+    fn enable(&mut self, mode: u16) {
+        if self.active && !self.modes.contains(&mode) {
+            if !self.suspended && write!(self.term(), csi!("?{}h"), mode).is_err() {
+                return;
+            }
+            self.modes.push(mode);
+        }
+    }
+
+    /// Also report the mouse moving with no button held, as `MouseEvent::Moved`, e.g. for hover
+    /// highlighting.
+    ///
+    /// This is xterm's any-motion tracking mode, which sends a report for every cell the pointer
+    /// crosses, so only enable it when the motion is needed.
+    pub fn track_motion(mut self) -> MouseTerminal<W> {
+        self.enable(1003);
+        self
+    }
+
+    /// Report mouse positions in pixels instead of cells, for sub-cell precision.
+    ///
+    /// The reports look like ordinary SGR ones, so the events must be read with
+    /// `ParseOptions::sgr_pixels` set for them to come as `MouseEvent::Pixels`. Terminals without
+    /// SGR-Pixels mode keep reporting cells.
+    pub fn pixel_coordinates(mut self) -> MouseTerminal<W> {
+        self.enable(1016);
+        self
+    }
+
+    /// Disable mouse support and get the output target back.
+    ///
+    /// Unlike dropping, this keeps the wrapped writer, and any guard it is itself (such as a
+    /// `RawTerminal`), alive.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        let restored = self.restore();
+        // Taking the output target leaves nothing for `drop` to restore.
+        let term = self.term.take().expect("the output target is only taken when consumed");
+        restored?;
+        term.into_inner().map_err(|e| e.into_error())
+    }
+
+    /// Temporarily disable mouse support, e.g. before running a shell or an editor, and flush.
+    ///
+    /// Suspending a suspended terminal does nothing.
+    pub fn suspend(&mut self) -> io::Result<()> {
+        if !self.suspended {
+            self.suspended = true;
+            let exit = exit_sequence(&self.modes);
+            self.term().write_all(exit.as_bytes())?;
+        }
+        self.term().flush()
+    }
+
+    /// Enable mouse support again after `suspend`, and flush.
+    ///
+    /// Resuming a terminal that is not suspended does nothing.
+    pub fn resume(&mut self) -> io::Result<()> {
+        if self.suspended {
+            self.suspended = false;
+            let enter = enter_sequence(&self.modes);
+            self.term().write_all(enter.as_bytes())?;
+        }
+        self.term().flush()
     }
+
+    /// Disable mouse support again, if it was enabled, and flush.
+    fn restore(&mut self) -> io::Result<()> {
+        self.suspend()
+    }
+}
+
+/// The sequence enabling `modes`, in order.
+fn enter_sequence(modes: &[u16]) -> String {
+    modes.iter().map(|mode| format!(csi!("?{}h"), mode)).collect()
+}
+
+/// The sequence disabling `modes`, in reverse order.
+fn exit_sequence(modes: &[u16]) -> String {
+    modes.iter().rev().map(|mode| format!(csi!("?{}l"), mode)).collect()
 }
 
 impl<W: Write> Drop for MouseTerminal<W> {
     fn drop(&mut self) {
-        self.term.write_all(EXIT_MOUSE_SEQUENCE.as_bytes()).unwrap();
+        if self.term.is_some() {
+            let _ = self.restore();
+        }
     }
 }
 
@@ -128,23 +417,388 @@ impl<W: Write> ops::Deref for MouseTerminal<W> {
     type Target = W;
 
     fn deref(&self) -> &W {
-        &self.term
+        self.term.as_ref().expect("the output target is only taken when consumed").get_ref()
     }
 }
 
 impl<W: Write> ops::DerefMut for MouseTerminal<W> {
+    /// Get the output target, bypassing the buffer of a buffered terminal.
     fn deref_mut(&mut self) -> &mut W {
-        &mut self.term
+        self.term().get_mut()
     }
 }
 
 impl<W: Write> Write for MouseTerminal<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.term.write(buf)
+        self.term().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term().flush()
+    }
+}
+
+derive_csi_sequence!("Make the terminal send arrow keys for the mouse wheel on the alternate screen.",
+                     EnterAlternateScroll, "?1007h");
+derive_csi_sequence!("Stop translating the mouse wheel on the alternate screen.", ExitAlternateScroll, "?1007l");
+
+/// A terminal in alternate scroll mode.
+///
+/// While mouse reporting is off, the terminal translates the mouse wheel into up and down arrow
+/// keys when the alternate screen is shown, giving full-screen programs wheel support without
+/// handling the mouse. To do the same translation with mouse reporting on, see
+/// `event::adapters::WheelToArrows`.
+///
+/// This can be obtained through `AlternateScroll::new` or the `From` implementations.
+///
+/// Dropping it leaves alternate scroll mode on a best-effort basis: errors are ignored, since the
+/// output may well be gone by then. Use `into_inner` to see them.
+pub struct AlternateScroll<W: Write> {
+    term: ModeGuard<W>,
+}
+
+impl<W: Write> From<W> for AlternateScroll<W> {
+    /// Enter alternate scroll mode on `from`.
+    ///
+    /// # Panics
+    ///
+    /// If entering it fails, e.g. because the output was closed. `AlternateScroll::new` returns the error instead.
+    fn from(from: W) -> AlternateScroll<W> {
+        AlternateScroll::new(from).unwrap()
+    }
+}
+
+impl<W: Write> AlternateScroll<W> {
+    /// Enter alternate scroll mode on `from`.
+    pub fn new(from: W) -> io::Result<AlternateScroll<W>> {
+        let term = ModeGuard::enable(from, EnterAlternateScroll::BYTES, ExitAlternateScroll::BYTES)?;
+        Ok(AlternateScroll { term })
+    }
+
+    /// Leave alternate scroll mode and get the output target back.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.term.into_inner()
+    }
+}
+
+impl<W: Write> ops::Deref for AlternateScroll<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.term.get_ref()
+    }
+}
+
+impl<W: Write> ops::DerefMut for AlternateScroll<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.term.get_mut()
+    }
+}
+
+impl<W: Write> Write for AlternateScroll<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.get_mut().flush()
+    }
+}
+
+/// Keyboard enhancements of the kitty keyboard protocol.
+///
+/// Combine them with `|`.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct KeyboardEnhancementFlags(u8);
+
+impl KeyboardEnhancementFlags {
+    /// Report Esc, Alt and Ctrl combinations, and keys such as Ctrl+I that legacy terminals
+    /// confuse with others, as unambiguous `CSI u` sequences.
+    pub const DISAMBIGUATE_ESCAPE_CODES: KeyboardEnhancementFlags = KeyboardEnhancementFlags(1);
+    /// Report key repeats and releases too, see `KeyEventKind`.
+    pub const REPORT_EVENT_TYPES: KeyboardEnhancementFlags = KeyboardEnhancementFlags(2);
+    /// Report the shifted and base layout keys along with the key, see `KeyEvent::base_layout`.
+    pub const REPORT_ALTERNATE_KEYS: KeyboardEnhancementFlags = KeyboardEnhancementFlags(4);
+    /// Report every key, including Enter, Tab and plain text, as a `CSI u` sequence.
+    pub const REPORT_ALL_KEYS_AS_ESCAPE_CODES: KeyboardEnhancementFlags = KeyboardEnhancementFlags(8);
+
+    /// The flags as the number sent to the terminal.
+    pub fn bits(self) -> u8 {
+        self.0
+    }
+}
+
+impl ops::BitOr for KeyboardEnhancementFlags {
+    type Output = KeyboardEnhancementFlags;
+
+    fn bitor(self, rhs: KeyboardEnhancementFlags) -> KeyboardEnhancementFlags {
+        KeyboardEnhancementFlags(self.0 | rhs.0)
+    }
+}
+
+derive_csi_sequence!("Pop the keyboard enhancement flags pushed last.", PopKeyboardEnhancementFlags, "<u");
+
+/// A terminal with the kitty keyboard protocol enabled.
+///
+/// The flags are pushed on the terminal's stack when the guard is created and popped when it is
+/// dropped. Terminals without the protocol ignore both, so this is safe to use unconditionally;
+/// the keys they send are parsed as before.
+///
+/// This can be obtained through the `From` implementations, which enable
+/// `DISAMBIGUATE_ESCAPE_CODES`, or through `KeyboardEnhancement::with_flags`.
+///
+/// Dropping it pops the flags on a best-effort basis: errors are ignored, since the output may well
+/// be gone by then. Use `into_inner` to see them.
+pub struct KeyboardEnhancement<W: Write> {
+    term: ModeGuard<W>,
+}
+
+impl<W: Write> From<W> for KeyboardEnhancement<W> {
+    /// Push `DISAMBIGUATE_ESCAPE_CODES` on the keyboard enhancement stack of `from`.
+    ///
+    /// # Panics
+    ///
+    /// If pushing it fails, e.g. because the output was closed. `KeyboardEnhancement::with_flags`
+    /// returns the error instead.
+    fn from(from: W) -> KeyboardEnhancement<W> {
+        KeyboardEnhancement::with_flags(from, KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES).unwrap()
+    }
+}
+
+impl<W: Write> KeyboardEnhancement<W> {
+    /// Push `flags` on the keyboard enhancement stack of the terminal.
+    pub fn with_flags(from: W, flags: KeyboardEnhancementFlags) -> io::Result<KeyboardEnhancement<W>> {
+        let push = format!(csi!(">{}u"), flags.bits());
+        let term = ModeGuard::enable(from, push.as_bytes(), PopKeyboardEnhancementFlags::BYTES)?;
+        Ok(KeyboardEnhancement { term })
+    }
+
+    /// Pop the flags and get the output target back.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.term.into_inner()
+    }
+}
+
+impl<W: Write> ops::Deref for KeyboardEnhancement<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.term.get_ref()
+    }
+}
+
+impl<W: Write> ops::DerefMut for KeyboardEnhancement<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.term.get_mut()
+    }
+}
+
+impl<W: Write> Write for KeyboardEnhancement<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.get_mut().flush()
+    }
+}
+
+derive_csi_sequence!("Enable bracketed paste mode.", EnableBracketedPaste, "?2004h");
+derive_csi_sequence!("Disable bracketed paste mode.", DisableBracketedPaste, "?2004l");
+
+/// A terminal in bracketed paste mode.
+///
+/// Pasted text is reported as a single `Event::Paste` instead of one key event per character, so
+/// that for example a pasted newline does not submit a prompt.
+///
+/// This can be obtained through `BracketedPaste::new` or the `From` implementations.
+///
+/// Dropping it leaves bracketed paste mode on a best-effort basis: errors are ignored, since the
+/// output may well be gone by then. Use `into_inner` to see them.
+pub struct BracketedPaste<W: Write> {
+    term: ModeGuard<W>,
+}
+
+impl<W: Write> From<W> for BracketedPaste<W> {
+    /// Enable bracketed paste mode on `from`.
+    ///
+    /// # Panics
+    ///
+    /// If enabling it fails, e.g. because the output was closed. `BracketedPaste::new` returns the error instead.
+    fn from(from: W) -> BracketedPaste<W> {
+        BracketedPaste::new(from).unwrap()
+    }
+}
+
+impl<W: Write> BracketedPaste<W> {
+    /// Enable bracketed paste mode on `from`.
+    pub fn new(from: W) -> io::Result<BracketedPaste<W>> {
+        let term = ModeGuard::enable(from, EnableBracketedPaste::BYTES, DisableBracketedPaste::BYTES)?;
+        Ok(BracketedPaste { term })
+    }
+
+    /// Leave bracketed paste mode and get the output target back.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.term.into_inner()
+    }
+}
+
+impl<W: Write> ops::Deref for BracketedPaste<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.term.get_ref()
+    }
+}
+
+impl<W: Write> ops::DerefMut for BracketedPaste<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.term.get_mut()
+    }
+}
+
+impl<W: Write> Write for BracketedPaste<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.get_mut().flush()
+    }
+}
+
+/// Make the keypad send application sequences (DECKPAM).
+const ENTER_KEYPAD_APPLICATION_MODE: &[u8] = b"\x1B=";
+
+/// Make the keypad send the characters on its keys again (DECKPNM).
+const EXIT_KEYPAD_APPLICATION_MODE: &[u8] = b"\x1B>";
+
+/// A terminal in application keypad mode.
+///
+/// The keypad sends `SS3` sequences, so that a program can tell it apart from the main keyboard.
+/// They are read as `KeyCode::Keypad` keys; `KeypadKey::main_key` gives the digit, operator or
+/// Enter on each. Numeric keypad mode is
+/// restored when done, which is also how to recover from a program that left the terminal in
+/// application mode.
+///
+/// This can be obtained through `KeypadApplicationMode::new` or the `From` implementations.
+///
+/// Dropping it restores numeric keypad mode on a best-effort basis: errors are ignored, since the
+/// output may well be gone by then. Use `into_inner` to see them.
+pub struct KeypadApplicationMode<W: Write> {
+    term: ModeGuard<W>,
+}
+
+impl<W: Write> From<W> for KeypadApplicationMode<W> {
+    /// Switch the keypad to application mode on `from`.
+    ///
+    /// # Panics
+    ///
+    /// If switching it fails, e.g. because the output was closed. `KeypadApplicationMode::new` returns the error instead.
+    fn from(from: W) -> KeypadApplicationMode<W> {
+        KeypadApplicationMode::new(from).unwrap()
+    }
+}
+
+impl<W: Write> KeypadApplicationMode<W> {
+    /// Switch the keypad to application mode on `from`.
+    pub fn new(from: W) -> io::Result<KeypadApplicationMode<W>> {
+        let term = ModeGuard::enable(from, ENTER_KEYPAD_APPLICATION_MODE, EXIT_KEYPAD_APPLICATION_MODE)?;
+        Ok(KeypadApplicationMode { term })
+    }
+
+    /// Leave application keypad mode and get the output target back.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.term.into_inner()
+    }
+}
+
+impl<W: Write> ops::Deref for KeypadApplicationMode<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.term.get_ref()
+    }
+}
+
+impl<W: Write> ops::DerefMut for KeypadApplicationMode<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.term.get_mut()
+    }
+}
+
+impl<W: Write> Write for KeypadApplicationMode<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.get_mut().write(buf)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.term.flush()
+        self.term.get_mut().flush()
+    }
+}
+
+derive_csi_sequence!("Make xterm report modified keys as `CSI 27 ; mods ; code ~`.", EnableModifyOtherKeys, ">4;2m");
+derive_csi_sequence!("Restore the default reporting of modified keys.", DisableModifyOtherKeys, ">4m");
+
+/// A terminal with xterm's modifyOtherKeys mode enabled.
+///
+/// Keys that would otherwise lose their modifiers, such as Ctrl+Enter or Ctrl+Shift+A, are
+/// reported with them. This is the xterm counterpart of `KeyboardEnhancement`; terminals without
+/// it ignore the sequence.
+///
+/// This can be obtained through `ModifyOtherKeys::new` or the `From` implementations.
+///
+/// Dropping it disables modifyOtherKeys on a best-effort basis: errors are ignored, since the
+/// output may well be gone by then. Use `into_inner` to see them.
+pub struct ModifyOtherKeys<W: Write> {
+    term: ModeGuard<W>,
+}
+
+impl<W: Write> From<W> for ModifyOtherKeys<W> {
+    /// Enable modifyOtherKeys on `from`.
+    ///
+    /// # Panics
+    ///
+    /// If enabling it fails, e.g. because the output was closed. `ModifyOtherKeys::new` returns the error instead.
+    fn from(from: W) -> ModifyOtherKeys<W> {
+        ModifyOtherKeys::new(from).unwrap()
+    }
+}
+
+impl<W: Write> ModifyOtherKeys<W> {
+    /// Enable modifyOtherKeys on `from`.
+    pub fn new(from: W) -> io::Result<ModifyOtherKeys<W>> {
+        let term = ModeGuard::enable(from, EnableModifyOtherKeys::BYTES, DisableModifyOtherKeys::BYTES)?;
+        Ok(ModifyOtherKeys { term })
+    }
+
+    /// Leave modifyOtherKeys mode and get the output target back.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.term.into_inner()
+    }
+}
+
+impl<W: Write> ops::Deref for ModifyOtherKeys<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        self.term.get_ref()
+    }
+}
+
+impl<W: Write> ops::DerefMut for ModifyOtherKeys<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        self.term.get_mut()
+    }
+}
+
+impl<W: Write> Write for ModifyOtherKeys<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.term.get_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.term.get_mut().flush()
     }
 }
 
@@ -152,8 +806,6 @@ impl<W: Write> Write for MouseTerminal<W> {
 mod test {
     use std::io;
 
-    use event::{Event, Key, MouseButton, MouseEvent};
-
     use crate::event::{MouseButton, MouseEvent};
 
     use super::*;
@@ -179,21 +831,21 @@ mod test {
 
         assert_eq!(i.next().unwrap().unwrap(),
                    Event::Unsupported(vec![0x1B, b'[', 0x00]));
-        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Char('b')));
-        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Char('c')));
-        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Backspace));
-        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Left));
+        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Char('b').into()));
+        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Char('c').into()));
+        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Backspace.into()));
+        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Left.into()));
         assert_eq!(i.next().unwrap().unwrap(),
-                   Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4)));
+                   Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4).into()));
         assert_eq!(i.next().unwrap().unwrap(),
-                   Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4)));
+                   Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4).into()));
         assert_eq!(i.next().unwrap().unwrap(),
-                   Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4)));
+                   Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4).into()));
         assert_eq!(i.next().unwrap().unwrap(),
-                   Event::Mouse(MouseEvent::Release(2, 4)));
+                   Event::Mouse(MouseEvent::Release(Some(MouseButton::Left), 2, 4).into()));
         assert_eq!(i.next().unwrap().unwrap(),
-                   Event::Mouse(MouseEvent::Release(2, 4)));
-        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Char('b')));
+                   Event::Mouse(MouseEvent::Release(None, 2, 4).into()));
+        assert_eq!(i.next().unwrap().unwrap(), Event::Key(Key::Char('b').into()));
         assert!(i.next().is_none());
     }
 
@@ -204,29 +856,29 @@ mod test {
         let mut output = Vec::<u8>::new();
         {
             let mut i = input.events_and_raw().map(|res| res.unwrap())
-                .inspect(|&(_, ref raw)| { output.extend(raw); }).map(|(event, _)| event);
+                .inspect(|(_, raw)| { output.extend(raw); }).map(|(event, _)| event);
 
             assert_eq!(i.next().unwrap(),
                        Event::Unsupported(vec![0x1B, b'[', 0x00]));
-            assert_eq!(i.next().unwrap(), Event::Key(Key::Char('b')));
-            assert_eq!(i.next().unwrap(), Event::Key(Key::Char('c')));
-            assert_eq!(i.next().unwrap(), Event::Key(Key::Backspace));
-            assert_eq!(i.next().unwrap(), Event::Key(Key::Left));
+            assert_eq!(i.next().unwrap(), Event::Key(Key::Char('b').into()));
+            assert_eq!(i.next().unwrap(), Event::Key(Key::Char('c').into()));
+            assert_eq!(i.next().unwrap(), Event::Key(Key::Backspace.into()));
+            assert_eq!(i.next().unwrap(), Event::Key(Key::Left.into()));
             assert_eq!(i.next().unwrap(),
-                       Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4)));
+                       Event::Mouse(MouseEvent::Press(MouseButton::WheelUp, 2, 4).into()));
             assert_eq!(i.next().unwrap(),
-                       Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4)));
+                       Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4).into()));
             assert_eq!(i.next().unwrap(),
-                       Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4)));
+                       Event::Mouse(MouseEvent::Press(MouseButton::Left, 2, 4).into()));
             assert_eq!(i.next().unwrap(),
-                       Event::Mouse(MouseEvent::Release(2, 4)));
+                       Event::Mouse(MouseEvent::Release(Some(MouseButton::Left), 2, 4).into()));
             assert_eq!(i.next().unwrap(),
-                       Event::Mouse(MouseEvent::Release(2, 4)));
-            assert_eq!(i.next().unwrap(), Event::Key(Key::Char('b')));
+                       Event::Mouse(MouseEvent::Release(None, 2, 4).into()));
+            assert_eq!(i.next().unwrap(), Event::Key(Key::Char('b').into()));
             assert!(i.next().is_none());
         }
 
-        assert_eq!(input.iter().map(|b| *b).collect::<Vec<u8>>(), output)
+        assert_eq!(input.to_vec(), output)
     }
 
     #[test]
@@ -258,6 +910,44 @@ mod test {
         assert!(st.next().is_none());
     }
 
+    #[test]
+    fn test_shell_integration() {
+        use crate::event::ParseOptions;
+        use crate::shell::{Mark, ShellEvent};
+
+        let input = b"\x1B]133;A\x07$ \x1B]633;P;Cwd=/tmp\x1B\\\x1B]133;D;2\x1B\\";
+
+        let options = ParseOptions { shell_integration: true, ..ParseOptions::default() };
+        let mut st = input.events().with_options(options);
+        assert_eq!(st.next().unwrap().unwrap(), Event::Shell(ShellEvent::Mark(Mark::PromptStart)));
+        assert_eq!(st.next().unwrap().unwrap(), Event::Key(Key::Char('$').into()));
+        assert_eq!(st.next().unwrap().unwrap(), Event::Key(Key::Char(' ').into()));
+        assert_eq!(st.next().unwrap().unwrap(), Event::Shell(ShellEvent::Property {
+            name: "Cwd".to_string(),
+            value: "/tmp".to_string(),
+        }));
+        assert_eq!(st.next().unwrap().unwrap(),
+                   Event::Shell(ShellEvent::Mark(Mark::CommandFinished(Some(2)))));
+        assert!(st.next().is_none());
+
+        // Without the option, `ESC ]` stays an Alt-modified key.
+        let mut st = b"\x1B]a".keys();
+        assert_eq!(st.next().unwrap().unwrap(), Key::Alt(']'));
+        assert_eq!(st.next().unwrap().unwrap(), Key::Char('a'));
+    }
+
+    #[test]
+    fn test_wheel_to_arrows() {
+        use crate::event::adapters::WheelToArrows;
+
+        let mut st = WheelToArrows::new(b"\x1B[<65;1;1M\x1B[<64;1;1M\x1B[<0;1;1Mx".events());
+        assert_eq!(st.next().unwrap().unwrap(), Event::Key(Key::Down.into()));
+        assert_eq!(st.next().unwrap().unwrap(), Event::Key(Key::Up.into()));
+        assert_eq!(st.next().unwrap().unwrap(), Event::Mouse(MouseEvent::Press(MouseButton::Left, 1, 1).into()));
+        assert_eq!(st.next().unwrap().unwrap(), Event::Key(Key::Char('x').into()));
+        assert!(st.next().is_none());
+    }
+
     #[test]
     fn test_esc_key() {
         let mut st = b"\x1B".keys();
@@ -265,6 +955,56 @@ mod test {
         assert!(st.next().is_none());
     }
 
+    #[test]
+    fn test_events_last_raw() {
+        let mut events = b"a\x1B[A\x1B[\x00".events();
+        assert!(events.last_raw().is_empty());
+        events.next();
+        assert_eq!(events.last_raw(), b"a");
+        events.next();
+        assert_eq!(events.last_raw(), b"\x1B[A");
+        assert_eq!(events.next().unwrap().unwrap(), Event::Unsupported(vec![0x1B, b'[', 0x00]));
+        assert_eq!(events.last_raw(), b"\x1B[\x00");
+        assert!(events.next().is_none());
+        assert!(events.last_raw().is_empty());
+    }
+
+    #[test]
+    fn test_truncated_paste_raw() {
+        use crate::event::ParseOptions;
+
+        let options = ParseOptions { max_paste_len: Some(2), ..ParseOptions::default() };
+        let mut events = b"\x1B[200~abcdef\x1B[201~".events().with_options(options);
+        let event = events.next().unwrap().unwrap();
+        assert_eq!(event, Event::PasteTruncated { text: "ab".to_string(), dropped: 4 });
+        assert_eq!(events.last_raw(), b"\x1B[200~ab");
+    }
+
+    #[test]
+    fn test_paste_chunks() {
+        use crate::event::{Paste, ParseOptions};
+
+        let options = ParseOptions { paste_chunk_len: Some(3), ..ParseOptions::default() };
+        let mut events = b"\x1B[200~abcdefg\x1B[201~\x1B[A".events().with_options(options);
+        assert_eq!(events.next().unwrap().unwrap(), Event::PastePart(Paste::Chunk("abc".to_string())));
+        assert_eq!(events.last_raw(), b"\x1B[200~abc");
+        assert_eq!(events.next().unwrap().unwrap(), Event::PastePart(Paste::Chunk("def".to_string())));
+        assert_eq!(events.last_raw(), b"def");
+        let last = Event::PastePart(Paste::Last { text: "g".to_string(), dropped: 0 });
+        assert_eq!(events.next().unwrap().unwrap(), last);
+        assert_eq!(events.last_raw(), b"g\x1B[201~");
+        assert_eq!(events.next().unwrap().unwrap(), Event::Key(Key::Up.into()));
+        assert!(events.next().is_none());
+    }
+
+    #[test]
+    fn test_keys_skip_releases() {
+        let mut st = b"\x1B[97;1:1u\x1B[97;1:2u\x1B[97;1:3u".keys();
+        assert_eq!(st.next().unwrap().unwrap(), Key::Char('a'));
+        assert_eq!(st.next().unwrap().unwrap(), Key::Char('a'));
+        assert!(st.next().is_none());
+    }
+
     fn line_match(a: &str, b: Option<&str>) {
         let mut sink = io::sink();
 
@@ -299,6 +1039,44 @@ mod test {
                    Some("this is the secnd tes"));
     }
 
+    #[test]
+    fn test_closed_input() {
+        use std::fs::File;
+        use std::process::{Command, Stdio};
+
+        // `</dev/null`.
+        let null = || File::open("/dev/null").unwrap();
+        assert!(null().events().next().is_none());
+        assert!(null().keys().next().is_none());
+        assert_eq!(null().read_line().unwrap(), None);
+
+        // A pipe whose writer exited.
+        let mut child = Command::new("true").stdout(Stdio::piped()).spawn().unwrap();
+        let mut events = child.stdout.take().unwrap().events();
+        assert!(events.next().is_none());
+        assert!(events.next().is_none());
+        child.wait().unwrap();
+
+        // An input failing for good ends the events after the error instead of repeating it.
+        struct Closed(usize);
+        impl Read for Closed {
+            fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+                self.0 += 1;
+                Err(io::Error::from_raw_os_error(libc::EBADF))
+            }
+        }
+        let mut events = Closed(0).events();
+        assert!(events.next().unwrap().is_err());
+        assert!(events.next().is_none());
+        assert_eq!(events.inner.source.0, 1);
+    }
+
+    #[test]
+    fn test_ctrl_space() {
+        line_match("set\x00 mark", Some("set mark"));
+        line_match("abort\x03", None);
+    }
+
     #[test]
     fn test_end() {
         line_match("abc\nhttps://www.youtube.com/watch?v=dQw4w9WgXcQ",
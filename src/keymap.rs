@@ -0,0 +1,369 @@
+//! Configuring key bindings.
+//!
+//! Users can bind keys themselves with `capture_chord`, or keep the bindings they already set up
+//! for readline in `~/.inputrc`, read with `Inputrc`.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use pres::async_stdin;
+//! use pres::input::TermRead;
+//! use pres::keymap;
+//! use pres::raw::IntoRawMode;
+//! use std::io::stdout;
+//! use std::time::Duration;
+//!
+//! let _stdout = stdout().into_raw_mode().unwrap();
+//! println!("Press the keys to bind, then Enter.\r");
+//! let chord = keymap::capture_chord(async_stdin().events(), Duration::from_secs(5)).unwrap();
+//! println!("Bound {:?}\r", chord);
+//! ```
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::clock::{Clock, SystemClock};
+use crate::event::{Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use crate::input::TermRead;
+
+/// How often `capture_chord` checks for input when none is available.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Record the keys of a chord typed by the user, for "press keys to bind" prompts.
+///
+/// Recording stops at an unmodified Enter, which is not part of the chord, or once no key was
+/// pressed for `timeout`. Key releases and other events are ignored.
+///
+/// `events` is polled again after it runs dry, so the timeout only works with non-blocking input
+/// such as `async_stdin`; a blocking reader waits for the next key instead.
+pub fn capture_chord<I>(events: I, timeout: Duration) -> io::Result<Vec<KeyEvent>>
+    where I: Iterator<Item=io::Result<Event>>
+{
+    capture_chord_with_clock(events, timeout, &SystemClock)
+}
+
+/// `capture_chord`, timing the keys with `clock` rather than the system clock.
+pub fn capture_chord_with_clock<I, C>(mut events: I, timeout: Duration, clock: &C) -> io::Result<Vec<KeyEvent>>
+    where I: Iterator<Item=io::Result<Event>>,
+          C: Clock
+{
+    let mut chord = Vec::new();
+    let mut last_key = clock.now();
+    loop {
+        match events.next() {
+            Some(Ok(Event::Key(key))) if key.kind != KeyEventKind::Release => {
+                // Lock states are not modifiers held, so Enter still ends the chord with Num Lock on.
+                let held = key.modifiers - KeyModifiers::CAPS_LOCK - KeyModifiers::NUM_LOCK;
+                if key.code == KeyCode::Enter && held.is_empty() {
+                    return Ok(chord);
+                }
+                chord.push(key);
+                last_key = clock.now();
+            }
+            Some(Ok(_)) => {}
+            Some(Err(e)) => return Err(e),
+            None if clock.now() - last_key >= timeout => return Ok(chord),
+            None => thread::sleep(POLL_INTERVAL),
+        }
+    }
+}
+
+/// What a key sequence is bound to in an inputrc file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum InputrcAction {
+    /// A readline function, such as `beginning-of-line`.
+    Function(String),
+    /// Text to insert as if typed, from a quoted macro.
+    Macro(String),
+}
+
+/// A key binding from an inputrc file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct InputrcBinding {
+    /// The keys, as the events parsed from the bytes the terminal would send.
+    pub keys: Vec<KeyEvent>,
+    /// What the keys do.
+    pub action: InputrcAction,
+}
+
+/// The variables and key bindings of a readline init file, such as `~/.inputrc`.
+///
+/// Like readline, lines that cannot be parsed are skipped. Conditional blocks are followed for
+/// `$if mode=emacs` and `$if mode=vi`, against the `editing-mode` set so far; blocks for a given
+/// terminal or application are left out, and `$include` is ignored.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::event::{KeyCode, KeyEvent, KeyModifiers};
+/// use pres::keymap::{Inputrc, InputrcAction};
+///
+/// let inputrc = Inputrc::parse("set editing-mode vi\n\"\\C-l\": clear-screen\n");
+/// assert_eq!(inputrc.variable("editing-mode"), Some("vi"));
+/// assert_eq!(inputrc.bindings[0].keys, [KeyEvent::new(KeyCode::Char('l'), KeyModifiers::CONTROL)]);
+/// assert_eq!(inputrc.bindings[0].action, InputrcAction::Function("clear-screen".to_string()));
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inputrc {
+    /// The variables set with `set`, by name.
+    pub variables: HashMap<String, String>,
+    /// The key bindings, in the order of the file; a later binding of the same keys wins.
+    pub bindings: Vec<InputrcBinding>,
+}
+
+impl Inputrc {
+    /// Read the init file at `path`.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Inputrc> {
+        Ok(Inputrc::parse(&fs::read_to_string(path)?))
+    }
+
+    /// Parse the contents of an init file.
+    pub fn parse(text: &str) -> Inputrc {
+        let mut inputrc = Inputrc::default();
+        // Whether each enclosing conditional block is followed.
+        let mut conditions: Vec<bool> = Vec::new();
+
+        for line in text.lines() {
+            let line = line.trim();
+            if let Some(directive) = line.strip_prefix('$') {
+                let (name, arg) = directive.split_once(char::is_whitespace).unwrap_or((directive, ""));
+                match name {
+                    "if" => {
+                        let mode = arg.trim().strip_prefix("mode=");
+                        let current = inputrc.variable("editing-mode").unwrap_or("emacs");
+                        conditions.push(mode == Some(current));
+                    }
+                    "else" => {
+                        if let Some(condition) = conditions.last_mut() {
+                            *condition = !*condition;
+                        }
+                    }
+                    "endif" => {
+                        conditions.pop();
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            if line.is_empty() || line.starts_with('#') || conditions.contains(&false) {
+                continue;
+            }
+
+            if let Some(rest) = line.strip_prefix("set ") {
+                let mut words = rest.split_whitespace();
+                if let (Some(name), Some(value)) = (words.next(), words.next()) {
+                    inputrc.variables.insert(name.to_string(), value.to_string());
+                }
+            } else if let Some(binding) = parse_binding(line) {
+                inputrc.bindings.push(binding);
+            }
+        }
+        inputrc
+    }
+
+    /// The value of the variable `name`, if it was set.
+    pub fn variable(&self, name: &str) -> Option<&str> {
+        self.variables.get(name).map(String::as_str)
+    }
+}
+
+/// Parse a `keys: action` line.
+fn parse_binding(line: &str) -> Option<InputrcBinding> {
+    let (bytes, rest) = if let Some(quoted) = line.strip_prefix('"') {
+        let (seq, len) = unescape(quoted, '"')?;
+        (seq, quoted[len..].trim_start().strip_prefix(':')?)
+    } else {
+        let (name, rest) = line.split_once(':')?;
+        (key_name_bytes(name.trim())?, rest)
+    };
+
+    let rest = rest.trim();
+    let action = if let Some(quoted) = rest.strip_prefix(['"', '\'']) {
+        let (text, _) = unescape(quoted, rest.chars().next()?)?;
+        InputrcAction::Macro(String::from_utf8(text).ok()?)
+    } else {
+        InputrcAction::Function(rest.split_whitespace().next()?.to_string())
+    };
+
+    let keys = bytes
+        .as_slice()
+        .events()
+        .map(|event| match event {
+            Ok(Event::Key(key)) => Some(key),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    Some(InputrcBinding { keys, action })
+}
+
+/// The bytes of a key name binding such as `Control-u` or `Meta-Rubout`.
+fn key_name_bytes(name: &str) -> Option<Vec<u8>> {
+    let lower = name.to_ascii_lowercase();
+    let (meta, lower) = match lower.strip_prefix("meta-").or_else(|| lower.strip_prefix("m-")) {
+        Some(rest) => (true, rest.to_string()),
+        None => (false, lower),
+    };
+    let (control, lower) = match lower.strip_prefix("control-").or_else(|| lower.strip_prefix("c-")) {
+        Some(rest) => (true, rest),
+        None => (false, lower.as_str()),
+    };
+
+    let mut byte = match lower {
+        "rubout" | "del" => 0x7F,
+        "escape" | "esc" => 0x1B,
+        "lfd" | "newline" => b'\n',
+        "ret" | "return" => b'\r',
+        "spc" | "space" => b' ',
+        "tab" => b'\t',
+        _ => {
+            // A single character, in its original case.
+            let mut chars = name[name.len() - lower.len()..].chars();
+            let c = chars.next().filter(|c| c.is_ascii() && chars.next().is_none())?;
+            c as u8
+        }
+    };
+    if control {
+        byte &= 0x1F;
+    }
+    Some(if meta { vec![0x1B, byte] } else { vec![byte] })
+}
+
+/// Unescape a quoted string up to the closing `quote`, returning its bytes and the length read,
+/// including the closing quote.
+fn unescape(s: &str, quote: char) -> Option<(Vec<u8>, usize)> {
+    let bytes = s.as_bytes();
+    let mut out = Vec::new();
+    // Pending `\C-` and `\M-` prefixes.
+    let (mut control, mut meta) = (false, false);
+    let mut i = 0;
+    loop {
+        let mut byte = *bytes.get(i)?;
+        i += 1;
+        if byte == quote as u8 {
+            return Some((out, i));
+        }
+        if byte == b'\\' {
+            let escaped = *bytes.get(i)?;
+            i += 1;
+            byte = match escaped {
+                b'C' | b'M' if bytes.get(i) == Some(&b'-') => {
+                    i += 1;
+                    if escaped == b'C' { control = true } else { meta = true }
+                    continue;
+                }
+                b'e' => 0x1B,
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b'd' => 0x7F,
+                b'f' => 0x0C,
+                b'n' => b'\n',
+                b'r' => b'\r',
+                b't' => b'\t',
+                b'v' => 0x0B,
+                b'0'..=b'7' => {
+                    let digits = bytes[i - 1..].iter().take(3).take_while(|b| matches!(b, b'0'..=b'7')).count();
+                    i += digits - 1;
+                    u8::from_str_radix(&s[i - digits..i], 8).ok()?
+                }
+                b'x' => {
+                    let digits = bytes[i..].iter().take(2).take_while(|b| b.is_ascii_hexdigit()).count();
+                    i += digits;
+                    u8::from_str_radix(&s[i - digits..i], 16).ok()?
+                }
+                other => other,
+            };
+        }
+        if control {
+            byte = if byte == b'?' { 0x7F } else { byte & 0x1F };
+            control = false;
+        }
+        if meta {
+            out.push(0x1B);
+            meta = false;
+        }
+        out.push(byte);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::iter;
+
+    use super::*;
+    use crate::input::TermRead;
+
+    #[test]
+    fn test_capture_chord() {
+        let input: &[u8] = b"\x1B[1;5A\x1B[97;1:3ug\rx";
+        let mut events = input.events();
+        let chord = capture_chord(&mut events, Duration::ZERO).unwrap();
+        assert_eq!(chord, [
+            KeyEvent::new(KeyCode::Up, KeyModifiers::CONTROL),
+            KeyEvent::new(KeyCode::Char('g'), KeyModifiers::NONE),
+        ]);
+
+        // The input after Enter is left for the caller; the rest times out.
+        let chord = capture_chord(&mut events, Duration::ZERO).unwrap();
+        assert_eq!(chord, [KeyEvent::new(KeyCode::Char('x'), KeyModifiers::NONE)]);
+    }
+
+    #[test]
+    fn test_capture_chord_timeout() {
+        use crate::test::ManualClock;
+
+        let clock = ManualClock::new();
+        // Each poll moves the clock on, then finds a key or no input.
+        let mut polls = [(0, Some('a')), (999, None), (0, Some('b')), (1000, None)].into_iter();
+        let events = iter::from_fn(|| {
+            let (delay, key) = polls.next().expect("polled after the timeout");
+            clock.advance(Duration::from_millis(delay));
+            Some(Ok(Event::Key(KeyCode::Char(key?).into())))
+        });
+        let chord = capture_chord_with_clock(events, Duration::from_secs(1), &clock).unwrap();
+        assert_eq!(chord, [
+            KeyEvent::new(KeyCode::Char('a'), KeyModifiers::NONE),
+            KeyEvent::new(KeyCode::Char('b'), KeyModifiers::NONE),
+        ]);
+    }
+
+    #[test]
+    fn test_inputrc() {
+        let inputrc = Inputrc::parse(r#"
+            # Comments and unknown lines are skipped.
+            set bell-style none
+            $if mode=emacs
+            "\e[A": history-search-backward
+            Meta-Rubout: backward-kill-word
+            $else
+            "\C-w": unix-word-rubout
+            $endif
+            $if Bash
+            "\C-x": never
+            $endif
+            "\C-x\C-r": re-read-init-file
+            "\M-s": "sudo \"x\"\n"
+            Control-u: unix-line-discard
+            nonsense
+        "#);
+
+        assert_eq!(inputrc.variable("bell-style"), Some("none"));
+        let function = |name: &str| InputrcAction::Function(name.to_string());
+        let ctrl = |c| KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL);
+        let bindings: Vec<(Vec<KeyEvent>, InputrcAction)> =
+            inputrc.bindings.into_iter().map(|b| (b.keys, b.action)).collect();
+        assert_eq!(bindings, [
+            (vec![KeyCode::Up.into()], function("history-search-backward")),
+            (vec![KeyEvent::new(KeyCode::Backspace, KeyModifiers::ALT)], function("backward-kill-word")),
+            (vec![ctrl('x'), ctrl('r')], function("re-read-init-file")),
+            (vec![KeyEvent::new(KeyCode::Char('s'), KeyModifiers::ALT)], InputrcAction::Macro("sudo \"x\"\n".to_string())),
+            (vec![ctrl('u')], function("unix-line-discard")),
+        ]);
+
+        let vi = Inputrc::parse("set editing-mode vi\n$if mode=vi\n\"\\C-l\": clear-screen\n$endif\n");
+        assert_eq!(vi.bindings.len(), 1);
+    }
+}
@@ -0,0 +1,175 @@
+//! Keeping killed text to yank it back, as readline does.
+//!
+//! Text deleted with the kill commands (Ctrl+K, Ctrl+U, Ctrl+W, Alt+D, ...) goes into a
+//! `KillRing`. Ctrl+Y yanks the most recent kill back, and Alt+Y right after replaces the yanked
+//! text with the kill before it, going around the ring.
+//!
+//! There is no line editor in this crate to bind these keys; programs reading keys themselves
+//! call `KillRing` on them. `KillRing::shared` gives a ring for the whole process, so kills in
+//! one prompt can be yanked in the next, and so the program can seed or inspect it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::killring::KillRing;
+//!
+//! let mut ring = KillRing::new();
+//! ring.kill("first");
+//! ring.kill("second");
+//! assert_eq!(ring.yank(), Some("second"));
+//! assert_eq!(ring.rotate(), Some("first"));
+//! assert_eq!(ring.rotate(), Some("second"));
+//! ```
+
+use std::collections::VecDeque;
+use std::sync::{Mutex, MutexGuard};
+
+/// The number of kills kept by default, as in readline.
+const DEFAULT_CAPACITY: usize = 10;
+
+/// The ring shared by the whole process.
+static SHARED: Mutex<KillRing> = Mutex::new(KillRing::new());
+
+/// The text killed recently, most recent first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct KillRing {
+    entries: VecDeque<String>,
+    /// The most kills kept; older ones are dropped.
+    capacity: usize,
+    /// The entry yanked last, moved by `rotate`.
+    yanked: usize,
+}
+
+impl Default for KillRing {
+    fn default() -> KillRing {
+        KillRing::new()
+    }
+}
+
+impl KillRing {
+    /// An empty ring keeping the last 10 kills.
+    pub const fn new() -> KillRing {
+        KillRing::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// An empty ring keeping the last `capacity` kills, at least one.
+    pub const fn with_capacity(capacity: usize) -> KillRing {
+        let capacity = if capacity == 0 { 1 } else { capacity };
+        KillRing { entries: VecDeque::new(), capacity, yanked: 0 }
+    }
+
+    /// The ring shared by the whole process.
+    ///
+    /// Hold the guard only for as long as needed: other threads wait for it.
+    pub fn shared() -> MutexGuard<'static, KillRing> {
+        // A kill ring can't be left inconsistent by a panic, so poisoning is ignored.
+        SHARED.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Add killed `text` as the most recent kill.
+    ///
+    /// Empty text is ignored.
+    pub fn kill(&mut self, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        if self.entries.len() == self.capacity {
+            self.entries.pop_back();
+        }
+        self.entries.push_front(text.to_owned());
+        self.yanked = 0;
+    }
+
+    /// Add `text` to the end of the most recent kill, for consecutive forward kills.
+    pub fn append(&mut self, text: &str) {
+        match self.entries.front_mut() {
+            Some(last) => last.push_str(text),
+            None => self.kill(text),
+        }
+        self.yanked = 0;
+    }
+
+    /// Add `text` to the start of the most recent kill, for consecutive backward kills.
+    pub fn prepend(&mut self, text: &str) {
+        match self.entries.front_mut() {
+            Some(last) => last.insert_str(0, text),
+            None => self.kill(text),
+        }
+        self.yanked = 0;
+    }
+
+    /// The most recent kill, to insert it (Ctrl+Y).
+    pub fn yank(&mut self) -> Option<&str> {
+        self.yanked = 0;
+        self.entries.front().map(String::as_str)
+    }
+
+    /// The kill before the one yanked last, going around the ring, to replace the text just
+    /// yanked (Alt+Y).
+    ///
+    /// It is up to the caller to only do this right after a yank or rotation.
+    pub fn rotate(&mut self) -> Option<&str> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        self.yanked = (self.yanked + 1) % self.entries.len();
+        self.entries.get(self.yanked).map(String::as_str)
+    }
+
+    /// The kills, most recent first.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.entries.iter().map(String::as_str)
+    }
+
+    /// The number of kills kept.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Whether nothing was killed.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Forget every kill.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.yanked = 0;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_kill_ring() {
+        let mut ring = KillRing::with_capacity(2);
+        assert_eq!(ring.yank(), None);
+        assert_eq!(ring.rotate(), None);
+
+        ring.kill("a");
+        ring.kill("");
+        ring.append("b");
+        ring.prepend("c");
+        ring.kill("d");
+        ring.kill("e");
+        // "cab" was dropped for "e".
+        assert_eq!(ring.iter().collect::<Vec<_>>(), ["e", "d"]);
+
+        assert_eq!(ring.yank(), Some("e"));
+        assert_eq!(ring.rotate(), Some("d"));
+        assert_eq!(ring.rotate(), Some("e"));
+        // A new kill starts over from itself.
+        ring.rotate();
+        ring.kill("f");
+        assert_eq!(ring.rotate(), Some("e"));
+        assert_eq!(ring.yank(), Some("f"));
+    }
+
+    #[test]
+    fn test_shared() {
+        KillRing::shared().kill("shared");
+        assert!(KillRing::shared().iter().any(|kill| kill == "shared"));
+    }
+}
@@ -1,3 +1,7 @@
+// Input is deliberately read one byte at a time: buffering the TTY would swallow bytes that
+// belong to the next event.
+#![allow(clippy::unbuffered_bytes)]
+
 #[macro_use]
 mod macros;
 
@@ -5,13 +9,37 @@ mod macros;
 #[path = "sys/unix/mod.rs"]
 mod sys;
 
-mod types;
 pub mod input;
 pub mod event;
+pub mod batch;
+pub mod bell;
 pub mod raw;
 pub mod clear;
+pub mod clock;
 pub mod cursor;
+pub mod diagnostics;
+pub mod scroll;
+pub mod selection;
+mod sequence;
+pub mod shell;
+pub mod color;
+pub mod idle;
+pub mod keymap;
+pub mod killring;
+pub mod metrics;
+pub mod passthrough;
+pub mod password;
+pub mod profile;
+pub mod query;
+pub mod style;
+pub mod test;
+pub mod text;
 mod r#async;
+mod guard;
+mod ring;
 
-pub use r#async::{AsyncReader, async_stdin};
+pub use r#async::{AsyncReader, async_stdin, async_stdin_with_resize};
+pub use sequence::Sequence;
+pub use sys::size::{terminal_size, terminal_size_of, terminal_size_pixels};
+pub use sys::tty::{get_tty, is_tty};
 
@@ -10,17 +10,25 @@ macro_rules! derive_csi_sequence {
         #[derive(Copy, Clone)]
         pub struct $name;
 
+        impl $name {
+            /// The sequence as bytes.
+            pub const BYTES: &'static [u8] = csi!($value).as_bytes();
+        }
+
         impl fmt::Display for $name {
+            #[inline]
             fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-                write!(f, csi!($value))
+                f.write_str(csi!($value))
             }
         }
 
         impl AsRef<[u8]> for $name {
+            #[inline]
             fn as_ref(&self) -> &'static [u8] { csi!($value).as_bytes() }
         }
 
         impl AsRef<str> for $name {
+            #[inline]
             fn as_ref(&self) -> &'static str { csi!($value) }
         }
     };
@@ -0,0 +1,146 @@
+//! Input health metrics.
+//!
+//! Install a `Metrics` implementation once at startup to be told about the input this crate
+//! reads, e.g. to export it to a telemetry system. Nothing is measured until one is installed.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::event::Event;
+//! use pres::metrics::{self, Metrics};
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//!
+//! #[derive(Default)]
+//! struct Counters {
+//!     events: AtomicUsize,
+//!     errors: AtomicUsize,
+//! }
+//!
+//! impl Metrics for Counters {
+//!     fn event_parsed(&self, _event: &Event) {
+//!         self.events.fetch_add(1, Ordering::Relaxed);
+//!     }
+//!
+//!     fn parse_error(&self, _raw: &[u8]) {
+//!         self.errors.fetch_add(1, Ordering::Relaxed);
+//!     }
+//! }
+//!
+//! metrics::install(Box::new(Counters::default())).ok();
+//! ```
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+use crate::event::Event;
+
+/// Receiver of input metrics.
+///
+/// Every method does nothing by default, so implementations only override what they collect.
+/// Methods may be called from several threads and should be cheap, as they run on the input path.
+pub trait Metrics: Send + Sync {
+    /// `count` bytes of input were consumed by the event parser.
+    fn bytes_read(&self, _count: usize) {}
+
+    /// An event was parsed. Unparseable input is reported as `Event::Unsupported`, after
+    /// `parse_error`.
+    fn event_parsed(&self, _event: &Event) {}
+
+    /// `raw` could not be parsed as an event.
+    fn parse_error(&self, _raw: &[u8]) {}
+
+    /// `count` bytes after a malformed sequence were skipped, see `ParseOptions::resync`. They
+    /// are part of the `raw` bytes given to `parse_error` just before.
+    fn bytes_skipped(&self, _count: usize) {}
+
+    /// `depth` bytes are still waiting in the `AsyncReader` queue after a read.
+    fn queue_depth(&self, _depth: usize) {}
+
+    /// `gap` passed between the previous event and this one, across all event iterators.
+    fn event_gap(&self, _gap: Duration) {}
+}
+
+static METRICS: OnceLock<Box<dyn Metrics>> = OnceLock::new();
+
+/// Install the process-wide metrics receiver.
+///
+/// This can only be done once; later calls give `metrics` back.
+pub fn install(metrics: Box<dyn Metrics>) -> Result<(), Box<dyn Metrics>> {
+    METRICS.set(metrics)
+}
+
+/// The installed metrics receiver, if any.
+pub(crate) fn get() -> Option<&'static dyn Metrics> {
+    METRICS.get().map(|metrics| &**metrics)
+}
+
+/// Time of the last event plus one, in nanoseconds since `epoch()`; zero before the first event.
+static LAST_EVENT: AtomicU64 = AtomicU64::new(0);
+
+/// The reference point of `LAST_EVENT`.
+fn epoch() -> Instant {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    *EPOCH.get_or_init(Instant::now)
+}
+
+/// Report a parsed event made of `bytes` input bytes.
+pub(crate) fn record_event(metrics: &dyn Metrics, event: &Event, bytes: usize) {
+    metrics.bytes_read(bytes);
+    metrics.event_parsed(event);
+
+    let now = epoch().elapsed().as_nanos() as u64 + 1;
+    let last = LAST_EVENT.swap(now, Ordering::Relaxed);
+    if last != 0 {
+        metrics.event_gap(Duration::from_nanos(now.saturating_sub(last)));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::io;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::event::Key;
+    use crate::input::TermRead;
+
+    struct Recorder {
+        bytes: AtomicUsize,
+        gaps: AtomicUsize,
+        errors: Mutex<Vec<Vec<u8>>>,
+    }
+
+    static RECORDER: Recorder = Recorder {
+        bytes: AtomicUsize::new(0),
+        gaps: AtomicUsize::new(0),
+        errors: Mutex::new(Vec::new()),
+    };
+
+    impl Metrics for &'static Recorder {
+        fn bytes_read(&self, count: usize) {
+            self.bytes.fetch_add(count, Ordering::Relaxed);
+        }
+
+        fn parse_error(&self, raw: &[u8]) {
+            self.errors.lock().unwrap().push(raw.to_vec());
+        }
+
+        fn event_gap(&self, _gap: Duration) {
+            self.gaps.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn test_metrics() {
+        assert!(install(Box::new(&RECORDER)).is_ok());
+
+        // Other tests may parse events concurrently, so only lower bounds are checked.
+        let events: Vec<_> = io::Cursor::new(b"a\x1B[Ab\x1B[\x00".to_vec()).events().collect::<Result<_, _>>().unwrap();
+        assert_eq!(events[..3], [Event::Key(Key::Char('a').into()), Event::Key(Key::Up.into()), Event::Key(Key::Char('b').into())]);
+        assert!(RECORDER.bytes.load(Ordering::Relaxed) >= 8);
+        assert!(RECORDER.gaps.load(Ordering::Relaxed) >= 3);
+        assert!(RECORDER.errors.lock().unwrap().contains(&b"\x1B[\x00".to_vec()));
+    }
+}
@@ -0,0 +1,113 @@
+//! Sending escape sequences through tmux and GNU screen.
+//!
+//! A terminal multiplexer interprets the output of the programs it runs and draws its own screen
+//! on the real terminal, so sequences it does not know about, such as a clipboard write (OSC 52)
+//! or an image, never reach the terminal. Wrapped in a DCS passthrough string, they are handed to
+//! it as they are. tmux 3.3 and later only does so with `set -g allow-passthrough on`.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::cursor::SteadyBar;
+//! use pres::passthrough::{Multiplexer, Passthrough};
+//!
+//! let wrapped = Passthrough::for_multiplexer(SteadyBar, Some(Multiplexer::Tmux)).to_string();
+//! assert_eq!(wrapped, "\x1BPtmux;\x1B\x1B[6 q\x1B\\");
+//!
+//! // Outside a multiplexer, the sequence is left alone.
+//! print!("{}", Passthrough::new(SteadyBar));
+//! ```
+
+use std::env;
+use std::fmt;
+
+/// The longest string GNU screen passes through at once; longer ones are split.
+const SCREEN_CHUNK: usize = 768;
+
+/// A terminal multiplexer.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Multiplexer {
+    /// tmux.
+    Tmux,
+    /// GNU screen.
+    Screen,
+}
+
+impl Multiplexer {
+    /// The multiplexer this program runs in, from the `TMUX` and `STY` environment variables.
+    ///
+    /// This looks the variables up on every call; keep the result to wrap many sequences.
+    pub fn detect() -> Option<Multiplexer> {
+        if env::var_os("TMUX").is_some_and(|v| !v.is_empty()) {
+            Some(Multiplexer::Tmux)
+        } else if env::var_os("STY").is_some_and(|v| !v.is_empty()) {
+            Some(Multiplexer::Screen)
+        } else {
+            None
+        }
+    }
+}
+
+/// A sequence, written so that it reaches the terminal from inside a multiplexer.
+///
+/// tmux gets the whole sequence in one passthrough string, with its escape characters doubled.
+/// GNU screen gets it in passthrough strings of up to 768 bytes; since screen ends them at the
+/// first string terminator, OSC sequences must end with BEL there rather than ST.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Passthrough<T> {
+    inner: T,
+    multiplexer: Option<Multiplexer>,
+}
+
+impl<T: fmt::Display> Passthrough<T> {
+    /// Wrap `inner` for the multiplexer this program runs in, if any.
+    pub fn new(inner: T) -> Passthrough<T> {
+        Passthrough::for_multiplexer(inner, Multiplexer::detect())
+    }
+
+    /// Wrap `inner` for `multiplexer`, writing it as it is for `None`.
+    pub fn for_multiplexer(inner: T, multiplexer: Option<Multiplexer>) -> Passthrough<T> {
+        Passthrough { inner, multiplexer }
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Passthrough<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.multiplexer {
+            None => write!(f, "{}", self.inner),
+            Some(Multiplexer::Tmux) => {
+                let seq = self.inner.to_string();
+                write!(f, "\x1BPtmux;{}\x1B\\", seq.replace('\x1B', "\x1B\x1B"))
+            }
+            Some(Multiplexer::Screen) => {
+                let seq = self.inner.to_string();
+                let mut rest = seq.as_str();
+                while !rest.is_empty() {
+                    let mut end = rest.len().min(SCREEN_CHUNK);
+                    while !rest.is_char_boundary(end) {
+                        end -= 1;
+                    }
+                    write!(f, "\x1BP{}\x1B\\", &rest[..end])?;
+                    rest = &rest[end..];
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_screen_chunks() {
+        let seq = format!("\x1B]52;c;{}\x07", "a".repeat(1000));
+        let wrapped = Passthrough::for_multiplexer(&seq, Some(Multiplexer::Screen)).to_string();
+        let expected = format!("\x1BP{}\x1B\\\x1BP{}\x1B\\", &seq[..768], &seq[768..]);
+        assert_eq!(wrapped, expected);
+
+        assert_eq!(Passthrough::for_multiplexer("", Some(Multiplexer::Screen)).to_string(), "");
+        assert_eq!(Passthrough::for_multiplexer("\x1B[H", None).to_string(), "\x1B[H");
+    }
+}
@@ -0,0 +1,55 @@
+//! Prompting for passwords.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! match pres::password::prompt("Password: ").unwrap() {
+//!     Some(password) => println!("Got {} characters.", password.len()),
+//!     None => println!("Aborted."),
+//! }
+//! ```
+
+use std::fs;
+use std::io::{self, Write};
+use std::os::unix::io::AsRawFd;
+
+use crate::input::TermRead;
+use crate::sys::attr::{get_terminal_attr_of, no_echo_terminal_attr, no_signal_terminal_attr, set_terminal_attr_of};
+use crate::sys::tty::get_tty;
+use crate::sys::Termios;
+
+/// Restores the attributes of the TTY when dropped.
+struct EchoRestorer<'a> {
+    tty: &'a fs::File,
+    prev_ios: Termios,
+}
+
+impl<'a> Drop for EchoRestorer<'a> {
+    fn drop(&mut self) {
+        let _ = set_terminal_attr_of(self.tty.as_raw_fd(), &self.prev_ios);
+    }
+}
+
+/// Write `prompt` to the TTY and read a password from it.
+///
+/// Unlike `TermRead::read_passwd`, this does not need raw mode nor access to stdin and stdout:
+/// the TTY device is opened directly, only echoing is turned off while reading, and the previous
+/// settings are restored afterwards, even if reading fails. Line editing by the terminal driver
+/// (backspace, kill line) keeps working.
+///
+/// EOT and ETX will abort the prompt, returning `None`. Signal keys are off while reading, so
+/// Ctrl+C aborts the prompt rather than killing the process with the echo still off.
+pub fn prompt(prompt: &str) -> io::Result<Option<String>> {
+    let mut tty = get_tty()?;
+    write!(tty, "{}", prompt)?;
+    tty.flush()?;
+
+    let prev_ios = get_terminal_attr_of(tty.as_raw_fd())?;
+    let mut ios = prev_ios;
+    no_echo_terminal_attr(&mut ios);
+    no_signal_terminal_attr(&mut ios);
+    set_terminal_attr_of(tty.as_raw_fd(), &ios)?;
+    let _restorer = EchoRestorer { tty: &tty, prev_ios };
+
+    (&tty).read_line()
+}
@@ -0,0 +1,344 @@
+//! Restricting output to what older terminals understand.
+//!
+//! Serial consoles and embedded targets often speak nothing beyond VT100 or VT220: colors, DEC
+//! private modes such as mouse reporting and operating system commands at best do nothing and
+//! at worst are shown as garbage. A `Profile` tells which escape sequences a terminal handles.
+//!
+//! At run time, a `ProfileWriter` drops every sequence outside its profile and reports it. At
+//! compile time, `Profile::allows` checks constant sequences, such as the `BYTES` of the unit
+//! sequence types.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::profile::{Profile, ProfileWriter};
+//! use std::io::Write;
+//!
+//! // Fails to build if hiding the cursor is not VT220.
+//! const _: () = assert!(Profile::Vt220.allows(pres::cursor::Hide::BYTES));
+//!
+//! let mut rejected = Vec::new();
+//! let mut out = ProfileWriter::new(Vec::new(), Profile::Vt100, |seq: &[u8]| rejected.push(seq.to_vec()));
+//! write!(out, "\x1B[1mbold\x1B[38;2;255;0;0mred\x1B[0m").unwrap();
+//! assert_eq!(out.into_inner(), b"\x1B[1mboldred\x1B[0m");
+//! assert_eq!(rejected, [b"\x1B[38;2;255;0;0m".to_vec()]);
+//! ```
+
+use std::io::{self, Write};
+use std::ops;
+
+/// The SGR parameters of the VT100: reset, bold, underline, blink and inverse.
+const VT100_SGR: &[u16] = &[0, 1, 4, 5, 7];
+/// The SGR parameters of the VT220, which can also turn each attribute off.
+const VT220_SGR: &[u16] = &[0, 1, 4, 5, 7, 22, 24, 25, 27];
+/// The DEC private modes of the VT100.
+const VT100_MODES: &[u16] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 18, 19];
+/// The DEC private modes of the VT220, adding cursor visibility.
+const VT220_MODES: &[u16] = &[1, 2, 3, 4, 5, 6, 7, 8, 9, 18, 19, 25];
+
+/// A set of escape sequences a terminal understands.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum Profile {
+    /// The DEC VT100: cursor movement, erasing, scrolling regions and bold, underline, blink and
+    /// inverse text, with no colors.
+    Vt100,
+    /// The DEC VT220, adding to the VT100 cursor hiding, character and line insertion and
+    /// deletion, and turning individual attributes off.
+    Vt220,
+    /// Any sequence.
+    #[default]
+    Unrestricted,
+}
+
+impl Profile {
+    /// Whether every escape sequence in `bytes` is part of this profile.
+    ///
+    /// Text between the sequences is ignored; an unterminated or broken sequence is not allowed.
+    pub const fn allows(self, bytes: &[u8]) -> bool {
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != 0x1B {
+                i += 1;
+                continue;
+            }
+            let end = match sequence_end(bytes, i) {
+                SequenceEnd::Complete(end) => end,
+                SequenceEnd::Broken(_) | SequenceEnd::Incomplete => return false,
+            };
+            if !self.allows_sequence(bytes, i, end) {
+                return false;
+            }
+            i = end;
+        }
+        true
+    }
+
+    /// Whether the single sequence `bytes[start..end]` is part of this profile.
+    const fn allows_sequence(self, bytes: &[u8], start: usize, end: usize) -> bool {
+        let vt220 = match self {
+            Profile::Unrestricted => return true,
+            Profile::Vt220 => true,
+            Profile::Vt100 => false,
+        };
+
+        if bytes[start + 1] != b'[' {
+            // `ESC final`, or a character set designation `ESC ( final`.
+            return match (end - start, bytes[start + 1]) {
+                (2, b'7' | b'8' | b'D' | b'E' | b'M' | b'H' | b'c' | b'=' | b'>') => true,
+                (3, b'(' | b')') => matches!(bytes[start + 2], b'A' | b'B' | b'0' | b'1' | b'2'),
+                _ => false,
+            };
+        }
+
+        let final_byte = bytes[end - 1];
+        let mut params = start + 2;
+        let prefix = match bytes[params] {
+            c @ (b'<' | b'=' | b'>' | b'?') => {
+                params += 1;
+                c
+            }
+            _ => 0,
+        };
+        // Intermediate bytes sit right before the final byte.
+        let mut intermediates = end - 1;
+        while intermediates > params && matches!(bytes[intermediates - 1], 0x20..=0x2F) {
+            intermediates -= 1;
+        }
+        let intermediate = match end - 1 - intermediates {
+            0 => 0,
+            1 => bytes[intermediates],
+            _ => return false,
+        };
+
+        match (prefix, intermediate, final_byte) {
+            (0, 0, b'A' | b'B' | b'C' | b'D' | b'H' | b'f' | b'r' | b'g' | b'c' | b'n' | b'x' | b'q') => true,
+            (0, 0, b'J' | b'K') => all_params(bytes, params, intermediates, &[0, 1, 2]),
+            (0, 0, b'm') => all_params(bytes, params, intermediates, if vt220 { VT220_SGR } else { VT100_SGR }),
+            (0, 0, b'h' | b'l') => all_params(bytes, params, intermediates, if vt220 { &[4, 20] } else { &[20] }),
+            (b'?', 0, b'h' | b'l') => {
+                all_params(bytes, params, intermediates, if vt220 { VT220_MODES } else { VT100_MODES })
+            }
+            (0, 0, b'@' | b'L' | b'M' | b'P' | b'X') => vt220,
+            (b'?', 0, b'J' | b'K' | b'n') | (b'>', 0, b'c') | (0, b'!', b'p') | (0, b'"', b'p' | b'q') => vt220,
+            _ => false,
+        }
+    }
+}
+
+/// Where an escape sequence ends.
+enum SequenceEnd {
+    /// The sequence is complete and ends before the given index.
+    Complete(usize),
+    /// The sequence was cut by a byte that cannot be part of it, at the given index.
+    Broken(usize),
+    /// The sequence goes on past the end of the bytes.
+    Incomplete,
+}
+
+/// Where the escape sequence starting at `bytes[start]` ends.
+const fn sequence_end(bytes: &[u8], start: usize) -> SequenceEnd {
+    let mut i = start + 1;
+    if i >= bytes.len() {
+        return SequenceEnd::Incomplete;
+    }
+    match bytes[i] {
+        b'[' => {
+            i += 1;
+            while i < bytes.len() && matches!(bytes[i], 0x20..=0x3F) {
+                i += 1;
+            }
+            if i == bytes.len() {
+                SequenceEnd::Incomplete
+            } else if matches!(bytes[i], 0x40..=0x7E) {
+                SequenceEnd::Complete(i + 1)
+            } else {
+                SequenceEnd::Broken(i)
+            }
+        }
+        // String sequences, ended by BEL or ST.
+        b']' | b'P' | b'_' | b'^' | b'X' => {
+            while i + 1 < bytes.len() {
+                i += 1;
+                if bytes[i] == 0x07 {
+                    return SequenceEnd::Complete(i + 1);
+                }
+                if bytes[i] == 0x1B && i + 1 < bytes.len() && bytes[i + 1] == b'\\' {
+                    return SequenceEnd::Complete(i + 2);
+                }
+            }
+            SequenceEnd::Incomplete
+        }
+        _ => {
+            while i < bytes.len() && matches!(bytes[i], 0x20..=0x2F) {
+                i += 1;
+            }
+            if i < bytes.len() { SequenceEnd::Complete(i + 1) } else { SequenceEnd::Incomplete }
+        }
+    }
+}
+
+/// Whether every parameter in `bytes[start..end]` is in `allowed`, empty parameters counting as 0.
+const fn all_params(bytes: &[u8], start: usize, end: usize, allowed: &[u16]) -> bool {
+    let mut i = start;
+    while i <= end {
+        let mut n: u32 = 0;
+        while i < end && bytes[i] != b';' {
+            if !bytes[i].is_ascii_digit() {
+                return false;
+            }
+            n = n * 10 + (bytes[i] - b'0') as u32;
+            if n > u16::MAX as u32 {
+                return false;
+            }
+            i += 1;
+        }
+        let mut j = 0;
+        while j < allowed.len() && allowed[j] as u32 != n {
+            j += 1;
+        }
+        if j == allowed.len() {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
+
+/// A writer dropping the escape sequences outside a profile.
+///
+/// A sequence split across writes is held back until it is complete. A control sequence cut by a
+/// byte that cannot be part of it is rejected up to that byte, which is then written as text.
+pub struct ProfileWriter<W: Write, F: FnMut(&[u8])> {
+    inner: W,
+    profile: Profile,
+    on_rejected: F,
+    /// The start of a sequence, not complete yet.
+    pending: Vec<u8>,
+}
+
+impl<W: Write, F: FnMut(&[u8])> ProfileWriter<W, F> {
+    /// Wrap `inner`, calling `on_rejected` with every sequence outside `profile` instead of
+    /// writing it.
+    pub fn new(inner: W, profile: Profile, on_rejected: F) -> ProfileWriter<W, F> {
+        ProfileWriter { inner, profile, on_rejected, pending: Vec::new() }
+    }
+
+    /// Get the wrapped writer back, dropping an incomplete sequence.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write, F: FnMut(&[u8])> ops::Deref for ProfileWriter<W, F> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.inner
+    }
+}
+
+impl<W: Write, F: FnMut(&[u8])> ops::DerefMut for ProfileWriter<W, F> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+}
+
+impl<W: Write, F: FnMut(&[u8])> Write for ProfileWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let bytes = std::mem::take(&mut self.pending);
+
+        let mut out = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            if bytes[i] != 0x1B {
+                out.push(bytes[i]);
+                i += 1;
+                continue;
+            }
+            let end = match sequence_end(&bytes, i) {
+                SequenceEnd::Complete(end) => end,
+                SequenceEnd::Broken(end) => {
+                    (self.on_rejected)(&bytes[i..end]);
+                    i = end;
+                    continue;
+                }
+                SequenceEnd::Incomplete => {
+                    self.pending = bytes[i..].to_vec();
+                    break;
+                }
+            };
+            if self.profile.allows_sequence(&bytes, i, end) {
+                out.extend_from_slice(&bytes[i..end]);
+            } else {
+                (self.on_rejected)(&bytes[i..end]);
+            }
+            i = end;
+        }
+
+        self.inner.write_all(&out)?;
+        Ok(buf.len())
+    }
+
+    /// Flush the wrapped writer, rejecting a sequence that is still incomplete: whatever follows
+    /// it is no longer held back.
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.pending.is_empty() {
+            (self.on_rejected)(&std::mem::take(&mut self.pending));
+        }
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_allows() {
+        let cases: &[(&[u8], bool, bool)] = &[
+            (b"\x1B[H\x1B[2J\x1B[10;20H\x1B[K", true, true),
+            (b"\x1B[0;1;4;5;7m\x1B[m", true, true),
+            (b"\x1B[22;27m", false, true),
+            (b"\x1B[31m", false, false),
+            (b"\x1B[38;2;1;2;3m", false, false),
+            (b"\x1B[?25l", false, true),
+            (b"\x1B[?1;7h\x1B[?6l", true, true),
+            (b"\x1B[?1000h", false, false),
+            (b"\x1B[3;20r\x1B7\x1B8\x1B(0", true, true),
+            (b"\x1B[2@\x1B[3P", false, true),
+            (b"\x1B[>1u", false, false),
+            (b"\x1B[2 q", false, false),
+            (b"\x1B]0;title\x07", false, false),
+            (b"\x1B[", false, false),
+            (b"\x1B[1\nA", false, false),
+        ];
+        for &(bytes, vt100, vt220) in cases {
+            assert_eq!(Profile::Vt100.allows(bytes), vt100, "{:?}", bytes);
+            assert_eq!(Profile::Vt220.allows(bytes), vt220, "{:?}", bytes);
+            assert!(Profile::Unrestricted.allows(bytes) || bytes == b"\x1B[" || bytes == b"\x1B[1\nA");
+        }
+    }
+
+    #[test]
+    fn test_profile_writer() {
+        let mut rejected = Vec::new();
+        let mut out = ProfileWriter::new(Vec::new(), Profile::Vt220, |seq: &[u8]| rejected.push(seq.to_vec()));
+        out.write_all(b"a\x1B[?25l\x1B[?10").unwrap();
+        out.write_all(b"00hb\x1B]0;x\x1B").unwrap();
+        out.write_all(b"\\c").unwrap();
+        assert_eq!(out.into_inner(), b"a\x1B[?25lbc");
+        assert_eq!(rejected, [b"\x1B[?1000h".to_vec(), b"\x1B]0;x\x1B\\".to_vec()]);
+    }
+
+    #[test]
+    fn test_profile_writer_broken_sequences() {
+        let mut rejected = Vec::new();
+        let mut out = ProfileWriter::new(Vec::new(), Profile::Vt220, |seq: &[u8]| rejected.push(seq.to_vec()));
+        out.write_all(b"\x1B[\na\x1B[1;\x1B[Kb\x1B[1").unwrap();
+        out.flush().unwrap();
+        out.write_all(b"mc").unwrap();
+        assert_eq!(out.into_inner(), b"\na\x1B[Kbmc");
+        assert_eq!(rejected, [b"\x1B[".to_vec(), b"\x1B[1;".to_vec(), b"\x1B[1".to_vec()]);
+    }
+}
@@ -0,0 +1,186 @@
+//! Querying the terminal without losing key presses.
+//!
+//! Terminals answer queries such as DSR (`ESC [ 6 n`) or OSC 11 on their input, interleaved with
+//! whatever the user is typing. Reading the answer directly, as `cursor::DetectCursorPos` does,
+//! swallows the keys pressed at the same time. A `QueryManager` instead reads the input as events,
+//! sets the answers to its pending queries aside and passes everything else through.
+//!
+//! # Example
+//!
+//! ```rust,no_run
+//! use pres::query::{Query, QueryManager};
+//! use pres::raw::IntoRawMode;
+//! use std::io::{stdin, stdout};
+//!
+//! let mut stdout = stdout().into_raw_mode().unwrap();
+//! let mut input = QueryManager::new(stdin());
+//! let cursor = input.send(&mut stdout, &Query::cursor_position()).unwrap();
+//!
+//! while let Some(event) = input.next() {
+//!     println!("{:?}\r", event.unwrap());
+//!     if let Some(answer) = input.answer(cursor) {
+//!         println!("cursor report: {:?}\r", answer);
+//!     }
+//! }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, Read, Write};
+
+use crate::event::{Event, EventsAndRaw};
+use crate::input::TermReadEventsAndRaw;
+
+/// A query to the terminal, and how to recognize its answer.
+#[derive(Debug, Clone)]
+pub struct Query {
+    request: Vec<u8>,
+    is_answer: fn(&[u8]) -> bool,
+}
+
+/// Whether `raw` is `prefix`, digits and semicolons, then `last`.
+fn is_csi_report(raw: &[u8], prefix: &[u8], last: u8) -> bool {
+    match raw.strip_prefix(prefix).and_then(|rest| rest.split_last()) {
+        Some((&end, params)) => end == last && params.iter().all(|&b| b.is_ascii_digit() || b == b';'),
+        None => false,
+    }
+}
+
+impl Query {
+    /// Send `request`, and take the events whose raw bytes satisfy `is_answer` as its answer.
+    pub fn new(request: impl Into<Vec<u8>>, is_answer: fn(&[u8]) -> bool) -> Query {
+        Query { request: request.into(), is_answer }
+    }
+
+    /// Ask for the cursor position (DSR 6), answered by `ESC [ row ; col R`.
+    pub fn cursor_position() -> Query {
+        Query::new(&b"\x1B[6n"[..], |raw| is_csi_report(raw, b"\x1B[", b'R'))
+    }
+
+    /// Ask for the primary device attributes (DA1), answered by `ESC [ ? params c`.
+    pub fn device_attributes() -> Query {
+        Query::new(&b"\x1B[c"[..], |raw| is_csi_report(raw, b"\x1B[?", b'c'))
+    }
+
+    /// Ask for the background color (OSC 11), answered by `ESC ] 11 ; color ST`.
+    pub fn background_color() -> Query {
+        Query::new(&b"\x1B]11;?\x1B\\"[..], |raw| raw.starts_with(b"\x1B]11;"))
+    }
+}
+
+/// Identifies a query sent by a `QueryManager`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct QueryId(u64);
+
+/// An iterator over input events that sets the answers to queries aside.
+///
+/// While an OSC query is pending, OSC sequences are parsed as such, so Alt+] waits for a
+/// terminator as with `event::ParseOptions::shell_integration`.
+pub struct QueryManager<R> {
+    events: EventsAndRaw<R>,
+    /// The queries not answered yet, oldest first.
+    pending: VecDeque<(QueryId, Query)>,
+    answers: HashMap<QueryId, Vec<u8>>,
+    next_id: u64,
+    /// Whether the user asked for OSC sequences to be parsed.
+    shell_integration: bool,
+}
+
+impl<R: Read> QueryManager<R> {
+    /// Read events from `input`.
+    pub fn new(input: R) -> QueryManager<R> {
+        QueryManager::from_events(input.events_and_raw())
+    }
+}
+
+impl<R> QueryManager<R> {
+    /// Read the events of `events`, keeping its parse options.
+    pub fn from_events(events: EventsAndRaw<R>) -> QueryManager<R> {
+        let shell_integration = events.options.shell_integration;
+        QueryManager { events, pending: VecDeque::new(), answers: HashMap::new(), next_id: 0, shell_integration }
+    }
+
+    /// Write `query` to `out` and start waiting for its answer.
+    pub fn send<W: Write>(&mut self, out: &mut W, query: &Query) -> io::Result<QueryId> {
+        out.write_all(&query.request)?;
+        out.flush()?;
+        let id = QueryId(self.next_id);
+        self.next_id += 1;
+        self.pending.push_back((id, query.clone()));
+        self.update_options();
+        Ok(id)
+    }
+
+    /// Take the raw bytes of the answer to `id`, if it arrived.
+    pub fn answer(&mut self, id: QueryId) -> Option<Vec<u8>> {
+        self.answers.remove(&id)
+    }
+
+    /// Whether `id` is still waiting for its answer.
+    pub fn is_pending(&self, id: QueryId) -> bool {
+        self.pending.iter().any(|(pending, _)| *pending == id)
+    }
+
+    /// Stop waiting for the answer to `id`, e.g. after a timeout with a terminal that does not
+    /// support the query. A late answer is then passed through as an ordinary event.
+    pub fn cancel(&mut self, id: QueryId) {
+        self.pending.retain(|(pending, _)| *pending != id);
+        self.answers.remove(&id);
+        self.update_options();
+    }
+
+    /// Parse OSC sequences while an OSC query is pending.
+    fn update_options(&mut self) {
+        let osc_pending = self.pending.iter().any(|(_, query)| query.request.starts_with(b"\x1B]"));
+        self.events.options.shell_integration = self.shell_integration || osc_pending;
+    }
+}
+
+impl<R: Read> Iterator for QueryManager<R> {
+    type Item = io::Result<Event>;
+
+    fn next(&mut self) -> Option<io::Result<Event>> {
+        loop {
+            let (event, raw) = match self.events.next()? {
+                Ok(next) => next,
+                Err(e) => return Some(Err(e)),
+            };
+            // Answers arrive in order, but a terminal may ignore some queries.
+            match self.pending.iter().position(|(_, query)| (query.is_answer)(&raw)) {
+                Some(index) => {
+                    let (id, _) = self.pending.remove(index).unwrap();
+                    self.answers.insert(id, raw.to_vec());
+                    self.update_options();
+                }
+                None => return Some(Ok(event)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::event::Key;
+
+    #[test]
+    fn test_query_manager() {
+        // `ESC [ 1 ; 5 R` would otherwise be Ctrl+F3.
+        let input: &[u8] = b"a\x1B[1;5Rb\x1B]11;rgb:0000/0000/0000\x1B\\\x1B[A";
+        let mut manager = QueryManager::new(input);
+        let mut out = Vec::new();
+        let cursor = manager.send(&mut out, &Query::cursor_position()).unwrap();
+        let background = manager.send(&mut out, &Query::background_color()).unwrap();
+        let attributes = manager.send(&mut out, &Query::device_attributes()).unwrap();
+        assert_eq!(out, b"\x1B[6n\x1B]11;?\x1B\\\x1B[c");
+
+        let events: Vec<Event> = manager.by_ref().map(Result::unwrap).collect();
+        assert_eq!(events, [Event::Key(Key::Char('a').into()), Event::Key(Key::Char('b').into()),
+                            Event::Key(Key::Up.into())]);
+        assert_eq!(manager.answer(cursor).unwrap(), b"\x1B[1;5R");
+        assert_eq!(manager.answer(background).unwrap(), b"\x1B]11;rgb:0000/0000/0000\x1B\\");
+        assert!(manager.answer(cursor).is_none());
+        assert!(manager.is_pending(attributes));
+        manager.cancel(attributes);
+        assert!(!manager.is_pending(attributes));
+    }
+}
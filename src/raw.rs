@@ -24,8 +24,10 @@
 
 use std::io::{self, Write};
 use std::ops;
+use std::os::unix::io::{AsRawFd, RawFd};
 
-use crate::sys::attr::{get_terminal_attr, raw_terminal_attr, set_terminal_attr};
+use crate::sys::attr::{get_terminal_attr_of, raw_terminal_attr, read_timing_terminal_attr, set_terminal_attr_of};
+use crate::batch;
 use crate::sys::Termios;
 
 /// The timeout of an escape code control sequence, in milliseconds.
@@ -35,14 +37,25 @@ pub const CONTROL_SEQUENCE_TIMEOUT: u64 = 100;
 /// dropped.
 ///
 /// Restoring will entirely bring back the old TTY state.
+///
+/// In batch mode (see `batch`) the terminal is left alone and this only wraps the output.
+///
+/// Dropping it restores the terminal on a best-effort basis: errors are ignored, since the
+/// terminal may well be gone by then. Use `into_inner` to see them.
 pub struct RawTerminal<W: Write> {
-    prev_ios: Termios,
-    output: W,
+    fd: RawFd,
+    /// The state to restore, `None` in batch mode.
+    prev_ios: Option<Termios>,
+    options: RawModeBuilder,
+    /// The output target, `None` once taken back by `into_inner`.
+    output: Option<W>,
 }
 
 impl<W: Write> Drop for RawTerminal<W> {
     fn drop(&mut self) {
-        set_terminal_attr(&self.prev_ios).unwrap();
+        if let (Some(prev_ios), Some(_)) = (self.prev_ios, &self.output) {
+            let _ = set_terminal_attr_of(self.fd, &prev_ios);
+        }
     }
 }
 
@@ -50,36 +63,29 @@ impl<W: Write> ops::Deref for RawTerminal<W> {
     type Target = W;
 
     fn deref(&self) -> &W {
-        &self.output
+        self.output.as_ref().expect("the output target is only taken when consumed")
     }
 }
 
 impl<W: Write> ops::DerefMut for RawTerminal<W> {
     fn deref_mut(&mut self) -> &mut W {
-        &mut self.output
+        self.output.as_mut().expect("the output target is only taken when consumed")
     }
 }
 
 impl<W: Write> Write for RawTerminal<W> {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.output.write(buf)
+        (**self).write(buf)
     }
 
     fn flush(&mut self) -> io::Result<()> {
-        self.output.flush()
+        (**self).flush()
     }
 }
 
-#[cfg(unix)]
-mod unix_impl {
-    use std::os::unix::io::{AsRawFd, RawFd};
-
-    use super::*;
-
-    impl<W: Write + AsRawFd> AsRawFd for RawTerminal<W> {
-        fn as_raw_fd(&self) -> RawFd {
-            self.output.as_raw_fd()
-        }
+impl<W: Write + AsRawFd> AsRawFd for RawTerminal<W> {
+    fn as_raw_fd(&self) -> RawFd {
+        (**self).as_raw_fd()
     }
 }
 
@@ -100,16 +106,101 @@ pub trait IntoRawMode: Write + Sized {
 
 impl<W: Write> IntoRawMode for W {
     fn into_raw_mode(self) -> io::Result<RawTerminal<W>> {
-        let mut ios = get_terminal_attr()?;
+        RawModeBuilder::new().build(self)
+    }
+}
+
+/// A builder for raw mode with non-default read behavior.
+///
+/// Reads in raw mode are governed by two terminal settings: `VMIN`, the number of bytes a read
+/// waits for, and `VTIME`, a timeout in tenths of a second. The default, used by
+/// `IntoRawMode::into_raw_mode`, is to block until at least one byte is available.
+///
+/// | `vmin` | `vtime` | A read returns…                                             |
+/// |--------|---------|-------------------------------------------------------------|
+/// | > 0    | 0       | once `vmin` bytes are available.                            |
+/// | 0      | > 0     | once a byte is available or `vtime` has elapsed.            |
+/// | > 0    | > 0     | once `vmin` bytes are available or `vtime` passed between two bytes. |
+/// | 0      | 0       | immediately, with whatever is available.                    |
+///
+/// # Example
+///
+/// ```rust,no_run
+/// use pres::raw::RawModeBuilder;
+/// use std::io::stdout;
+///
+/// // Return from reads after 100 ms, even without input.
+/// let stdout = RawModeBuilder::new().vmin(0).vtime(1).build(stdout()).unwrap();
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct RawModeBuilder {
+    vmin: u8,
+    vtime: u8,
+}
+
+impl Default for RawModeBuilder {
+    fn default() -> RawModeBuilder {
+        RawModeBuilder { vmin: 1, vtime: 0 }
+    }
+}
+
+impl RawModeBuilder {
+    /// Start from the default raw mode, where reads block until a byte is available.
+    pub fn new() -> RawModeBuilder {
+        RawModeBuilder::default()
+    }
+
+    /// Set the minimum number of bytes for a read to return (`VMIN`).
+    pub fn vmin(mut self, vmin: u8) -> RawModeBuilder {
+        self.vmin = vmin;
+        self
+    }
+
+    /// Set the read timeout in tenths of a second (`VTIME`).
+    pub fn vtime(mut self, vtime: u8) -> RawModeBuilder {
+        self.vtime = vtime;
+        self
+    }
+
+    /// Apply these settings on top of raw mode.
+    fn apply(&self, ios: &mut Termios) {
+        raw_terminal_attr(ios);
+        read_timing_terminal_attr(ios, self.vmin, self.vtime);
+    }
+
+    /// Switch to raw mode with these settings.
+    ///
+    /// The terminal switched is the one on standard output, whatever `output` is.
+    pub fn build<W: Write>(self, output: W) -> io::Result<RawTerminal<W>> {
+        self.build_on(libc::STDOUT_FILENO, output)
+    }
+
+    /// Switch the terminal `tty` itself to raw mode with these settings.
+    ///
+    /// Use this to manage several terminals in one process, e.g. the controlling terminal and a
+    /// PTY: each `RawTerminal` restores the state of its own terminal only.
+    pub fn build_for<W: Write + AsRawFd>(self, tty: W) -> io::Result<RawTerminal<W>> {
+        let fd = tty.as_raw_fd();
+        self.build_on(fd, tty)
+    }
+
+    fn build_on<W: Write>(self, fd: RawFd, output: W) -> io::Result<RawTerminal<W>> {
+        if batch::is_batch_for(fd) {
+            return Ok(RawTerminal { fd, prev_ios: None, options: self, output: Some(output) });
+        }
+
+        let mut ios = get_terminal_attr_of(fd)?;
         let prev_ios = ios;
 
-        raw_terminal_attr(&mut ios);
+        self.apply(&mut ios);
 
-        set_terminal_attr(&ios)?;
+        set_terminal_attr_of(fd, &ios)?;
 
         Ok(RawTerminal {
-            prev_ios: prev_ios,
-            output: self,
+            fd,
+            prev_ios: Some(prev_ios),
+            options: self,
+            output: Some(output),
         })
     }
 }
@@ -117,17 +208,34 @@ impl<W: Write> IntoRawMode for W {
 impl<W: Write> RawTerminal<W> {
     /// Temporarily switch to original mode
     pub fn suspend_raw_mode(&self) -> io::Result<()> {
-        set_terminal_attr(&self.prev_ios)?;
+        if let Some(prev_ios) = self.prev_ios {
+            set_terminal_attr_of(self.fd, &prev_ios)?;
+        }
         Ok(())
     }
 
     /// Temporarily switch to raw mode
     pub fn activate_raw_mode(&self) -> io::Result<()> {
-        let mut ios = get_terminal_attr()?;
-        raw_terminal_attr(&mut ios);
-        set_terminal_attr(&ios)?;
+        if self.prev_ios.is_none() {
+            return Ok(());
+        }
+        let mut ios = get_terminal_attr_of(self.fd)?;
+        self.options.apply(&mut ios);
+        set_terminal_attr_of(self.fd, &ios)?;
         Ok(())
     }
+
+    /// Restore the original mode and get the output target back.
+    ///
+    /// Unlike dropping, this reports an error restoring the terminal.
+    pub fn into_inner(mut self) -> io::Result<W> {
+        // Taking the output target leaves nothing for `drop` to restore.
+        let output = self.output.take().expect("the output target is only taken when consumed");
+        if let Some(prev_ios) = self.prev_ios {
+            set_terminal_attr_of(self.fd, &prev_ios)?;
+        }
+        Ok(output)
+    }
 }
 
 #[cfg(test)]
@@ -144,4 +252,20 @@ mod test {
 
         drop(out);
     }
+
+    #[test]
+    fn test_raw_mode_into_inner() {
+        let out = stdout().into_raw_mode().unwrap();
+        out.into_inner().unwrap().write_all(b"restored\r\n").unwrap();
+    }
+
+    #[test]
+    fn test_raw_mode_builder() {
+        // No terminal needed: the settings are applied to a blank state.
+        let mut ios: Termios = unsafe { std::mem::zeroed() };
+        RawModeBuilder::new().vmin(0).vtime(1).apply(&mut ios);
+
+        assert_eq!(ios.c_cc[libc::VMIN], 0);
+        assert_eq!(ios.c_cc[libc::VTIME], 1);
+    }
 }
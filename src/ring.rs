@@ -0,0 +1,231 @@
+//! A bounded single-producer single-consumer byte queue.
+//!
+//! This connects the reader thread of `AsyncReader` to its consumer without locking on the hot
+//! path: each side owns one index and only reads the other's.
+
+use std::cell::{Cell, UnsafeCell};
+use std::io;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+/// How many times a producer facing a full queue yields before it starts sleeping.
+const FULL_SPINS: u32 = 64;
+
+/// How long a producer facing a full queue sleeps before checking again.
+const FULL_BACKOFF: Duration = Duration::from_micros(50);
+
+/// An index on its own cache line, so the two sides don't contend over unrelated writes.
+#[repr(align(64))]
+struct Index(AtomicUsize);
+
+struct Ring {
+    buf: Box<[UnsafeCell<u8>]>,
+    /// The next slot to read. Only written by the consumer.
+    head: Index,
+    /// The next slot to write. Only written by the producer.
+    tail: Index,
+    /// The error that ended the stream, delivered after the bytes before it.
+    error: Mutex<Option<io::Error>>,
+    /// Whether the producer is done, with or without an error.
+    closed: AtomicBool,
+    /// Whether the consumer is gone.
+    abandoned: AtomicBool,
+}
+
+// The producer only writes slots between `tail` and `head + capacity`, the consumer only reads
+// slots between `head` and `tail`, and each index is published with release ordering after the
+// slot access it covers.
+unsafe impl Sync for Ring {}
+
+/// Create a queue holding up to `capacity` bytes.
+pub fn channel(capacity: usize) -> (Producer, Consumer) {
+    let ring = Arc::new(Ring {
+        buf: (0..capacity.max(1)).map(|_| UnsafeCell::new(0)).collect(),
+        head: Index(AtomicUsize::new(0)),
+        tail: Index(AtomicUsize::new(0)),
+        error: Mutex::new(None),
+        closed: AtomicBool::new(false),
+        abandoned: AtomicBool::new(false),
+    });
+    (Producer { ring: ring.clone(), head: Cell::new(0) }, Consumer { ring, tail: Cell::new(0) })
+}
+
+/// The writing end of a queue.
+pub struct Producer {
+    ring: Arc<Ring>,
+    /// The last `head` seen, to avoid touching the consumer's cache line on every push.
+    head: Cell<usize>,
+}
+
+impl Producer {
+    /// Add `bytes`, waiting while the queue is full.
+    ///
+    /// Fails if the consumer was dropped.
+    pub fn push(&self, mut bytes: &[u8]) -> Result<(), ()> {
+        let ring = &*self.ring;
+        let capacity = ring.buf.len();
+        let mut spins = 0;
+
+        while !bytes.is_empty() {
+            if ring.abandoned.load(Ordering::Relaxed) {
+                return Err(());
+            }
+
+            let tail = ring.tail.0.load(Ordering::Relaxed);
+            if tail - self.head.get() == capacity {
+                self.head.set(ring.head.0.load(Ordering::Acquire));
+            }
+            let free = capacity - (tail - self.head.get());
+            if free == 0 {
+                if spins < FULL_SPINS {
+                    spins += 1;
+                    thread::yield_now();
+                } else {
+                    thread::sleep(FULL_BACKOFF);
+                }
+                continue;
+            }
+
+            let n = free.min(bytes.len());
+            for (i, &byte) in bytes[..n].iter().enumerate() {
+                unsafe { *ring.buf[(tail + i) % capacity].get() = byte };
+            }
+            ring.tail.0.store(tail + n, Ordering::Release);
+            bytes = &bytes[n..];
+        }
+        Ok(())
+    }
+
+    /// End the stream with `error`, which the consumer receives after the queued bytes.
+    pub fn fail(self, error: io::Error) {
+        *self.ring.error.lock().unwrap() = Some(error);
+    }
+}
+
+impl Drop for Producer {
+    fn drop(&mut self) {
+        self.ring.closed.store(true, Ordering::Release);
+    }
+}
+
+/// The reading end of a queue.
+pub struct Consumer {
+    ring: Arc<Ring>,
+    /// The last `tail` seen, to avoid touching the producer's cache line on every pop.
+    tail: Cell<usize>,
+}
+
+impl Consumer {
+    /// Take as many queued bytes as fit in `out`, without waiting.
+    ///
+    /// Returns 0 if nothing is queued right now or the stream is over, and the error ending the
+    /// stream once all bytes before it were taken.
+    pub fn pop(&self, out: &mut [u8]) -> io::Result<usize> {
+        let ring = &*self.ring;
+        let capacity = ring.buf.len();
+        let head = ring.head.0.load(Ordering::Relaxed);
+
+        if head == self.tail.get() {
+            // Read `closed` first: once set, every byte is already visible in `tail`.
+            let closed = ring.closed.load(Ordering::Acquire);
+            self.tail.set(ring.tail.0.load(Ordering::Acquire));
+            if head == self.tail.get() {
+                // The error is stored before `closed` is set; leave it until then.
+                if closed {
+                    if let Some(error) = ring.error.lock().unwrap().take() {
+                        return Err(error);
+                    }
+                }
+                return Ok(0);
+            }
+        }
+
+        let n = (self.tail.get() - head).min(out.len());
+        for (i, byte) in out[..n].iter_mut().enumerate() {
+            *byte = unsafe { *ring.buf[(head + i) % capacity].get() };
+        }
+        ring.head.0.store(head + n, Ordering::Release);
+        Ok(n)
+    }
+
+    /// Whether the stream is over and everything in it, including its error, was taken.
+    pub fn is_finished(&self) -> bool {
+        let ring = &*self.ring;
+        ring.closed.load(Ordering::Acquire)
+            && ring.tail.0.load(Ordering::Acquire) == ring.head.0.load(Ordering::Relaxed)
+            && ring.error.lock().unwrap().is_none()
+    }
+
+    /// The number of bytes waiting to be taken.
+    pub fn queued(&self) -> usize {
+        self.ring.tail.0.load(Ordering::Acquire) - self.ring.head.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Drop for Consumer {
+    fn drop(&mut self) {
+        self.ring.abandoned.store(true, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ring_order_and_backpressure() {
+        let (producer, consumer) = channel(4);
+        let writer = thread::spawn(move || {
+            for b in 0..=255u8 {
+                producer.push(&[b]).unwrap();
+            }
+            producer.push(&[1, 2, 3, 4, 5, 6, 7]).unwrap();
+            producer.fail(io::Error::other("done"));
+        });
+
+        let mut received = Vec::new();
+        let mut buf = [0; 3];
+        loop {
+            match consumer.pop(&mut buf) {
+                Ok(0) => thread::yield_now(),
+                Ok(n) => received.extend_from_slice(&buf[..n]),
+                Err(e) => {
+                    assert_eq!(e.to_string(), "done");
+                    break;
+                }
+            }
+        }
+        writer.join().unwrap();
+
+        let expected: Vec<u8> = (0..=255).chain(1..=7).collect();
+        assert_eq!(received, expected);
+        assert_eq!(consumer.pop(&mut buf).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_ring_error_before_close() {
+        let (producer, consumer) = channel(4);
+        producer.push(&[1]).unwrap();
+        // What `fail` does before the producer is dropped.
+        *producer.ring.error.lock().unwrap() = Some(io::Error::other("failed"));
+
+        let mut buf = [0; 4];
+        assert_eq!(consumer.pop(&mut buf).unwrap(), 1);
+        assert_eq!(consumer.pop(&mut buf).unwrap(), 0);
+        assert!(!consumer.is_finished());
+
+        drop(producer);
+        assert_eq!(consumer.pop(&mut buf).unwrap_err().to_string(), "failed");
+        assert!(consumer.is_finished());
+    }
+
+    #[test]
+    fn test_ring_abandoned() {
+        let (producer, consumer) = channel(1);
+        producer.push(&[1]).unwrap();
+        drop(consumer);
+        assert!(producer.push(&[2]).is_err());
+    }
+}
@@ -0,0 +1,56 @@
+//! Scrolling regions.
+
+use std::fmt;
+
+use crate::sequence::Sequence;
+
+derive_csi_sequence!("Reset the scrolling region to the whole screen.", ResetRegion, "r");
+
+/// Restrict scrolling to the lines `top` through `bottom` (one-based, inclusive).
+///
+/// This is DECSTBM. Lines outside the region stay put while text scrolls inside it. Setting the
+/// region moves the cursor to the home position.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::cursor::Goto;
+/// use pres::scroll::Region;
+///
+/// let region = Region(5, 20);
+/// // The first line of the region is line 5 of the screen.
+/// assert!(Goto::relative_to_region(1, 1, region) == Some(Goto(1, 5)));
+/// assert!(Goto::relative_to_region(1, 17, region).is_none());
+/// ```
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region(pub u16, pub u16);
+
+impl Region {
+    /// The number of lines in the region.
+    ///
+    /// This saturates at `u16::MAX` for `Region(0, u16::MAX)`, which is not a valid region since
+    /// lines are one-based.
+    pub fn height(&self) -> u16 {
+        self.1.saturating_sub(self.0).saturating_add(1)
+    }
+
+    /// The sequence as bytes, formatted on the stack.
+    #[inline]
+    pub fn bytes(self) -> Sequence {
+        debug_assert!(self.0 >= 1 && self.0 <= self.1, "Region is one-based and top ≤ bottom.");
+        Sequence::csi2(self.0, self.1, b'r')
+    }
+}
+
+impl From<Region> for String {
+    fn from(this: Region) -> String {
+        this.bytes().as_str().to_owned()
+    }
+}
+
+impl fmt::Display for Region {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.bytes().fmt(f)
+    }
+}
@@ -0,0 +1,149 @@
+//! Selecting text with the mouse.
+//!
+//! While a program has mouse reporting on, the terminal stops selecting text itself. A
+//! `Selection` follows left button drags instead, so the program can highlight the selected cells
+//! and get the text under them from what it drew.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::event::{MouseButton, MouseEvent};
+//! use pres::selection::Selection;
+//!
+//! let screen = ["hello world", "second line"];
+//!
+//! let mut selection = Selection::new();
+//! selection.handle(MouseEvent::Press(MouseButton::Left, 7, 1));
+//! selection.handle(MouseEvent::Hold(6, 2));
+//! selection.handle(MouseEvent::Release(Some(MouseButton::Left), 6, 2));
+//! assert_eq!(selection.text(&screen), "world\nsecond");
+//! ```
+
+use crate::event::{MouseButton, MouseEvent};
+
+/// A range of text selected by dragging with the left mouse button.
+///
+/// Cells are selected in reading order, from the cell where the button was pressed to the one
+/// where it is held or was released, both included. Coordinates are one-based, as in
+/// `MouseEvent`, and a column is taken to hold one character.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Selection {
+    /// Where the drag started.
+    anchor: Option<(u16, u16)>,
+    /// Where the drag is now, or ended.
+    head: Option<(u16, u16)>,
+    /// Whether the button is still held.
+    dragging: bool,
+}
+
+impl Selection {
+    /// An empty selection.
+    pub fn new() -> Selection {
+        Selection::default()
+    }
+
+    /// Follow a mouse event, returning whether the selection changed.
+    ///
+    /// Pressing the left button starts a new selection, which follows the pointer until the
+    /// button is released. A click that ends on the cell it started on clears the selection.
+    pub fn handle(&mut self, event: MouseEvent) -> bool {
+        let before = *self;
+        match event {
+            MouseEvent::Press(MouseButton::Left, x, y) => {
+                self.anchor = Some((x, y));
+                self.head = Some((x, y));
+                self.dragging = true;
+            }
+            MouseEvent::Hold(x, y) | MouseEvent::Drag { to: (x, y), .. } if self.dragging => self.head = Some((x, y)),
+            MouseEvent::Release(None | Some(MouseButton::Left), x, y) if self.dragging => {
+                self.dragging = false;
+                if self.anchor == Some((x, y)) {
+                    self.clear();
+                } else {
+                    self.head = Some((x, y));
+                }
+            }
+            _ => {}
+        }
+        *self != before
+    }
+
+    /// Forget the selection.
+    pub fn clear(&mut self) {
+        *self = Selection::default();
+    }
+
+    /// Whether the left button is still held.
+    pub fn is_dragging(&self) -> bool {
+        self.dragging
+    }
+
+    /// The first and last cells selected, in reading order, as `(x, y)`.
+    pub fn range(&self) -> Option<((u16, u16), (u16, u16))> {
+        let (anchor, head) = (self.anchor?, self.head?);
+        // Order by row, then column.
+        let key = |(x, y): (u16, u16)| (y, x);
+        Some(if key(anchor) <= key(head) { (anchor, head) } else { (head, anchor) })
+    }
+
+    /// Whether the cell at `(x, y)` is selected, e.g. to draw it highlighted.
+    pub fn contains(&self, x: u16, y: u16) -> bool {
+        match self.range() {
+            Some(((x0, y0), (x1, y1))) => (y0, x0) <= (y, x) && (y, x) <= (y1, x1),
+            None => false,
+        }
+    }
+
+    /// The selected text, given the `lines` shown on screen from the first row.
+    ///
+    /// Rows are joined with `\n`; cells past the end of a line or below the last one are left out.
+    pub fn text<S: AsRef<str>>(&self, lines: &[S]) -> String {
+        let Some(((x0, y0), (x1, y1))) = self.range() else { return String::new() };
+
+        let first = (y0 as usize).saturating_sub(1);
+        let mut text = Vec::new();
+        for (i, line) in lines.iter().enumerate().skip(first).take((y1 - y0) as usize + 1) {
+            let y = i as u16 + 1;
+            let start = if y == y0 { (x0 as usize).saturating_sub(1) } else { 0 };
+            let end = if y == y1 { x1 as usize } else { usize::MAX };
+            let row: String = line.as_ref().chars().take(end).skip(start).collect();
+            text.push(row);
+        }
+        text.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_drag_backwards() {
+        let mut selection = Selection::new();
+        assert!(selection.handle(MouseEvent::Press(MouseButton::Left, 4, 3)));
+        assert!(selection.handle(MouseEvent::Hold(2, 1)));
+        assert!(selection.is_dragging());
+        // Other buttons and motion after the release are ignored.
+        assert!(!selection.handle(MouseEvent::Press(MouseButton::WheelUp, 9, 9)));
+        assert!(selection.handle(MouseEvent::Release(None, 2, 2)));
+        assert!(!selection.handle(MouseEvent::Hold(5, 5)));
+
+        assert_eq!(selection.range(), Some(((2, 2), (4, 3))));
+        assert!(selection.contains(9, 2) && selection.contains(1, 3) && !selection.contains(5, 3));
+        assert_eq!(selection.text(&["abc", "defg", "hijkl"]), "efg\nhijk");
+        assert_eq!(selection.text(&["abc"]), "");
+    }
+
+    #[test]
+    fn test_click_clears() {
+        let mut selection = Selection::new();
+        selection.handle(MouseEvent::Press(MouseButton::Left, 1, 1));
+        selection.handle(MouseEvent::Release(Some(MouseButton::Left), 3, 1));
+        assert_eq!(selection.text(&["abcd"]), "abc");
+
+        selection.handle(MouseEvent::Press(MouseButton::Left, 2, 1));
+        selection.handle(MouseEvent::Release(Some(MouseButton::Left), 2, 1));
+        assert_eq!(selection.range(), None);
+        assert_eq!(selection.text(&["abcd"]), "");
+    }
+}
@@ -0,0 +1,121 @@
+//! Escape sequences formatted on the stack.
+
+use std::fmt;
+use std::ops;
+use std::str;
+
+use numtoa::NumToA;
+
+/// Room for the longest parameterized sequence, `ESC [ 65535 ; 65535 H`.
+const CAPACITY: usize = 16;
+
+/// A parameterized escape sequence formatted without allocating.
+///
+/// Obtained from `bytes()` on types such as `cursor::Goto`, for writing sequences in hot loops,
+/// e.g. one cursor move per changed cell.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::cursor::Goto;
+/// use std::io::Write;
+///
+/// let mut out = Vec::new();
+/// out.write_all(&Goto(5, 3).bytes()).unwrap();
+/// assert_eq!(out, b"\x1B[3;5H");
+/// ```
+#[derive(Copy, Clone)]
+pub struct Sequence {
+    buf: [u8; CAPACITY],
+    len: u8,
+}
+
+impl Sequence {
+    /// Format `CSI a final`.
+    #[inline]
+    pub(crate) fn csi1(a: u16, final_byte: u8) -> Sequence {
+        let mut seq = Sequence::csi();
+        seq.push_num(a);
+        seq.push(final_byte);
+        seq
+    }
+
+    /// Format `CSI a ; b final`.
+    #[inline]
+    pub(crate) fn csi2(a: u16, b: u16, final_byte: u8) -> Sequence {
+        let mut seq = Sequence::csi();
+        seq.push_num(a);
+        seq.push(b';');
+        seq.push_num(b);
+        seq.push(final_byte);
+        seq
+    }
+
+    #[inline]
+    fn csi() -> Sequence {
+        Sequence { buf: [0x1B, b'[', 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], len: 2 }
+    }
+
+    #[inline]
+    fn push(&mut self, byte: u8) {
+        self.buf[self.len as usize] = byte;
+        self.len += 1;
+    }
+
+    #[inline]
+    fn push_num(&mut self, n: u16) {
+        let mut digits = [0u8; 5];
+        let start = n.numtoa(10, &mut digits);
+        for &digit in digits[start..].iter() {
+            self.push(digit);
+        }
+    }
+
+    /// The sequence as a string.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        // Only ASCII is ever pushed.
+        str::from_utf8(self).unwrap()
+    }
+}
+
+impl ops::Deref for Sequence {
+    type Target = [u8];
+
+    #[inline]
+    fn deref(&self) -> &[u8] {
+        &self.buf[..self.len as usize]
+    }
+}
+
+impl AsRef<[u8]> for Sequence {
+    #[inline]
+    fn as_ref(&self) -> &[u8] {
+        self
+    }
+}
+
+impl fmt::Display for Sequence {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Sequence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.as_str().fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_sequence() {
+        assert_eq!(&*Sequence::csi2(65535, 65535, b'H'), b"\x1B[65535;65535H");
+        assert_eq!(&*Sequence::csi1(0, b'A'), b"\x1B[0A");
+        assert_eq!(Sequence::csi1(12, b'S').to_string(), "\x1B[12S");
+    }
+}
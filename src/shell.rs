@@ -0,0 +1,269 @@
+//! Shell integration marks (OSC 133 and OSC 633).
+//!
+//! Terminals that understand these marks know where prompts, commands and their output begin,
+//! which enables features like jumping between prompts. The same marks let a program find its own
+//! output again, see `Sections`, and let terminal wrappers follow the shell running inside them,
+//! see `ShellEvent`.
+
+use std::fmt;
+use std::io::{self, Write};
+use std::ops;
+
+use crate::clear;
+use crate::cursor;
+
+/// A semantic mark in the output of an interactive shell.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Mark {
+    /// A prompt is about to be drawn (`133;A`).
+    PromptStart,
+    /// The prompt is drawn and the user is typing a command (`133;B`).
+    CommandStart,
+    /// The command was submitted and its output follows (`133;C`).
+    CommandExecuted,
+    /// The command finished, possibly with an exit code (`133;D[;code]`).
+    CommandFinished(Option<i32>),
+}
+
+impl Mark {
+    /// Parse the payload of an OSC 133 sequence, that is everything between `ESC ]` and the
+    /// terminator, e.g. `133;D;1`. The equivalent OSC 633 marks are accepted as well.
+    ///
+    /// Returns `None` if this is not a mark understood by this module. Unknown trailing options
+    /// (such as `;aid=…`) are ignored.
+    pub fn parse(payload: &str) -> Option<Mark> {
+        let mut fields = payload.split(';');
+        if !matches!(fields.next(), Some("133") | Some("633")) {
+            return None;
+        }
+        Some(match fields.next()? {
+            "A" => Mark::PromptStart,
+            "B" => Mark::CommandStart,
+            "C" => Mark::CommandExecuted,
+            "D" => Mark::CommandFinished(fields.next().and_then(|code| code.parse().ok())),
+            _ => return None,
+        })
+    }
+}
+
+impl fmt::Display for Mark {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Mark::PromptStart => write!(f, "\x1B]133;A\x1B\\"),
+            Mark::CommandStart => write!(f, "\x1B]133;B\x1B\\"),
+            Mark::CommandExecuted => write!(f, "\x1B]133;C\x1B\\"),
+            Mark::CommandFinished(None) => write!(f, "\x1B]133;D\x1B\\"),
+            Mark::CommandFinished(Some(code)) => write!(f, "\x1B]133;D;{}\x1B\\", code),
+        }
+    }
+}
+
+/// Shell integration information reported by a shell, as seen by a terminal wrapper.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum ShellEvent {
+    /// A prompt or command boundary (OSC 133 or OSC 633 `A` to `D`).
+    Mark(Mark),
+    /// The command line about to be executed (OSC 633 `E`).
+    CommandLine(String),
+    /// A property of the shell, such as the current directory (OSC 633 `P`).
+    Property {
+        /// The property name, e.g. `Cwd`.
+        name: String,
+        /// The property value.
+        value: String,
+    },
+}
+
+impl ShellEvent {
+    /// Parse the payload of an OSC 133 or OSC 633 sequence.
+    ///
+    /// Returns `None` if this is not shell integration information understood by this module.
+    pub fn parse(payload: &str) -> Option<ShellEvent> {
+        if let Some(mark) = Mark::parse(payload) {
+            return Some(ShellEvent::Mark(mark));
+        }
+
+        let mut fields = payload.splitn(3, ';');
+        if fields.next() != Some("633") {
+            return None;
+        }
+        match (fields.next()?, fields.next()) {
+            ("E", command) => {
+                // A nonce may follow the command line; it is of no use to us.
+                let command = command.unwrap_or("").split(';').next().unwrap_or("");
+                Some(ShellEvent::CommandLine(unescape_633(command)))
+            }
+            ("P", Some(property)) => {
+                let (name, value) = property.split_once('=')?;
+                Some(ShellEvent::Property { name: name.to_string(), value: unescape_633(value) })
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Undo the escaping of OSC 633 values, where `\\` is a backslash and `\xAB` a byte in hex.
+fn unescape_633(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut rest = value.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        rest = tail;
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match rest {
+            [b'\\', tail @ ..] => {
+                bytes.push(b'\\');
+                rest = tail;
+            }
+            [b'x', hi, lo, tail @ ..] => {
+                match std::str::from_utf8(&[*hi, *lo]).ok().and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                    Some(byte) => {
+                        bytes.push(byte);
+                        rest = tail;
+                    }
+                    None => bytes.push(b'\\'),
+                }
+            }
+            _ => bytes.push(b'\\'),
+        }
+    }
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+/// A writer that splits its output into sections delimited by marks.
+///
+/// Every `mark` starts a new section; `clear_last_section` erases everything written since the
+/// last one, so prompt tooling can redraw just its own block of output.
+///
+/// Lines are counted from the newlines written, so the mark should be placed at the start of a
+/// line and the section should not contain lines wrapped by the terminal.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::shell::{Mark, Sections};
+/// use std::io::Write;
+///
+/// let mut out = Sections::from(Vec::new());
+/// out.mark(Mark::CommandExecuted).unwrap();
+/// write!(out, "working...\nstill working...\n").unwrap();
+/// out.clear_last_section().unwrap();
+/// write!(out, "done\n").unwrap();
+/// ```
+pub struct Sections<W: Write> {
+    /// The output target.
+    output: W,
+    /// Newlines written since the last mark.
+    lines: u16,
+}
+
+impl<W: Write> Sections<W> {
+    /// Wrap `output`, starting a first (unmarked) section at the current line.
+    pub fn from(output: W) -> Self {
+        Sections { output, lines: 0 }
+    }
+
+    /// Write `mark` and start a new section after it.
+    pub fn mark(&mut self, mark: Mark) -> io::Result<()> {
+        write!(self.output, "{}", mark)?;
+        self.lines = 0;
+        Ok(())
+    }
+
+    /// The number of lines the current section spans beyond its first one.
+    pub fn lines(&self) -> u16 {
+        self.lines
+    }
+
+    /// Erase everything written since the last mark and put the cursor where the mark was.
+    pub fn clear_last_section(&mut self) -> io::Result<()> {
+        write!(self.output, "\r")?;
+        if self.lines > 0 {
+            write!(self.output, "{}", cursor::Up(self.lines))?;
+        }
+        write!(self.output, "{}", clear::AfterCursor)?;
+        self.lines = 0;
+        Ok(())
+    }
+
+    /// Get the output target back.
+    pub fn into_inner(self) -> W {
+        self.output
+    }
+}
+
+impl<W: Write> ops::Deref for Sections<W> {
+    type Target = W;
+
+    fn deref(&self) -> &W {
+        &self.output
+    }
+}
+
+impl<W: Write> ops::DerefMut for Sections<W> {
+    fn deref_mut(&mut self) -> &mut W {
+        &mut self.output
+    }
+}
+
+impl<W: Write> Write for Sections<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.output.write(buf)?;
+        let newlines = buf[..written].iter().filter(|&&b| b == b'\n').count();
+        self.lines = self.lines.saturating_add(newlines as u16);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_mark_round_trip() {
+        let marks = [
+            Mark::PromptStart,
+            Mark::CommandStart,
+            Mark::CommandExecuted,
+            Mark::CommandFinished(None),
+            Mark::CommandFinished(Some(127)),
+        ];
+        for &mark in marks.iter() {
+            let text = mark.to_string();
+            let payload = &text[2..text.len() - 2];
+            assert_eq!(Mark::parse(payload), Some(mark));
+        }
+        assert_eq!(Mark::parse("133;A;aid=42"), Some(Mark::PromptStart));
+        assert_eq!(Mark::parse("133;X"), None);
+        assert_eq!(Mark::parse("8;;http://example.com"), None);
+    }
+
+    #[test]
+    fn test_shell_event() {
+        assert_eq!(ShellEvent::parse("633;D;0"), Some(ShellEvent::Mark(Mark::CommandFinished(Some(0)))));
+        assert_eq!(ShellEvent::parse("633;E;echo a\\x3bb \\\\;nonce"),
+                   Some(ShellEvent::CommandLine("echo a;b \\".to_string())));
+        assert_eq!(ShellEvent::parse("633;P;Cwd=/home/me"),
+                   Some(ShellEvent::Property { name: "Cwd".to_string(), value: "/home/me".to_string() }));
+        assert_eq!(ShellEvent::parse("633;P;Cwd"), None);
+        assert_eq!(ShellEvent::parse("7;file:///home/me"), None);
+    }
+
+    #[test]
+    fn test_clear_last_section() {
+        let mut out = Sections::from(Vec::new());
+        out.write_all(b"kept\n").unwrap();
+        out.mark(Mark::CommandExecuted).unwrap();
+        out.write_all(b"one\ntwo\n").unwrap();
+        assert_eq!(out.lines(), 2);
+        out.clear_last_section().unwrap();
+        assert_eq!(out.lines(), 0);
+        assert_eq!(out.into_inner(), b"kept\n\x1B]133;C\x1B\\one\ntwo\n\r\x1B[2A\x1B[J".to_vec());
+    }
+}
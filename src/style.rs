@@ -0,0 +1,354 @@
+//! Text styling.
+//!
+//! Each attribute has a type to turn it on and one to turn it off again, or `Reset` turns every
+//! attribute and color off. Attributes disallowed by the `StylePolicy` are not written.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::style;
+//!
+//! println!("{}important{} and {}emphasized{}", style::Bold, style::NoBold, style::Italic, style::Reset);
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::color::{AnsiValue, Rgb};
+
+/// Which attributes are allowed to reach the terminal.
+///
+/// Some environments (screen readers, screen recordings) should never see certain attributes,
+/// such as blinking text. The policy is process-wide: set it once with `set_policy` and every
+/// style emitted by this module afterwards honors it.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::style::{self, SgrState, StylePolicy};
+///
+/// style::set_policy(StylePolicy { blink: false, ..StylePolicy::default() });
+///
+/// let blinking = SgrState { blink: true, ..SgrState::default() };
+/// assert_eq!(SgrState::default().transition_to(&blinking), "");
+/// # style::set_policy(StylePolicy::default());
+/// ```
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct StylePolicy {
+    /// Allow bold text.
+    pub bold: bool,
+    /// Allow faint text.
+    pub faint: bool,
+    /// Allow italic text.
+    pub italic: bool,
+    /// Allow underlined text.
+    pub underline: bool,
+    /// Allow blinking text.
+    pub blink: bool,
+    /// Allow inverted colors.
+    pub invert: bool,
+    /// Allow invisible text.
+    pub invisible: bool,
+    /// Allow crossed-out text.
+    pub strikethrough: bool,
+}
+
+impl Default for StylePolicy {
+    /// Allow every attribute.
+    fn default() -> StylePolicy {
+        StylePolicy::from_bits(ALLOW_ALL)
+    }
+}
+
+impl StylePolicy {
+    fn to_bits(self) -> u8 {
+        [self.bold, self.faint, self.italic, self.underline,
+            self.blink, self.invert, self.invisible, self.strikethrough]
+            .iter()
+            .enumerate()
+            .fold(0, |bits, (i, &allowed)| bits | ((allowed as u8) << i))
+    }
+
+    fn from_bits(bits: u8) -> StylePolicy {
+        let allowed = |i: u8| bits & (1 << i) != 0;
+        StylePolicy {
+            bold: allowed(0),
+            faint: allowed(1),
+            italic: allowed(2),
+            underline: allowed(3),
+            blink: allowed(4),
+            invert: allowed(5),
+            invisible: allowed(6),
+            strikethrough: allowed(7),
+        }
+    }
+
+    /// Strip the attributes this policy disallows from `state`.
+    pub fn apply(&self, state: SgrState) -> SgrState {
+        SgrState {
+            bold: state.bold && self.bold,
+            faint: state.faint && self.faint,
+            italic: state.italic && self.italic,
+            underline: state.underline && self.underline,
+            blink: state.blink && self.blink,
+            invert: state.invert && self.invert,
+            invisible: state.invisible && self.invisible,
+            strikethrough: state.strikethrough && self.strikethrough,
+            ..state
+        }
+    }
+}
+
+const ALLOW_ALL: u8 = 0xFF;
+
+/// The process-wide policy, as `StylePolicy` bits.
+static POLICY: AtomicU8 = AtomicU8::new(ALLOW_ALL);
+
+/// Replace the process-wide style policy.
+pub fn set_policy(policy: StylePolicy) {
+    POLICY.store(policy.to_bits(), Ordering::Relaxed);
+}
+
+/// Get the process-wide style policy.
+pub fn policy() -> StylePolicy {
+    StylePolicy::from_bits(POLICY.load(Ordering::Relaxed))
+}
+
+/// Derive an attribute, written only if the process-wide policy allows it.
+macro_rules! derive_attribute {
+    ($doc:expr, $name:ident, $value:expr, $allowed:ident) => {
+        #[doc = $doc]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        pub struct $name;
+
+        impl fmt::Display for $name {
+            #[inline]
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                if policy().$allowed { f.write_str(csi!($value)) } else { Ok(()) }
+            }
+        }
+    };
+}
+
+derive_attribute!("Bold or increased intensity.", Bold, "1m", bold);
+derive_attribute!("Faint or decreased intensity.", Faint, "2m", faint);
+derive_attribute!("Italic.", Italic, "3m", italic);
+derive_attribute!("Underline.", Underline, "4m", underline);
+derive_attribute!("Blink.", Blink, "5m", blink);
+derive_attribute!("Swap the foreground and background colors.", Invert, "7m", invert);
+derive_attribute!("Invisible text.", Invisible, "8m", invisible);
+derive_attribute!("Crossed-out text.", Strikethrough, "9m", strikethrough);
+
+derive_csi_sequence!("Turn every attribute and color off.", Reset, "0m");
+derive_csi_sequence!("Turn bold off, and faint with it: both share one reset.", NoBold, "22m");
+derive_csi_sequence!("Turn faint off, and bold with it: both share one reset.", NoFaint, "22m");
+derive_csi_sequence!("Turn italic off.", NoItalic, "23m");
+derive_csi_sequence!("Turn underline off.", NoUnderline, "24m");
+derive_csi_sequence!("Turn blink off.", NoBlink, "25m");
+derive_csi_sequence!("Swap the colors back.", NoInvert, "27m");
+derive_csi_sequence!("Make text visible again.", NoInvisible, "28m");
+derive_csi_sequence!("Turn crossing out off.", NoStrikethrough, "29m");
+
+/// A foreground or background color as tracked by `SgrState`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum SgrColor {
+    /// A palette color.
+    Ansi(AnsiValue),
+    /// A truecolor value.
+    Rgb(Rgb),
+}
+
+impl SgrColor {
+    /// Push the SGR parameters selecting this color. `base` is 30 for foreground, 40 for
+    /// background.
+    fn push_params(self, base: u8, params: &mut Vec<String>) {
+        match self {
+            SgrColor::Ansi(AnsiValue(n @ 0..=7)) => params.push((base + n).to_string()),
+            SgrColor::Ansi(AnsiValue(n @ 8..=15)) => params.push((base + 60 + n - 8).to_string()),
+            SgrColor::Ansi(AnsiValue(n)) => params.push(format!("{};5;{}", base + 8, n)),
+            SgrColor::Rgb(Rgb(r, g, b)) => params.push(format!("{};2;{};{};{}", base + 8, r, g, b)),
+        }
+    }
+}
+
+/// The graphic rendition state of a terminal.
+///
+/// `SgrState::default()` is the state right after `ESC [ 0 m`.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::style::SgrState;
+///
+/// let plain = SgrState::default();
+/// let bold = SgrState { bold: true, ..plain };
+/// let bold_underline = SgrState { underline: true, ..bold };
+///
+/// // Only the attribute that changed is emitted.
+/// assert_eq!(bold.transition_to(&bold_underline), "\x1B[4m");
+/// assert_eq!(bold_underline.transition_to(&bold), "\x1B[24m");
+/// ```
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub struct SgrState {
+    /// Bold or increased intensity.
+    pub bold: bool,
+    /// Faint or decreased intensity.
+    pub faint: bool,
+    /// Italic.
+    pub italic: bool,
+    /// Underline.
+    pub underline: bool,
+    /// Blink.
+    pub blink: bool,
+    /// Swapped foreground and background.
+    pub invert: bool,
+    /// Invisible text.
+    pub invisible: bool,
+    /// Crossed-out text.
+    pub strikethrough: bool,
+    /// Foreground color, `None` being the terminal default.
+    pub fg: Option<SgrColor>,
+    /// Background color, `None` being the terminal default.
+    pub bg: Option<SgrColor>,
+}
+
+impl SgrState {
+    /// The parameters needed to go from the default state to this one.
+    fn params_from_default(&self) -> Vec<String> {
+        self.params_from(&SgrState::default())
+    }
+
+    /// The parameters needed to go from `prev` to this state without a full reset.
+    fn params_from(&self, prev: &SgrState) -> Vec<String> {
+        let mut params = Vec::new();
+
+        // Bold and faint share a single reset code, so turning either off means re-enabling the
+        // other afterwards.
+        let intensity_reset = (prev.bold && !self.bold) || (prev.faint && !self.faint);
+        if intensity_reset {
+            params.push("22".to_string());
+        }
+        if self.bold && (intensity_reset || !prev.bold) {
+            params.push("1".to_string());
+        }
+        if self.faint && (intensity_reset || !prev.faint) {
+            params.push("2".to_string());
+        }
+
+        let toggles = [
+            (prev.italic, self.italic, "3", "23"),
+            (prev.underline, self.underline, "4", "24"),
+            (prev.blink, self.blink, "5", "25"),
+            (prev.invert, self.invert, "7", "27"),
+            (prev.invisible, self.invisible, "8", "28"),
+            (prev.strikethrough, self.strikethrough, "9", "29"),
+        ];
+        for &(was, is, on, off) in toggles.iter() {
+            if was != is {
+                params.push(if is { on } else { off }.to_string());
+            }
+        }
+
+        if prev.fg != self.fg {
+            match self.fg {
+                Some(color) => color.push_params(30, &mut params),
+                None => params.push("39".to_string()),
+            }
+        }
+        if prev.bg != self.bg {
+            match self.bg {
+                Some(color) => color.push_params(40, &mut params),
+                None => params.push("49".to_string()),
+            }
+        }
+
+        params
+    }
+
+    /// The shortest SGR sequence changing the terminal from this state to `next`.
+    ///
+    /// Attributes are switched individually where possible; a full reset is only used when it
+    /// produces fewer bytes. The result is empty if both states are equal. Attributes disallowed
+    /// by the current `policy()` are left out.
+    pub fn transition_to(&self, next: &SgrState) -> String {
+        let policy = policy();
+        let (prev, next) = (policy.apply(*self), policy.apply(*next));
+
+        let incremental = next.params_from(&prev);
+        if incremental.is_empty() {
+            return String::new();
+        }
+
+        let mut reset = next.params_from_default();
+        reset.insert(0, "0".to_string());
+
+        let params = if reset.join(";").len() < incremental.join(";").len() { reset } else { incremental };
+        format!("\x1B[{}m", params.join(";"))
+    }
+}
+
+impl fmt::Display for SgrState {
+    /// Write the full state, starting from a reset.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut params = policy().apply(*self).params_from_default();
+        params.insert(0, "0".to_string());
+        write!(f, "\x1B[{}m", params.join(";"))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_no_change() {
+        let state = SgrState { italic: true, ..SgrState::default() };
+        assert_eq!(state.transition_to(&state), "");
+    }
+
+    #[test]
+    fn test_intensity_reset() {
+        let both = SgrState { bold: true, faint: true, italic: true, ..SgrState::default() };
+        let faint = SgrState { bold: false, ..both };
+        assert_eq!(both.transition_to(&faint), "\x1B[22;2m");
+    }
+
+    #[test]
+    fn test_reset_when_shorter() {
+        let busy = SgrState {
+            bold: true,
+            italic: true,
+            underline: true,
+            strikethrough: true,
+            ..SgrState::default()
+        };
+        assert_eq!(busy.transition_to(&SgrState::default()), "\x1B[0m");
+    }
+
+    #[test]
+    fn test_colors() {
+        let plain = SgrState::default();
+        let red = SgrState { fg: Some(SgrColor::Ansi(AnsiValue(1))), ..plain };
+        let bright_bg = SgrState { bg: Some(SgrColor::Ansi(AnsiValue(12))), ..plain };
+        let rgb = SgrState { fg: Some(SgrColor::Rgb(Rgb(1, 2, 3))), ..plain };
+        assert_eq!(plain.transition_to(&red), "\x1B[31m");
+        assert_eq!(plain.transition_to(&bright_bg), "\x1B[104m");
+        assert_eq!(red.transition_to(&rgb), "\x1B[38;2;1;2;3m");
+        assert_eq!(format!("{}", rgb), "\x1B[0;38;2;1;2;3m");
+    }
+
+    #[test]
+    fn test_attributes() {
+        let text = format!("{}{}a{}{}b{}", Bold, Underline, NoBold, Strikethrough, Reset);
+        assert_eq!(text, "\x1B[1m\x1B[4ma\x1B[22m\x1B[9mb\x1B[0m");
+        assert_eq!(NoItalic::BYTES, b"\x1B[23m");
+    }
+
+    #[test]
+    fn test_policy_apply() {
+        let policy = StylePolicy { blink: false, invisible: false, ..StylePolicy::default() };
+        let state = SgrState { bold: true, blink: true, invisible: true, ..SgrState::default() };
+        assert_eq!(policy.apply(state), SgrState { bold: true, ..SgrState::default() });
+        assert_eq!(StylePolicy::from_bits(policy.to_bits()), policy);
+    }
+}
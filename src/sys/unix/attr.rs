@@ -1,21 +1,40 @@
 use std::{io, mem};
+use std::os::unix::io::RawFd;
 
 use super::{cvt, Termios};
 
-pub fn get_terminal_attr() -> io::Result<Termios> {
+pub fn get_terminal_attr_of(fd: RawFd) -> io::Result<Termios> {
     unsafe {
         let mut termios = mem::zeroed();
-        cvt(libc::tcgetattr(libc::STDOUT_FILENO, &mut termios))?;
+        cvt(libc::tcgetattr(fd, &mut termios))?;
         Ok(termios)
     }
 }
 
-pub fn set_terminal_attr(termios: &Termios) -> io::Result<()> {
+pub fn set_terminal_attr_of(fd: RawFd, termios: &Termios) -> io::Result<()> {
     cvt(unsafe {
-        libc::tcsetattr(libc::STDOUT_FILENO, libc::TCSANOW, termios)
+        libc::tcsetattr(fd, libc::TCSANOW, termios)
     }).and(Ok(()))
 }
 
 pub fn raw_terminal_attr(termios: &mut Termios) {
     unsafe { libc::cfmakeraw(termios) }
 }
+
+pub fn no_echo_terminal_attr(termios: &mut Termios) {
+    // Keep echoing the final newline, so the cursor still moves past the prompt.
+    termios.c_lflag &= !libc::ECHO;
+    termios.c_lflag |= libc::ECHONL;
+}
+
+pub fn no_signal_terminal_attr(termios: &mut Termios) {
+    // ETX reaches the reader as a byte instead of raising SIGINT, and ends the line like a newline
+    // would, so it is read without waiting for Enter.
+    termios.c_lflag &= !libc::ISIG;
+    termios.c_cc[libc::VEOL] = 0x03;
+}
+
+pub fn read_timing_terminal_attr(termios: &mut Termios, vmin: u8, vtime: u8) {
+    termios.c_cc[libc::VMIN] = vmin as libc::cc_t;
+    termios.c_cc[libc::VTIME] = vtime as libc::cc_t;
+}
@@ -0,0 +1,105 @@
+use std::io;
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::OnceLock;
+
+use super::{cvt, libc};
+
+/// The write end of the resize pipe, for the signal handler.
+static RESIZE_WRITE: AtomicI32 = AtomicI32::new(-1);
+
+/// The `SIGWINCH` action in place before `on_resize`, which is called after it.
+static PREVIOUS_ACTION: OnceLock<libc::sigaction> = OnceLock::new();
+
+/// The location of `errno` for the calling thread.
+unsafe fn errno() -> *mut libc::c_int {
+    #[cfg(any(target_os = "linux", target_os = "redox"))]
+    return libc::__errno_location();
+    #[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+    return libc::__errno();
+    #[cfg(not(any(target_os = "linux", target_os = "redox", target_os = "android", target_os = "netbsd",
+                  target_os = "openbsd")))]
+    return libc::__error();
+}
+
+extern "C" fn on_resize(signum: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+    // The interrupted code may be about to read `errno`, which `write` could change.
+    let saved_errno = unsafe { *errno() };
+    let fd = RESIZE_WRITE.load(Ordering::Relaxed);
+    // A full pipe already holds a pending notification, so a failed write loses nothing.
+    unsafe { libc::write(fd, b"R".as_ptr() as *const libc::c_void, 1) };
+
+    // Chain to the handler installed before, so that the rest of the program still sees resizes.
+    if let Some(previous) = PREVIOUS_ACTION.get() {
+        let handler = previous.sa_sigaction;
+        if handler != libc::SIG_DFL && handler != libc::SIG_IGN {
+            unsafe {
+                if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                    let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+                        std::mem::transmute(handler);
+                    handler(signum, info, context);
+                } else {
+                    let handler: extern "C" fn(libc::c_int) = std::mem::transmute(handler);
+                    handler(signum);
+                }
+            }
+        }
+    }
+    unsafe { *errno() = saved_errno };
+}
+
+fn open_resize_pipe() -> io::Result<RawFd> {
+    let mut fds = [0; 2];
+    unsafe {
+        cvt(libc::pipe(fds.as_mut_ptr()))?;
+        for &fd in &fds {
+            cvt(libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC))?;
+            cvt(libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK))?;
+        }
+        RESIZE_WRITE.store(fds[1], Ordering::Relaxed);
+
+        // Keep the current action before replacing it, so the handler never runs without it.
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        cvt(libc::sigaction(libc::SIGWINCH, std::ptr::null(), &mut previous))?;
+        let _ = PREVIOUS_ACTION.set(previous);
+
+        let mut action: libc::sigaction = std::mem::zeroed();
+        let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) = on_resize;
+        action.sa_sigaction = handler as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+        cvt(libc::sigemptyset(&mut action.sa_mask))?;
+        cvt(libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut()))?;
+    }
+    Ok(fds[0])
+}
+
+/// Get a non-blocking pipe receiving a byte whenever the terminal window is resized.
+///
+/// The `SIGWINCH` handler is installed on the first call, and calls any previous one after it.
+/// The pipe is shared by every caller, so each notification is only seen by one reader.
+pub fn resize_pipe() -> io::Result<RawFd> {
+    static PIPE: OnceLock<Result<RawFd, io::ErrorKind>> = OnceLock::new();
+    (*PIPE.get_or_init(|| open_resize_pipe().map_err(|e| e.kind()))).map_err(io::Error::from)
+}
+
+/// Read every pending notification from the resize pipe `fd`.
+pub fn drain_resize_pipe(fd: RawFd) {
+    let mut buf = [0u8; 64];
+    while unsafe { libc::read(fd, buf.as_mut_ptr() as *mut libc::c_void, buf.len()) } > 0 {}
+}
+
+/// Block until `input` can be read or the resize pipe `resize` gets a notification, returning
+/// whether the window was resized.
+pub fn wait_input_or_resize(input: RawFd, resize: RawFd) -> io::Result<bool> {
+    let mut fds = [
+        libc::pollfd { fd: input, events: libc::POLLIN, revents: 0 },
+        libc::pollfd { fd: resize, events: libc::POLLIN, revents: 0 },
+    ];
+    loop {
+        match cvt(unsafe { libc::poll(fds.as_mut_ptr(), 2, -1) }) {
+            Ok(_) => return Ok(fds[1].revents & libc::POLLIN != 0),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
@@ -1,4 +1,5 @@
 use std::{io, mem};
+use std::os::unix::io::RawFd;
 
 use super::cvt;
 use super::libc::{c_ushort, ioctl, STDOUT_FILENO, TIOCGWINSZ};
@@ -10,20 +11,28 @@ struct TermSize {
     x: c_ushort,
     y: c_ushort,
 }
-/// Get the size of the terminal.
-pub fn terminal_size() -> io::Result<(u16, u16)> {
+
+fn term_size_of(fd: RawFd) -> io::Result<TermSize> {
     unsafe {
         let mut size: TermSize = mem::zeroed();
-        cvt(ioctl(STDOUT_FILENO, TIOCGWINSZ.into(), &mut size as *mut _))?;
-        Ok((size.col as u16, size.row as u16))
+        cvt(ioctl(fd, TIOCGWINSZ, &mut size as *mut _))?;
+        Ok(size)
     }
 }
 
+/// Get the size of the terminal.
+pub fn terminal_size() -> io::Result<(u16, u16)> {
+    terminal_size_of(STDOUT_FILENO)
+}
+
+/// Get the size of the terminal behind the file descriptor `fd`.
+pub fn terminal_size_of(fd: RawFd) -> io::Result<(u16, u16)> {
+    let size = term_size_of(fd)?;
+    Ok((size.col as u16, size.row as u16))
+}
+
 /// Get the size of the terminal, in pixels
 pub fn terminal_size_pixels() -> io::Result<(u16, u16)> {
-    unsafe {
-        let mut size: TermSize = mem::zeroed();
-        cvt(ioctl(STDOUT_FILENO, TIOCGWINSZ.into(), &mut size as *mut _))?;
-        Ok((size.x as u16, size.y as u16))
-    }
+    let size = term_size_of(STDOUT_FILENO)?;
+    Ok((size.x as u16, size.y as u16))
 }
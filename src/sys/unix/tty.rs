@@ -1,12 +1,17 @@
 use std::{fs, io};
-use std::os::unix::io::AsRawFd;
+use std::os::unix::io::{AsRawFd, RawFd};
 
 use super::libc;
 
 
 /// Is this stream a TTY?
 pub fn is_tty<T: AsRawFd>(stream: &T) -> bool {
-    unsafe { libc::isatty(stream.as_raw_fd()) == 1 }
+    is_tty_fd(stream.as_raw_fd())
+}
+
+/// Is this file descriptor a TTY?
+pub fn is_tty_fd(fd: RawFd) -> bool {
+    unsafe { libc::isatty(fd) == 1 }
 }
 
 /// Get the TTY device.
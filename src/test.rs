@@ -0,0 +1,217 @@
+//! Helpers for testing programs built on this crate.
+//!
+//! A program that exits, or panics, without undoing the modes it enabled leaves the user's shell
+//! with a hidden cursor or swallowing mouse clicks. Writing to a `Vec<u8>` instead of the terminal
+//! captures everything the program sent, and `assert_terminal_restored` checks it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use pres::cursor::HideCursor;
+//! use pres::input::MouseTerminal;
+//! use std::io::Write;
+//!
+//! let mut out = HideCursor::from(MouseTerminal::from(Vec::new()));
+//! write!(out, "hello").unwrap();
+//! let captured = out.into_inner().unwrap().into_inner().unwrap();
+//! pres::test::assert_terminal_restored(&captured);
+//! ```
+
+use std::cell::Cell;
+use std::fmt::Write;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use crate::clock::Clock;
+
+/// The DEC private modes checked, with their names and whether they are set by default.
+const MODES: &[(u16, &str, bool)] = &[
+    (6, "origin mode", false),
+    (7, "auto-wrap", true),
+    (25, "cursor visible", true),
+    (47, "alternate screen", false),
+    (1000, "mouse reporting", false),
+    (1002, "mouse drag reporting", false),
+    (1003, "mouse motion reporting", false),
+    (1004, "focus reporting", false),
+    (1005, "UTF-8 mouse encoding", false),
+    (1006, "SGR mouse encoding", false),
+    (1007, "alternate scroll", false),
+    (1015, "urxvt mouse encoding", false),
+    (1016, "SGR-Pixels mouse encoding", false),
+    (1047, "alternate screen", false),
+    (1049, "alternate screen", false),
+    (2004, "bracketed paste", false),
+];
+
+/// The terminal state left by some output.
+#[derive(Default)]
+struct State {
+    /// The modes of `MODES` not in their default state, in the order they were changed.
+    modes: Vec<u16>,
+    /// The kitty keyboard enhancement flags pushed and not popped.
+    keyboard_flags: usize,
+    /// Whether xterm's modifyOtherKeys is on.
+    modify_other_keys: bool,
+}
+
+impl State {
+    /// Apply the escape sequence `CSI params final`.
+    fn apply(&mut self, params: &[u8], final_byte: u8) {
+        let (prefix, params) = match params.first() {
+            Some(&c @ (b'?' | b'>' | b'<' | b'=')) => (Some(c), &params[1..]),
+            _ => (None, params),
+        };
+        let nums: Vec<Option<u16>> = params
+            .split(|&c| c == b';')
+            .map(|n| std::str::from_utf8(n).ok()?.parse().ok())
+            .collect();
+
+        match (prefix, final_byte) {
+            (Some(b'?'), b'h' | b'l') => {
+                for mode in nums.into_iter().flatten() {
+                    let Some(&(_, _, default)) = MODES.iter().find(|m| m.0 == mode) else { continue };
+                    self.modes.retain(|&m| m != mode);
+                    if (final_byte == b'h') != default {
+                        self.modes.push(mode);
+                    }
+                }
+            }
+            (Some(b'>'), b'u') => self.keyboard_flags += 1,
+            (Some(b'<'), b'u') => {
+                let count = nums.first().copied().flatten().unwrap_or(1).max(1);
+                self.keyboard_flags = self.keyboard_flags.saturating_sub(count as usize);
+            }
+            (Some(b'>'), b'm') if nums.first() == Some(&Some(4)) => {
+                self.modify_other_keys = nums.get(1).copied().flatten().unwrap_or(0) != 0;
+            }
+            _ => {}
+        }
+    }
+
+    /// Describe what was not restored, if anything.
+    fn unrestored(&self) -> Option<String> {
+        let mut msg = String::new();
+        for &mode in &self.modes {
+            let (_, name, default) = MODES.iter().find(|m| m.0 == mode).unwrap();
+            let state = if *default { "reset" } else { "set" };
+            write!(msg, "\n  {} (?{}) left {}", name, mode, state).unwrap();
+        }
+        if self.keyboard_flags > 0 {
+            write!(msg, "\n  {} keyboard enhancement flags left pushed", self.keyboard_flags).unwrap();
+        }
+        if self.modify_other_keys {
+            msg.push_str("\n  modifyOtherKeys left on");
+        }
+        if msg.is_empty() { None } else { Some(msg) }
+    }
+}
+
+/// Assert that `output`, everything a program wrote to the terminal, undoes every mode it changes.
+///
+/// This covers the cursor visibility, the alternate screen, mouse reporting, bracketed paste,
+/// focus reporting and the keyboard protocols: each enabling sequence must be followed by its
+/// disabling one before the end of `output`, and kitty keyboard enhancement flags must be popped
+/// as many times as they were pushed.
+///
+/// # Panics
+///
+/// If some mode is left changed, listing them.
+pub fn assert_terminal_restored(output: &[u8]) {
+    let mut state = State::default();
+    let mut rest = output;
+    while let Some(start) = rest.windows(2).position(|w| w == b"\x1B[") {
+        rest = &rest[start + 2..];
+        // Parameter bytes, then the final byte; intermediate bytes make it another sequence.
+        let len = rest.iter().take_while(|c| (0x30..=0x3F).contains(*c)).count();
+        match rest.get(len) {
+            Some(&c @ 0x40..=0x7E) => state.apply(&rest[..len], c),
+            _ => continue,
+        }
+        rest = &rest[len + 1..];
+    }
+
+    if let Some(msg) = state.unrestored() {
+        panic!("terminal not restored:{}", msg);
+    }
+}
+
+/// A clock that only moves when told to, for testing timing-dependent input handling.
+///
+/// Clones share the time, so a test keeps one to step the clock given to an adapter.
+///
+/// # Example
+///
+/// ```rust
+/// use pres::clock::Clock;
+/// use pres::test::ManualClock;
+/// use std::time::Duration;
+///
+/// let clock = ManualClock::new();
+/// let start = clock.now();
+/// clock.clone().advance(Duration::from_millis(500));
+/// assert_eq!(clock.now() - start, Duration::from_millis(500));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Rc<Cell<Instant>>,
+}
+
+impl ManualClock {
+    /// A clock stopped at the current time.
+    pub fn new() -> ManualClock {
+        ManualClock { now: Rc::new(Cell::new(Instant::now())) }
+    }
+
+    /// Move the time forward by `by`.
+    pub fn advance(&self, by: Duration) {
+        self.now.set(self.now.get() + by);
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> ManualClock {
+        ManualClock::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        self.now.get()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_restored() {
+        assert_terminal_restored(b"");
+        assert_terminal_restored(b"\x1B[?1049h\x1B[?25l\x1B[>1u\x1B[2J\x1B[?25h\x1B[<u\x1B[?1049l");
+        // Several modes at once, and a pop of several flags.
+        assert_terminal_restored(b"\x1B[?1000;1006h\x1B[>1u\x1B[>3u\x1B[?1006;1000l\x1B[<2u");
+        // Disabling what was never enabled is harmless.
+        assert_terminal_restored(b"\x1B[?1000l\x1B[<u\x1B[>4m");
+        // Cursor restore and the margin bell volume are not keyboard pops.
+        assert_terminal_restored(b"\x1B[>1u\x1B[u\x1B[4 u\x1B[<u");
+    }
+
+    #[test]
+    #[should_panic(expected = "cursor visible (?25) left reset")]
+    fn test_hidden_cursor() {
+        assert_terminal_restored(b"\x1B[?25l\x1B[?25h\x1B[?25l");
+    }
+
+    #[test]
+    #[should_panic(expected = "1 keyboard enhancement flags left pushed")]
+    fn test_keyboard_flags() {
+        assert_terminal_restored(b"\x1B[>1u\x1B[>1u\x1B[<u");
+    }
+
+    #[test]
+    #[should_panic(expected = "modifyOtherKeys left on")]
+    fn test_modify_other_keys() {
+        assert_terminal_restored(b"\x1B[>4;2m");
+    }
+}
@@ -0,0 +1,127 @@
+//! Text measurement.
+
+use std::io::{self, Error, Read, Write};
+use std::sync::atomic::{AtomicU8, Ordering};
+
+use crate::clear;
+use crate::cursor::{self, Goto};
+
+/// Measure how many columns the terminal uses to display `s`.
+///
+/// This prints `s`, asks the terminal where the cursor ended up and erases the text again. Emoji,
+/// ZWJ sequences and ambiguous-width characters are rendered differently by different emulators,
+/// so asking the terminal itself is the only reliable way to know their width; it is slow, and
+/// best done once at startup to calibrate.
+///
+/// `output` and `input` should belong to the same terminal, in raw mode. A read timeout (see
+/// `raw::RawModeBuilder::vtime`) avoids waiting forever for a terminal that does not answer.
+///
+/// `s` should not contain control characters. Text that wraps to the next line is an error, since
+/// its width cannot be told from the cursor position.
+pub fn measure_on_terminal<W: Write, R: Read>(output: &mut W, input: &mut R, s: &str) -> io::Result<u16> {
+    write!(output, "\x1B[6n")?;
+    output.flush()?;
+    let (x0, y0) = cursor::read_cursor_report(input)?;
+
+    write!(output, "{}\x1B[6n", s)?;
+    output.flush()?;
+    let (x1, y1) = cursor::read_cursor_report(input)?;
+
+    write!(output, "{}{}", Goto(x0, y0), clear::UntilNewline)?;
+    output.flush()?;
+
+    if y1 != y0 || x1 < x0 {
+        return Err(Error::other("Measured text wrapped to another line."));
+    }
+    Ok(x1 - x0)
+}
+
+/// How wide East Asian ambiguous-width characters, such as `○`, `§` or Greek letters, are
+/// displayed.
+///
+/// These take one column in most terminals, and two in CJK locales or when the terminal is set to
+/// do so. Code laying out text should count them with the process-wide policy, from
+/// `ambiguous_width`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Hash)]
+pub enum AmbiguousWidth {
+    /// One column.
+    #[default]
+    Narrow,
+    /// Two columns.
+    Wide,
+}
+
+impl AmbiguousWidth {
+    /// The number of columns an ambiguous-width character takes.
+    pub fn columns(self) -> u16 {
+        match self {
+            AmbiguousWidth::Narrow => 1,
+            AmbiguousWidth::Wide => 2,
+        }
+    }
+}
+
+/// The process-wide policy, as the `AmbiguousWidth` discriminant.
+static AMBIGUOUS_WIDTH: AtomicU8 = AtomicU8::new(AmbiguousWidth::Narrow as u8);
+
+/// Replace the process-wide ambiguous-width policy, e.g. from a setting of the program.
+pub fn set_ambiguous_width(width: AmbiguousWidth) {
+    AMBIGUOUS_WIDTH.store(width as u8, Ordering::Relaxed);
+}
+
+/// Get the process-wide ambiguous-width policy: `Narrow` unless set or probed.
+pub fn ambiguous_width() -> AmbiguousWidth {
+    match AMBIGUOUS_WIDTH.load(Ordering::Relaxed) {
+        0 => AmbiguousWidth::Narrow,
+        _ => AmbiguousWidth::Wide,
+    }
+}
+
+/// Find how wide the terminal displays ambiguous-width characters and make it the process-wide
+/// policy.
+///
+/// This measures one with `measure_on_terminal`, with the same requirements on `output` and
+/// `input`. On an error, the policy is left as it was.
+pub fn probe_ambiguous_width<W: Write, R: Read>(output: &mut W, input: &mut R) -> io::Result<AmbiguousWidth> {
+    let width = match measure_on_terminal(output, input, "\u{25CB}")? {
+        1 => AmbiguousWidth::Narrow,
+        2 => AmbiguousWidth::Wide,
+        columns => {
+            return Err(Error::other(format!("Ambiguous-width character measured as {} columns.", columns)));
+        }
+    };
+    set_ambiguous_width(width);
+    Ok(width)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_measure_on_terminal() {
+        let mut output = Vec::new();
+        let mut input = io::Cursor::new(b"\x1B[5;3R\x1B[5;5R".to_vec());
+        assert_eq!(measure_on_terminal(&mut output, &mut input, "👍").unwrap(), 2);
+        assert_eq!(output, "\x1B[6n👍\x1B[6n\x1B[5;3H\x1B[K".as_bytes());
+
+        let mut wrapped = io::Cursor::new(b"\x1B[5;79R\x1B[6;1R".to_vec());
+        assert!(measure_on_terminal(&mut Vec::new(), &mut wrapped, "ab").is_err());
+    }
+
+    #[test]
+    fn test_probe_ambiguous_width() {
+        let mut output = Vec::new();
+        let mut input = io::Cursor::new(b"\x1B[1;1R\x1B[1;3R".to_vec());
+        assert_eq!(probe_ambiguous_width(&mut output, &mut input).unwrap(), AmbiguousWidth::Wide);
+        assert_eq!(ambiguous_width(), AmbiguousWidth::Wide);
+        assert_eq!(output, "\x1B[6n\u{25CB}\x1B[6n\x1B[1;1H\x1B[K".as_bytes());
+
+        // A terminal that does not answer leaves the policy alone.
+        assert!(probe_ambiguous_width(&mut Vec::new(), &mut io::empty()).is_err());
+        assert_eq!(ambiguous_width(), AmbiguousWidth::Wide);
+
+        set_ambiguous_width(AmbiguousWidth::Narrow);
+        assert_eq!(ambiguous_width(), AmbiguousWidth::Narrow);
+    }
+}
@@ -0,0 +1,24 @@
+//! Mode guards in batch mode.
+//!
+//! This sets the process-wide batch policy, so it runs in its own test binary.
+
+use std::io::Write;
+
+use pres::batch::{self, BatchPolicy};
+use pres::cursor::HideCursor;
+use pres::input::{AlternateScroll, MouseTerminal};
+use pres::raw::IntoRawMode;
+
+#[test]
+fn test_guards_are_no_ops() {
+    batch::set_policy(BatchPolicy::Batch);
+    assert!(batch::is_batch());
+
+    let raw = Vec::new().into_raw_mode().unwrap();
+    let mut out = HideCursor::from(AlternateScroll::from(MouseTerminal::from(raw)));
+    write!(out, "plain").unwrap();
+    out.suspend_raw_mode().unwrap();
+
+    let raw = out.into_inner().unwrap().into_inner().unwrap().into_inner().unwrap();
+    assert_eq!(*raw, b"plain");
+}
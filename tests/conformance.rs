@@ -0,0 +1,249 @@
+//! Runs the input conformance corpus in `tests/corpus.json`.
+//!
+//! The corpus is plain data so forks and downstream crates can load it into their own parsers.
+//! When fixing how a terminal's input is parsed, add a case for it there.
+
+use pres::event::{
+    Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeypadKey, MediaKey, MouseAction, MouseButton,
+    MouseEvent, MouseEventEx, ParseOptions, Paste,
+};
+use pres::input::TermRead;
+use serde_json::Value;
+
+fn input_of(case: &Value) -> Vec<u8> {
+    if let Some(input) = case["input"].as_str() {
+        return input.as_bytes().to_vec();
+    }
+    case["bytes"]
+        .as_array()
+        .expect("a case needs `input` or `bytes`")
+        .iter()
+        .map(|b| b.as_u64().expect("bytes are numbers") as u8)
+        .collect()
+}
+
+fn options_of(case: &Value) -> ParseOptions {
+    let enabled = |name: &str| case["options"][name].as_bool().unwrap_or(false);
+    ParseOptions {
+        shell_integration: enabled("shell_integration"),
+        c1_controls: enabled("c1_controls"),
+        normalize_newlines: enabled("normalize_newlines"),
+        coalesce_text: enabled("coalesce_text"),
+        max_sequence_len: case["options"]["max_sequence_len"].as_u64().map(|len| len as usize),
+        max_paste_len: case["options"]["max_paste_len"].as_u64().map(|len| len as usize),
+        paste_chunk_len: case["options"]["paste_chunk_len"].as_u64().map(|len| len as usize),
+        resync: enabled("resync"),
+        sgr_pixels: case["options"]["sgr_pixels"].as_array().map(|size| {
+            (size[0].as_u64().unwrap() as u16, size[1].as_u64().unwrap() as u16)
+        }),
+    }
+}
+
+fn number(value: &Value) -> u16 {
+    value.as_u64().unwrap_or_else(|| panic!("not a number: {}", value)) as u16
+}
+
+fn modifiers_of(event: &Value) -> KeyModifiers {
+    let Some(mods) = event["mods"].as_array() else { return KeyModifiers::NONE };
+    mods.iter().fold(KeyModifiers::NONE, |all, name| {
+        all | match name.as_str().unwrap() {
+            "shift" => KeyModifiers::SHIFT,
+            "alt" => KeyModifiers::ALT,
+            "ctrl" => KeyModifiers::CONTROL,
+            "super" => KeyModifiers::SUPER,
+            "hyper" => KeyModifiers::HYPER,
+            "meta" => KeyModifiers::META,
+            "caps-lock" => KeyModifiers::CAPS_LOCK,
+            "num-lock" => KeyModifiers::NUM_LOCK,
+            other => panic!("unknown modifier: {}", other),
+        }
+    })
+}
+
+fn media_key_of(name: &str) -> MediaKey {
+    match name {
+        "play" => MediaKey::Play,
+        "pause" => MediaKey::Pause,
+        "play-pause" => MediaKey::PlayPause,
+        "reverse" => MediaKey::Reverse,
+        "stop" => MediaKey::Stop,
+        "fast-forward" => MediaKey::FastForward,
+        "rewind" => MediaKey::Rewind,
+        "track-next" => MediaKey::TrackNext,
+        "track-previous" => MediaKey::TrackPrevious,
+        "record" => MediaKey::Record,
+        "lower-volume" => MediaKey::LowerVolume,
+        "raise-volume" => MediaKey::RaiseVolume,
+        "mute-volume" => MediaKey::MuteVolume,
+        other => panic!("unknown media key: {}", other),
+    }
+}
+
+fn keypad_key_of(name: &str) -> KeypadKey {
+    if let Some(n) = name.parse().ok().filter(|&n| n <= 9) {
+        return KeypadKey::Digit(n);
+    }
+    match name {
+        "decimal" => KeypadKey::Decimal,
+        "divide" => KeypadKey::Divide,
+        "multiply" => KeypadKey::Multiply,
+        "subtract" => KeypadKey::Subtract,
+        "add" => KeypadKey::Add,
+        "enter" => KeypadKey::Enter,
+        "equal" => KeypadKey::Equal,
+        "separator" => KeypadKey::Separator,
+        other => match key_code_of(other) {
+            KeyCode::Left => KeypadKey::Left,
+            KeyCode::Right => KeypadKey::Right,
+            KeyCode::Up => KeypadKey::Up,
+            KeyCode::Down => KeypadKey::Down,
+            KeyCode::PageUp => KeypadKey::PageUp,
+            KeyCode::PageDown => KeypadKey::PageDown,
+            KeyCode::Home => KeypadKey::Home,
+            KeyCode::End => KeypadKey::End,
+            KeyCode::Insert => KeypadKey::Insert,
+            KeyCode::Delete => KeypadKey::Delete,
+            _ => panic!("unknown keypad key: {}", name),
+        },
+    }
+}
+
+fn key_code_of(name: &str) -> KeyCode {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        return KeyCode::Char(c);
+    }
+    if let Some(media) = name.strip_prefix("media-") {
+        return KeyCode::Media(media_key_of(media));
+    }
+    if let Some(keypad) = name.strip_prefix("kp-") {
+        return KeyCode::Keypad(keypad_key_of(keypad));
+    }
+    if let Some(n) = name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+        return KeyCode::F(n);
+    }
+    match name {
+        "backspace" => KeyCode::Backspace,
+        "enter" => KeyCode::Enter,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "page-up" => KeyCode::PageUp,
+        "page-down" => KeyCode::PageDown,
+        "tab" => KeyCode::Tab,
+        "back-tab" => KeyCode::BackTab,
+        "delete" => KeyCode::Delete,
+        "insert" => KeyCode::Insert,
+        "esc" => KeyCode::Esc,
+        other => panic!("unknown key: {}", other),
+    }
+}
+
+fn button_of(button: &Value) -> MouseButton {
+    match button.as_str() {
+        Some("left") => MouseButton::Left,
+        Some("right") => MouseButton::Right,
+        Some("middle") => MouseButton::Middle,
+        Some("wheel-up") => MouseButton::WheelUp,
+        Some("wheel-down") => MouseButton::WheelDown,
+        Some("back") => MouseButton::Back,
+        Some("forward") => MouseButton::Forward,
+        Some(other) => panic!("unknown mouse button: {}", other),
+        None => MouseButton::Unknown(number(button) as u8),
+    }
+}
+
+fn mouse_event_of(event: &Value, kind: &str) -> MouseEvent {
+    let (x, y) = (number(&event["x"]), number(&event["y"]));
+    let button = (!event["button"].is_null()).then(|| button_of(&event["button"]));
+    let action = match kind {
+        "press" => MouseAction::Press(button.expect("a press needs a `button`")),
+        "release" => MouseAction::Release(button),
+        "hold" => MouseAction::Hold,
+        "moved" => MouseAction::Moved,
+        other => panic!("unknown mouse event: {}", other),
+    };
+    if let Some(pixels) = event["pixels"].as_array() {
+        return MouseEvent::Pixels { action, x: number(&pixels[0]), y: number(&pixels[1]), column: x, row: y };
+    }
+    match action {
+        MouseAction::Press(button) => MouseEvent::Press(button, x, y),
+        MouseAction::Release(button) => MouseEvent::Release(button, x, y),
+        MouseAction::Hold => MouseEvent::Hold(x, y),
+        MouseAction::Moved => MouseEvent::Moved(x, y),
+    }
+}
+
+fn bytes_of(value: &Value) -> Vec<u8> {
+    value.as_array().unwrap().iter().map(|b| number(b) as u8).collect()
+}
+
+/// Build the event a corpus entry describes, see the corpus description for the schema.
+fn event_of(event: &Value) -> Event {
+    if let Some(key) = event["key"].as_str() {
+        let kind = match event["kind"].as_str() {
+            None | Some("press") => KeyEventKind::Press,
+            Some("repeat") => KeyEventKind::Repeat,
+            Some("release") => KeyEventKind::Release,
+            Some(other) => panic!("unknown key event kind: {}", other),
+        };
+        let key = KeyEvent::new(key_code_of(key), modifiers_of(event)).with_kind(kind);
+        Event::Key(match event["base"].as_str() {
+            Some(base) => key.with_base_layout(key_code_of(base)),
+            None => key,
+        })
+    } else if let Some(kind) = event["mouse"].as_str() {
+        Event::Mouse(MouseEventEx { event: mouse_event_of(event, kind), modifiers: modifiers_of(event) })
+    } else if let Some(text) = event["paste"].as_str() {
+        let text = text.to_string();
+        let dropped = event["dropped"].as_u64().map(|dropped| dropped as usize);
+        match (event["part"].as_str(), dropped) {
+            (Some("chunk"), _) => Event::PastePart(Paste::Chunk(text)),
+            (Some("last"), dropped) => Event::PastePart(Paste::Last { text, dropped: dropped.unwrap_or(0) }),
+            (Some(other), _) => panic!("unknown paste part: {}", other),
+            (None, Some(dropped)) => Event::PasteTruncated { text, dropped },
+            (None, None) => Event::Paste(text),
+        }
+    } else if let Some(text) = event["text"].as_str() {
+        Event::Text(text.to_string())
+    } else if let Some(size) = event["resize"].as_array() {
+        Event::Resize(number(&size[0]), number(&size[1]))
+    } else if event["csi"].is_object() {
+        let csi = &event["csi"];
+        Event::UnknownCsi {
+            params: csi["params"].as_array().unwrap().iter().map(number).collect(),
+            intermediates: csi["intermediates"].as_str().unwrap().as_bytes().to_vec(),
+            final_byte: csi["final"].as_str().unwrap().as_bytes()[0],
+        }
+    } else if event["unsupported"].is_array() {
+        Event::Unsupported(bytes_of(&event["unsupported"]))
+    } else {
+        panic!("unknown event: {}", event)
+    }
+}
+
+#[test]
+fn test_corpus() {
+    let corpus: Value = serde_json::from_str(include_str!("corpus.json")).unwrap();
+    let mut failures = Vec::new();
+
+    for case in corpus["cases"].as_array().unwrap() {
+        let name = case["name"].as_str().unwrap();
+        let expected: Vec<Event> = case["events"].as_array().unwrap().iter().map(event_of).collect();
+        let actual: Vec<Event> = input_of(case)
+            .as_slice()
+            .events()
+            .with_options(options_of(case))
+            .map(|event| event.unwrap())
+            .collect();
+
+        if actual != expected {
+            failures.push(format!("{} ({}): expected {:?}, got {:?}", name, case["terminal"], expected, actual));
+        }
+    }
+
+    assert!(failures.is_empty(), "corpus mismatches:\n{}", failures.join("\n"));
+}
@@ -0,0 +1,196 @@
+//! What the mode guards write.
+//!
+//! This sets the process-wide batch policy, so it runs in its own test binary.
+
+use std::io::{self, Write};
+
+use pres::batch::{self, BatchPolicy};
+use pres::cursor::HideCursor;
+use pres::event::{Event, Key};
+use pres::input::{AlternateScroll, BracketedPaste, KeyboardEnhancement, KeyboardEnhancementFlags,
+                  KeypadApplicationMode, ModifyOtherKeys, MouseEncoding, MouseTerminal, MouseTracking,
+                  TermRead};
+
+const ENTER_MOUSE_SEQUENCE: &str = "\x1B[?1000h\x1B[?1002h\x1B[?1015h\x1B[?1006h";
+const EXIT_MOUSE_SEQUENCE: &str = "\x1B[?1006l\x1B[?1015l\x1B[?1002l\x1B[?1000l";
+
+#[test]
+fn test_events_outlive_output_guards() {
+    // Render finished, await final keypress: the output modes are restored while the input
+    // iterator, which only owns the reader, keeps going.
+    batch::set_policy(BatchPolicy::Interactive);
+    let mut events = b"q".events();
+    let mut screen = HideCursor::from(MouseTerminal::buffered(Vec::new()));
+    write!(screen, "frame").unwrap();
+    let output = screen.into_inner().unwrap().into_inner().unwrap();
+
+    let expected = [ENTER_MOUSE_SEQUENCE.as_bytes(), b"\x1B[?25lframe\x1B[?25h",
+                    EXIT_MOUSE_SEQUENCE.as_bytes()].concat();
+    assert_eq!(output, expected);
+    assert_eq!(events.next().unwrap().unwrap(), Event::Key(Key::Char('q').into()));
+
+    let scroll = AlternateScroll::from(Vec::new());
+    assert_eq!(scroll.into_inner().unwrap(), b"\x1B[?1007h\x1B[?1007l");
+
+    let flags = KeyboardEnhancementFlags::DISAMBIGUATE_ESCAPE_CODES
+        | KeyboardEnhancementFlags::REPORT_ALTERNATE_KEYS;
+    let keyboard = KeyboardEnhancement::with_flags(Vec::new(), flags).unwrap();
+    assert_eq!(keyboard.into_inner().unwrap(), b"\x1B[>5u\x1B[<u");
+
+    let other_keys = ModifyOtherKeys::from(Vec::new());
+    assert_eq!(other_keys.into_inner().unwrap(), b"\x1B[>4;2m\x1B[>4m");
+
+    let paste = BracketedPaste::from(Vec::new());
+    assert_eq!(paste.into_inner().unwrap(), b"\x1B[?2004h\x1B[?2004l");
+
+    let keypad = KeypadApplicationMode::from(Vec::new());
+    assert_eq!(keypad.into_inner().unwrap(), b"\x1B=\x1B>");
+}
+
+/// A writer counting the writes it receives.
+#[derive(Default)]
+struct CountingWriter {
+    writes: usize,
+    bytes: Vec<u8>,
+}
+
+impl Write for CountingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writes += 1;
+        self.bytes.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mouse_terminal_buffering() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let mut unbuffered = MouseTerminal::from(CountingWriter::default());
+    for _ in 0..10 {
+        unbuffered.write_all(b"x").unwrap();
+    }
+    assert_eq!(unbuffered.writes, 11);
+
+    let mut buffered = MouseTerminal::buffered(CountingWriter::default());
+    for _ in 0..10 {
+        buffered.write_all(b"x").unwrap();
+    }
+    assert_eq!(buffered.writes, 0);
+    buffered.flush().unwrap();
+    assert_eq!(buffered.writes, 1);
+    assert_eq!(buffered.bytes, [ENTER_MOUSE_SEQUENCE.as_bytes(), b"xxxxxxxxxx"].concat());
+}
+
+#[test]
+fn test_mouse_terminal_pixels() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let term = MouseTerminal::from(Vec::new()).pixel_coordinates().track_motion();
+    let out = term.into_inner().unwrap();
+    let expected = [ENTER_MOUSE_SEQUENCE, "\x1B[?1016h\x1B[?1003h", "\x1B[?1003l\x1B[?1016l",
+        EXIT_MOUSE_SEQUENCE].concat();
+    assert_eq!(out, expected.as_bytes());
+}
+
+#[test]
+fn test_mouse_terminal_builder() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let term = MouseTerminal::builder()
+        .tracking(MouseTracking::Clicks)
+        .encoding(MouseEncoding::Sgr)
+        .build(Vec::new())
+        .unwrap();
+    assert_eq!(term.into_inner().unwrap(), b"\x1B[?1000h\x1B[?1006h\x1B[?1006l\x1B[?1000l");
+
+    // Any-motion tracking is not enabled twice.
+    let term = MouseTerminal::builder()
+        .tracking(MouseTracking::AnyMotion)
+        .encoding(MouseEncoding::X10)
+        .build(Vec::new())
+        .unwrap()
+        .track_motion();
+    assert_eq!(term.into_inner().unwrap(), b"\x1B[?1000h\x1B[?1003h\x1B[?1003l\x1B[?1000l");
+}
+
+#[test]
+fn test_mouse_terminal_motion() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let term = MouseTerminal::from(Vec::new()).track_motion().track_motion();
+    let out = term.into_inner().unwrap();
+    let expected = [ENTER_MOUSE_SEQUENCE, "\x1B[?1003h", "\x1B[?1003l", EXIT_MOUSE_SEQUENCE].concat();
+    assert_eq!(out, expected.as_bytes());
+}
+
+#[test]
+fn test_mouse_terminal_suspend() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let mut term = MouseTerminal::new(Vec::new()).unwrap();
+    term.suspend().unwrap();
+    term.suspend().unwrap();
+    let mut term = term.track_motion();
+    term.resume().unwrap();
+    term.suspend().unwrap();
+    // Already disabled, so not again.
+    let out = term.into_inner().unwrap();
+    let expected = [ENTER_MOUSE_SEQUENCE, EXIT_MOUSE_SEQUENCE, ENTER_MOUSE_SEQUENCE, "\x1B[?1003h",
+        "\x1B[?1003l", EXIT_MOUSE_SEQUENCE].concat();
+    assert_eq!(out, expected.as_bytes());
+}
+
+/// A writer whose output was closed.
+struct ClosedWriter;
+
+impl Write for ClosedWriter {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::ErrorKind::BrokenPipe.into())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mouse_terminal_closed() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let err = MouseTerminal::new(ClosedWriter).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+    // Neither enabling more modes nor dropping panics.
+    let term = MouseTerminal::buffered(ClosedWriter).track_motion();
+    drop(term);
+}
+
+/// A writer whose output can be closed after the fact.
+#[derive(Default)]
+struct ClosingWriter {
+    closed: bool,
+}
+
+impl Write for ClosingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.closed { Err(io::ErrorKind::BrokenPipe.into()) } else { Ok(buf.len()) }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_mode_guards_closed() {
+    batch::set_policy(BatchPolicy::Interactive);
+    let err = BracketedPaste::new(ClosedWriter).err().unwrap();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    assert!(KeyboardEnhancement::with_flags(ClosedWriter, KeyboardEnhancementFlags::default()).is_err());
+
+    // Dropping a guard whose output was closed meanwhile does not panic.
+    let mut paste = BracketedPaste::new(ClosingWriter::default()).unwrap();
+    paste.closed = true;
+    drop(paste);
+    let mut scroll = AlternateScroll::new(ClosingWriter::default()).unwrap();
+    scroll.closed = true;
+    assert!(scroll.into_inner().is_err());
+}